@@ -0,0 +1,215 @@
+// comtrade_rust/src/archive.rs
+// This file contains discovery and extraction of COMTRADE recordings from ZIP archives.
+// This file exists because relays and DFR software export recordings as ZIP files, often several per archive.
+// RELEVANT FILES: comtrade_rust/src/lib.rs, app/src/lib/components/Upload.svelte
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+
+use crate::{RecordingFiles, WasmComtradeError};
+
+/// A recording found inside a ZIP archive, grouped by file stem.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ZipRecording {
+    /// The path of the recording inside the archive without the extension
+    /// (e.g., "2024/FAULT_001"). Pass this back to select the recording.
+    pub name: String,
+    /// The path of the .cfg file, if present.
+    pub cfg: Option<String>,
+    /// The path of the .dat file, if present.
+    pub dat: Option<String>,
+    /// The path of the .hdr file, if present.
+    pub hdr: Option<String>,
+    /// The path of the .inf file, if present.
+    pub inf: Option<String>,
+    /// The path of the .cff file, if present.
+    pub cff: Option<String>,
+    /// Whether the recording can be parsed, i.e. it has a CFF file or both a CFG and a DAT file.
+    pub complete: bool,
+}
+
+/// The largest file read out of an archive. The sizes an archive declares are
+/// not trusted, so a crafted archive cannot exhaust the WASM memory.
+const MAX_ENTRY_BYTES: u64 = 256 * 1024 * 1024;
+
+fn archive_error(e: impl std::fmt::Display) -> WasmComtradeError {
+    WasmComtradeError::ArchiveError(e.to_string())
+}
+
+fn open_archive(zip_data: &[u8]) -> Result<zip::ZipArchive<Cursor<&[u8]>>, WasmComtradeError> {
+    zip::ZipArchive::new(Cursor::new(zip_data)).map_err(archive_error)
+}
+
+/// Reads at most `limit` bytes of an archive entry, failing if there are more.
+/// The buffer grows with the bytes actually read, not the declared size.
+fn read_limited(reader: impl Read, path: &str, limit: u64) -> Result<Vec<u8>, WasmComtradeError> {
+    let mut bytes = Vec::new();
+    reader
+        .take(limit + 1)
+        .read_to_end(&mut bytes)
+        .map_err(archive_error)?;
+    if bytes.len() as u64 > limit {
+        return Err(WasmComtradeError::ArchiveError(format!(
+            "'{}' is larger than the {} MB limit for a file in an archive.",
+            path,
+            limit / (1024 * 1024)
+        )));
+    }
+    Ok(bytes)
+}
+
+/// Lists the COMTRADE recordings inside a ZIP archive, sorted by name.
+///
+/// Files are grouped case-insensitively by their path without extension, so
+/// `REC.CFG` and `rec.dat` belong to the same recording. Directories, macOS
+/// resource forks and files with other extensions are skipped.
+pub fn list_recordings(zip_data: &[u8]) -> Result<Vec<ZipRecording>, WasmComtradeError> {
+    let archive = open_archive(zip_data)?;
+    let mut recordings: BTreeMap<String, ZipRecording> = BTreeMap::new();
+
+    for path in archive.file_names() {
+        if path.ends_with('/') || path.starts_with("__MACOSX/") {
+            continue;
+        }
+        let Some((stem, extension)) = path.rsplit_once('.') else {
+            continue;
+        };
+        if stem.rsplit('/').next().is_some_and(|file| file.is_empty()) {
+            continue;
+        }
+
+        let extension = extension.to_lowercase();
+        if !matches!(extension.as_str(), "cfg" | "dat" | "hdr" | "inf" | "cff") {
+            continue;
+        }
+
+        let recording = recordings
+            .entry(stem.to_lowercase())
+            .or_insert_with(|| ZipRecording {
+                name: stem.to_string(),
+                ..Default::default()
+            });
+        let slot = match extension.as_str() {
+            "cfg" => &mut recording.cfg,
+            "dat" => &mut recording.dat,
+            "hdr" => &mut recording.hdr,
+            "inf" => &mut recording.inf,
+            _ => &mut recording.cff,
+        };
+        *slot = Some(path.to_string());
+    }
+
+    Ok(recordings
+        .into_values()
+        .map(|mut recording| {
+            recording.complete =
+                recording.cff.is_some() || (recording.cfg.is_some() && recording.dat.is_some());
+            recording
+        })
+        .collect())
+}
+
+/// Extracts the files of one recording from a ZIP archive.
+///
+/// `name` is matched case-insensitively against `ZipRecording::name`. When
+/// `name` is `None` the archive must contain exactly one complete recording.
+pub fn read_recording(
+    zip_data: &[u8],
+    name: Option<&str>,
+) -> Result<RecordingFiles, WasmComtradeError> {
+    let recordings = list_recordings(zip_data)?;
+    let complete: Vec<&ZipRecording> = recordings.iter().filter(|r| r.complete).collect();
+
+    let recording = match name {
+        Some(name) => complete
+            .iter()
+            .find(|r| r.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                WasmComtradeError::InvalidFileCombination(format!(
+                    "No complete recording named '{}' in the archive.",
+                    name
+                ))
+            })?,
+        None => match complete.as_slice() {
+            [recording] => recording,
+            [] => {
+                return Err(WasmComtradeError::InvalidFileCombination(
+                    "The archive does not contain a CFF file or a CFG and DAT file pair."
+                        .to_string(),
+                ));
+            }
+            _ => {
+                let names: Vec<&str> = complete.iter().map(|r| r.name.as_str()).collect();
+                return Err(WasmComtradeError::InvalidFileCombination(format!(
+                    "The archive contains several recordings, select one of: {}.",
+                    names.join(", ")
+                )));
+            }
+        },
+    };
+
+    let mut archive = open_archive(zip_data)?;
+    let mut read = |path: &Option<String>| -> Result<Option<Vec<u8>>, WasmComtradeError> {
+        let Some(path) = path else {
+            return Ok(None);
+        };
+        let file = archive.by_name(path).map_err(archive_error)?;
+        read_limited(file, path, MAX_ENTRY_BYTES).map(Some)
+    };
+
+    Ok(RecordingFiles {
+        cfg: read(&recording.cfg)?,
+        dat: read(&recording.dat)?,
+        cff: read(&recording.cff)?,
+        hdr: read(&recording.hdr)?,
+        inf: read(&recording.inf)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn build_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in files {
+            writer
+                .start_file(*path, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_list_and_read_recordings() {
+        let zip_data = build_zip(&[
+            ("rec1/FAULT.CFG", b"cfg"),
+            ("rec1/fault.dat", b"dat"),
+            ("rec1/FAULT.HDR", b"hdr"),
+            ("rec2.cff", b"cff"),
+            ("notes.txt", b"ignored"),
+            ("orphan.cfg", b"cfg"),
+        ]);
+
+        let recordings = list_recordings(&zip_data).unwrap();
+        let names: Vec<&str> = recordings.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["orphan", "rec1/FAULT", "rec2"]);
+        assert!(!recordings[0].complete);
+        assert!(recordings[1].complete);
+
+        assert!(read_recording(&zip_data, None).is_err());
+
+        let files = read_recording(&zip_data, Some("rec1/fault")).unwrap();
+        assert_eq!(files.dat.as_deref(), Some(&b"dat"[..]));
+        assert_eq!(files.hdr.as_deref(), Some(&b"hdr"[..]));
+        assert!(files.cff.is_none());
+
+        // Entries up to the limit are read; larger ones are refused.
+        let bytes = read_limited(&b"dat"[..], "a.dat", 3).unwrap();
+        assert_eq!(bytes, b"dat");
+        assert!(read_limited(&b"data"[..], "a.dat", 3).is_err());
+    }
+}
@@ -0,0 +1,590 @@
+// comtrade_rust/src/breaker.rs
+// This file contains the breaker operating time and auto-reclose sequence analysis.
+// This file exists so trip and close commands, 52a/52b contacts and current interruption are correlated into per-pole timings.
+// RELEVANT FILES: comtrade_rust/src/phases.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::{nominal_frequency, sample_rate_at, samples_per_cycle};
+use crate::phases::{channel_phase, phase_number, three_phase_set};
+use crate::{SerializableAnalogChannel, SerializableDigitalChannel};
+
+/// The current interruption threshold as a fraction of the peak current in
+/// the cycle before the trip command.
+const CURRENT_THRESHOLD_FRACTION: f64 = 0.05;
+
+const POLE_NAMES: [&str; 3] = ["A", "B", "C"];
+
+/// Options for the breaker analysis. Every field is optional; channels not
+/// given are found by name.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct BreakerOptions {
+    /// Positions in `digital_channels` of the trip command(s). Defaults to the
+    /// channels whose name contains "trip".
+    pub trip_channels: Option<Vec<usize>>,
+    /// Positions in `digital_channels` of the close command(s). Defaults to
+    /// the channels whose name contains "close" (but not "closed").
+    pub close_channels: Option<Vec<usize>>,
+    /// Positions in `digital_channels` of the 52a contacts (closed when the
+    /// breaker is closed). Defaults to the channels whose name contains "52a".
+    pub a_contacts: Option<Vec<usize>>,
+    /// Positions in `digital_channels` of the 52b contacts (closed when the
+    /// breaker is open). Defaults to the channels whose name contains "52b".
+    pub b_contacts: Option<Vec<usize>>,
+    /// Positions in `analog_channels` of the pole currents. Defaults to the A,
+    /// B and C current channels, or to every current channel.
+    pub current_channels: Option<Vec<usize>>,
+    /// The current below which a pole counts as interrupted, in primary
+    /// amperes. Defaults to 5 % of the peak current before the trip command.
+    pub current_threshold: Option<f64>,
+}
+
+/// The opening of one pole during a trip operation. Times are Unix seconds
+/// and durations are seconds.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PoleOperation {
+    /// The pole ("A", "B", "C") or the current channel name.
+    pub pole: String,
+    /// The current channel of the pole, if any.
+    pub current_channel: Option<String>,
+    /// When the auxiliary contacts changed state after the trip command.
+    pub contact_parting_time: Option<f64>,
+    /// When the pole current fell below the threshold for good.
+    pub arc_extinction_time: Option<f64>,
+    /// From the trip command to contact parting.
+    pub opening_time: Option<f64>,
+    /// From contact parting to arc extinction.
+    pub arcing_time: Option<f64>,
+    /// From the trip command to arc extinction.
+    pub clearing_time: Option<f64>,
+}
+
+/// An auto-reclose following a trip operation. Times are Unix seconds and
+/// durations are seconds.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecloseAttempt {
+    /// The close command after the trip, if a close channel was found.
+    pub close_command_time: Option<f64>,
+    /// When the auxiliary contacts returned to the closed state.
+    pub contact_closing_time: Option<f64>,
+    /// When current flowed again on any pole.
+    pub current_restored_time: Option<f64>,
+    /// From arc extinction on the last pole (or contact parting) to current
+    /// restoration (or contact closing).
+    pub dead_time: Option<f64>,
+    /// False when the breaker tripped again later in the recording.
+    pub successful: bool,
+}
+
+/// One trip operation of the breaker.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BreakerOperation {
+    /// The rising edge of the trip command as Unix seconds.
+    pub trip_command_time: f64,
+    /// True if the trip command was already asserted at the first sample, so the
+    /// command time is the start of the recording and the times below are lower bounds.
+    pub asserted_at_start: bool,
+    /// The opening of each pole.
+    pub poles: Vec<PoleOperation>,
+    /// The longest clearing time of any pole, in seconds.
+    pub clearing_time: Option<f64>,
+    /// The reclose that followed this trip, if any.
+    pub reclose: Option<RecloseAttempt>,
+}
+
+/// The breaker operations found in a recording.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BreakerAnalysis {
+    /// The names of the channels used for each role.
+    pub trip_channels: Vec<String>,
+    pub close_channels: Vec<String>,
+    pub a_contacts: Vec<String>,
+    pub b_contacts: Vec<String>,
+    pub current_channels: Vec<String>,
+    /// The trip operations in time order.
+    pub operations: Vec<BreakerOperation>,
+}
+
+/// Returns the channel name lowercased with separators removed, so "52-A",
+/// "52 a" and "52A" compare equal.
+fn normalized(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn find_digital(
+    channels: &[SerializableDigitalChannel],
+    matches: impl Fn(&str) -> bool,
+) -> Vec<usize> {
+    channels
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| matches(&normalized(&c.name)))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Returns the logical OR of the given digital channels at each sample.
+fn combined(channels: &[SerializableDigitalChannel], indices: &[usize], samples: usize) -> Vec<u8> {
+    (0..samples)
+        .map(|k| {
+            indices
+                .iter()
+                .any(|&i| channels[i].values.get(k).is_some_and(|&v| v != 0)) as u8
+        })
+        .collect()
+}
+
+/// Returns the first sample in `from..to` at which `values` changes to `state`.
+fn first_edge(values: &[u8], state: u8, from: usize, to: usize) -> Option<usize> {
+    (from.max(1)..to.min(values.len())).find(|&k| values[k] == state && values[k - 1] != state)
+}
+
+/// Returns the first change of any 52a contact to open or 52b contact to
+/// closed (`opening`), or the reverse, in `from..to`.
+fn contact_edge(
+    a_contacts: &[&[u8]],
+    b_contacts: &[&[u8]],
+    opening: bool,
+    from: usize,
+    to: usize,
+) -> Option<usize> {
+    let (a_state, b_state) = if opening { (0, 1) } else { (1, 0) };
+    a_contacts
+        .iter()
+        .filter_map(|values| first_edge(values, a_state, from, to))
+        .chain(
+            b_contacts
+                .iter()
+                .filter_map(|values| first_edge(values, b_state, from, to)),
+        )
+        .min()
+}
+
+/// Returns the sample after the last one in `from..to` at which `current`
+/// exceeds `threshold`, provided the current then stays below it for at
+/// least `cycle` samples (or until `to`).
+fn extinction(
+    current: &[f64],
+    threshold: f64,
+    from: usize,
+    to: usize,
+    cycle: usize,
+) -> Option<usize> {
+    let to = to.min(current.len());
+    let mut last_above = None;
+    for (k, value) in current.iter().enumerate().take(to).skip(from) {
+        if value.abs() > threshold {
+            last_above = Some(k);
+        } else if let Some(last) = last_above
+            && k - last >= cycle
+        {
+            return Some(last + 1);
+        }
+    }
+    last_above
+        .filter(|&last| last + 1 < to)
+        .map(|last| last + 1)
+}
+
+struct Pole<'a> {
+    name: String,
+    phase: Option<usize>,
+    current: Option<&'a SerializableAnalogChannel>,
+}
+
+/// Correlates trip and close commands, 52a/52b contacts and the pole currents
+/// into breaker operations.
+///
+/// Returns `None` when there is no trip command channel or it never asserts.
+pub fn analyze_breaker(
+    analog_channels: &[SerializableAnalogChannel],
+    digital_channels: &[SerializableDigitalChannel],
+    timestamps: &[f64],
+    line_frequency: f64,
+    options: &BreakerOptions,
+) -> Option<BreakerAnalysis> {
+    let samples = timestamps.len();
+    let valid = |indices: &Vec<usize>| -> Vec<usize> {
+        indices
+            .iter()
+            .copied()
+            .filter(|&i| i < digital_channels.len())
+            .collect()
+    };
+    let trip_channels = options.trip_channels.as_ref().map_or_else(
+        || find_digital(digital_channels, |n| n.contains("trip")),
+        valid,
+    );
+    let close_channels = options.close_channels.as_ref().map_or_else(
+        || {
+            find_digital(digital_channels, |n| {
+                n.contains("close") && !n.contains("closed")
+            })
+        },
+        valid,
+    );
+    let a_contacts = options.a_contacts.as_ref().map_or_else(
+        || find_digital(digital_channels, |n| n.contains("52a")),
+        valid,
+    );
+    let b_contacts = options.b_contacts.as_ref().map_or_else(
+        || find_digital(digital_channels, |n| n.contains("52b")),
+        valid,
+    );
+    if trip_channels.is_empty() {
+        return None;
+    }
+
+    let current_indices: Vec<usize> = match &options.current_channels {
+        Some(indices) => indices
+            .iter()
+            .copied()
+            .filter(|&i| i < analog_channels.len())
+            .collect(),
+        None => three_phase_set(analog_channels, |c| c.is_current()).map_or_else(
+            || {
+                analog_channels
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| c.is_current())
+                    .map(|(i, _)| i)
+                    .collect()
+            },
+            |set| set.to_vec(),
+        ),
+    };
+    let mut poles: Vec<Pole> = current_indices
+        .iter()
+        .map(|&i| {
+            let channel = &analog_channels[i];
+            let phase = channel_phase(channel);
+            Pole {
+                name: phase.map_or_else(|| channel.name.clone(), |p| POLE_NAMES[p].to_string()),
+                phase,
+                current: Some(channel),
+            }
+        })
+        .collect();
+    if poles.is_empty() {
+        poles.push(Pole {
+            name: "ABC".to_string(),
+            phase: None,
+            current: None,
+        });
+    }
+
+    // Contacts with a phase belong to that pole; the others to every pole.
+    let contacts_of = |indices: &[usize], pole: &Pole| -> Vec<&[u8]> {
+        indices
+            .iter()
+            .map(|&i| &digital_channels[i])
+            .filter(|c| match (phase_number(&c.phase), pole.phase) {
+                (Some(contact), Some(pole)) => contact == pole,
+                _ => true,
+            })
+            .map(|c| c.values.as_slice())
+            .collect()
+    };
+    let all_a: Vec<&[u8]> = a_contacts
+        .iter()
+        .map(|&i| digital_channels[i].values.as_slice())
+        .collect();
+    let all_b: Vec<&[u8]> = b_contacts
+        .iter()
+        .map(|&i| digital_channels[i].values.as_slice())
+        .collect();
+
+    let trip = combined(digital_channels, &trip_channels, samples);
+    let close = combined(digital_channels, &close_channels, samples);
+    let trip_edges: Vec<usize> = (0..samples)
+        .filter(|&k| trip[k] == 1 && (k == 0 || trip[k - 1] == 0))
+        .collect();
+    if trip_edges.is_empty() {
+        return None;
+    }
+
+    let frequency = nominal_frequency(line_frequency);
+    let time = |k: usize| timestamps[k];
+    let mut operations = Vec::new();
+
+    for (n, &trip_sample) in trip_edges.iter().enumerate() {
+        let window_end = trip_edges.get(n + 1).copied().unwrap_or(samples);
+        let cycle = sample_rate_at(timestamps, trip_sample)
+            .map_or(1, |rate| samples_per_cycle(rate, frequency));
+        let trip_time = time(trip_sample);
+
+        let mut threshold_used = Vec::new();
+        let mut extinction_samples = Vec::new();
+        let mut parting_samples = Vec::new();
+        let pole_operations: Vec<PoleOperation> = poles
+            .iter()
+            .map(|pole| {
+                let parting = contact_edge(
+                    &contacts_of(&a_contacts, pole),
+                    &contacts_of(&b_contacts, pole),
+                    true,
+                    trip_sample,
+                    window_end,
+                );
+                let extinct = pole.current.and_then(|channel| {
+                    let current = &channel.primary_values;
+                    // The load or fault current is taken from the cycle before the
+                    // command, or the first cycle if the command was there from the start.
+                    let end = trip_sample.min(current.len());
+                    let before = if trip_sample == 0 {
+                        &current[..cycle.min(current.len())]
+                    } else {
+                        &current[trip_sample.saturating_sub(cycle).min(end)..end]
+                    };
+                    let peak = before.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+                    let threshold = options
+                        .current_threshold
+                        .unwrap_or(CURRENT_THRESHOLD_FRACTION * peak);
+                    threshold_used.push((channel, threshold));
+                    if threshold <= 0.0 {
+                        return None;
+                    }
+                    extinction(current, threshold, trip_sample, window_end, cycle)
+                });
+                parting_samples.extend(parting);
+                extinction_samples.extend(extinct);
+
+                let parting_time = parting.map(time);
+                let extinction_time = extinct.map(time);
+                PoleOperation {
+                    pole: pole.name.clone(),
+                    current_channel: pole.current.map(|c| c.name.clone()),
+                    contact_parting_time: parting_time,
+                    arc_extinction_time: extinction_time,
+                    opening_time: parting_time.map(|t| t - trip_time),
+                    arcing_time: parting_time.zip(extinction_time).map(|(p, e)| e - p),
+                    clearing_time: extinction_time.map(|t| t - trip_time),
+                }
+            })
+            .collect();
+
+        let clearing_time = pole_operations
+            .iter()
+            .filter_map(|p| p.clearing_time)
+            .reduce(f64::max);
+
+        // The breaker is open from the last pole interruption, or from the
+        // contacts parting when no current was measured.
+        let open_sample = extinction_samples
+            .iter()
+            .copied()
+            .max()
+            .or_else(|| parting_samples.iter().copied().max());
+        let reclose = open_sample.and_then(|open| {
+            let close_command = first_edge(&close, 1, trip_sample, window_end);
+            let contact_closing = contact_edge(&all_a, &all_b, false, open, window_end);
+            let restored = (open..window_end).find(|&k| {
+                threshold_used
+                    .iter()
+                    .any(|(c, t)| *t > 0.0 && c.primary_values.get(k).is_some_and(|v| v.abs() > *t))
+            });
+            if close_command.is_none() && contact_closing.is_none() && restored.is_none() {
+                return None;
+            }
+            let closed = restored.or(contact_closing);
+            Some(RecloseAttempt {
+                close_command_time: close_command.map(time),
+                contact_closing_time: contact_closing.map(time),
+                current_restored_time: restored.map(time),
+                dead_time: closed.map(|k| time(k) - time(open)),
+                successful: n + 1 == trip_edges.len(),
+            })
+        });
+
+        operations.push(BreakerOperation {
+            trip_command_time: trip_time,
+            asserted_at_start: trip_sample == 0,
+            poles: pole_operations,
+            clearing_time,
+            reclose,
+        });
+    }
+
+    let names = |indices: &[usize]| -> Vec<String> {
+        indices
+            .iter()
+            .map(|&i| digital_channels[i].name.clone())
+            .collect()
+    };
+    Some(BreakerAnalysis {
+        trip_channels: names(&trip_channels),
+        close_channels: names(&close_channels),
+        a_contacts: names(&a_contacts),
+        b_contacts: names(&b_contacts),
+        current_channels: current_indices
+            .iter()
+            .map(|&i| analog_channels[i].name.clone())
+            .collect(),
+        operations,
+    })
+}
+
+/// Describes each operation for `analysis_notes`. `recording_start` is the
+/// time of the first sample, so the notes give seconds into the recording.
+pub fn breaker_notes(analysis: &BreakerAnalysis, recording_start: f64) -> Vec<String> {
+    let ms = |seconds: f64| seconds * 1000.0;
+    let mut notes = Vec::new();
+    for operation in &analysis.operations {
+        let mut note = if operation.asserted_at_start {
+            "Breaker trip command already asserted at the start of the recording.".to_string()
+        } else {
+            format!(
+                "Breaker trip command at {:.4} seconds.",
+                operation.trip_command_time - recording_start
+            )
+        };
+        if let Some(opening) = operation
+            .poles
+            .iter()
+            .filter_map(|p| p.opening_time)
+            .reduce(f64::min)
+        {
+            note.push_str(&format!(" Contacts parted after {:.1} ms.", ms(opening)));
+        }
+        let clearing: Vec<String> = operation
+            .poles
+            .iter()
+            .filter_map(|p| {
+                p.clearing_time
+                    .map(|t| format!("{} {:.1} ms", p.pole, ms(t)))
+            })
+            .collect();
+        if !clearing.is_empty() {
+            note.push_str(&format!(" Clearing time: {}.", clearing.join(", ")));
+        }
+        notes.push(note);
+
+        if let Some(reclose) = &operation.reclose {
+            let outcome = if reclose.successful {
+                "successful"
+            } else {
+                "unsuccessful, the breaker tripped again"
+            };
+            notes.push(match reclose.dead_time {
+                Some(dead_time) => format!(
+                    "Auto-reclose after a dead time of {:.1} ms ({}).",
+                    ms(dead_time),
+                    outcome
+                ),
+                None => format!("Close command issued after the trip ({}).", outcome),
+            });
+        }
+    }
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, digital_channel, recording, sine};
+
+    /// A digital channel that is `1` from `on` until `off` (sample numbers).
+    fn pulse(
+        index: u32,
+        name: &str,
+        on: usize,
+        off: usize,
+        samples: usize,
+    ) -> SerializableDigitalChannel {
+        let mut channel = digital_channel(index, name, 0);
+        channel.values = (0..samples).map(|k| (on..off).contains(&k) as u8).collect();
+        channel
+    }
+
+    #[test]
+    fn test_analyze_breaker() {
+        // 2 kHz, 50 Hz: trip at 100, current interrupted at 180/190/200,
+        // reclose restores current at 1000, second trip at 1200.
+        let samples = 1600;
+        let currents: Vec<SerializableAnalogChannel> = [(0.0, 180), (-120.0, 190), (120.0, 200)]
+            .iter()
+            .zip(["IA", "IB", "IC"])
+            .enumerate()
+            .map(|(i, (&(shift, stop), name))| {
+                let wave = sine(1000.0, 50.0, shift, 2000.0, samples);
+                let values = wave
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &v)| {
+                        if (stop..1000).contains(&k) || k >= 1300 {
+                            0.0
+                        } else {
+                            v
+                        }
+                    })
+                    .collect();
+                analog_channel(i as u32 + 1, name, "A", "", values)
+            })
+            .collect();
+
+        let mut trip = pulse(1, "TRIP", 100, 250, samples);
+        trip.values[1200..1350].fill(1);
+        let digital = vec![
+            trip,
+            pulse(2, "79 CLOSE", 900, 950, samples),
+            pulse(3, "CB 52a", 0, 170, samples),
+        ];
+        let mut contact = digital[2].values.clone();
+        contact[990..1280].fill(1);
+        let mut info = recording(2000.0, 50.0, currents, digital);
+        info.digital_channels[2].values = contact;
+
+        let analysis = analyze_breaker(
+            &info.analog_channels,
+            &info.digital_channels,
+            &info.timestamps,
+            50.0,
+            &BreakerOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(analysis.close_channels, ["79 CLOSE"]);
+        assert_eq!(analysis.operations.len(), 2);
+
+        let first = &analysis.operations[0];
+        assert_eq!(first.poles.len(), 3);
+        assert!((first.poles[0].opening_time.unwrap() - 0.035).abs() < 1e-9);
+        let clearing: Vec<f64> = first
+            .poles
+            .iter()
+            .map(|p| p.clearing_time.unwrap())
+            .collect();
+        assert!(clearing[0] <= 0.04 && clearing[0] > 0.03);
+        assert!(clearing[2] <= 0.05 && clearing[2] > clearing[0]);
+        assert!((first.clearing_time.unwrap() - clearing[2]).abs() < 1e-12);
+
+        let reclose = first.reclose.as_ref().unwrap();
+        assert!((reclose.close_command_time.unwrap() - 0.45).abs() < 1e-9);
+        assert!((reclose.contact_closing_time.unwrap() - 0.495).abs() < 1e-9);
+        assert!(reclose.current_restored_time.unwrap() >= 0.5);
+        assert!(!reclose.successful);
+        assert!(analysis.operations[1].reclose.is_none());
+        assert_eq!(breaker_notes(&analysis, 0.0).len(), 3);
+        assert!(!first.asserted_at_start);
+
+        // A trip asserted from the first sample and a current channel shorter
+        // than the timestamps.
+        info.digital_channels[0].values[..50].fill(1);
+        info.analog_channels[0].primary_values.truncate(40);
+        let analysis = analyze_breaker(
+            &info.analog_channels,
+            &info.digital_channels,
+            &info.timestamps,
+            50.0,
+            &BreakerOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(analysis.operations.len(), 3);
+        assert!(analysis.operations[0].asserted_at_start);
+        assert_eq!(analysis.operations[0].trip_command_time, 0.0);
+        assert!(breaker_notes(&analysis, 0.0)[0].contains("already asserted"));
+    }
+}
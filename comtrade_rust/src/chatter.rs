@@ -0,0 +1,223 @@
+// comtrade_rust/src/chatter.rs
+// This file contains detection of contact chatter and bounce on digital channels, and their debouncing.
+// This file exists because faulty auxiliary contacts toggle rapidly and confuse both relays and the review of a recording.
+// RELEVANT FILES: comtrade_rust/src/diagnostics.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::SerializableDigitalChannel;
+use crate::diagnostics::Diagnostic;
+
+/// Transitions closer together than this many seconds belong to one burst by default.
+const DEFAULT_INTERVAL: f64 = 0.02;
+/// The fewest transitions that make a burst by default: a close, bounce open and close again.
+const DEFAULT_MIN_TRANSITIONS: usize = 3;
+
+/// Options for the chatter detection. Every field is optional.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ChatterOptions {
+    /// The longest gap in seconds between transitions of one burst. Defaults to 20 ms.
+    pub interval: Option<f64>,
+    /// The fewest transitions that count as a burst. Defaults to 3.
+    pub min_transitions: Option<usize>,
+    /// How long in seconds a new state must hold before the debounced channel
+    /// accepts it. Defaults to `interval`.
+    pub debounce_time: Option<f64>,
+    /// Also return a debounced copy of each chattering channel.
+    pub debounce: bool,
+}
+
+/// A burst of rapid transitions on one channel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatterBurst {
+    /// The sample of the first transition.
+    pub start_sample: usize,
+    /// The sample of the last transition.
+    pub end_sample: usize,
+    /// The time of `start_sample` as Unix seconds.
+    pub start_time: f64,
+    /// The time of `end_sample` as Unix seconds.
+    pub end_time: f64,
+    /// The number of transitions in the burst.
+    pub transitions: usize,
+}
+
+/// The chatter found on one digital channel.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ChannelChatter {
+    /// The position of the channel in `digital_channels`.
+    pub channel_index: usize,
+    /// The channel name.
+    pub channel: String,
+    /// The transitions of the channel over the whole recording.
+    pub total_transitions: usize,
+    /// The bursts of rapid transitions.
+    pub bursts: Vec<ChatterBurst>,
+    /// A copy of the channel in which a state only counts once it has held
+    /// for the debounce time, when debouncing was requested.
+    pub debounced: Option<SerializableDigitalChannel>,
+}
+
+/// Returns `values` debounced: a new state is accepted once it has held for
+/// `debounce_time` seconds, and the change is placed at the first transition
+/// of the bounce so contact timing is preserved.
+pub fn debounce(values: &[u8], timestamps: &[f64], debounce_time: f64) -> Vec<u8> {
+    let samples = values.len().min(timestamps.len());
+    let mut output = values[..samples].to_vec();
+    let Some(&first) = values.first() else {
+        return output;
+    };
+
+    let mut accepted = first;
+    let mut departed: Option<usize> = None;
+    let mut last_change = 0;
+    for k in 0..samples {
+        if k > 0 && values[k] != values[k - 1] {
+            last_change = k;
+        }
+        let stable = timestamps[k] - timestamps[last_change] >= debounce_time;
+        if values[k] != accepted {
+            let from = *departed.get_or_insert(k);
+            if stable {
+                output[from..=k].fill(values[k]);
+                accepted = values[k];
+                departed = None;
+            }
+        } else if stable {
+            departed = None;
+        }
+        output[k] = if departed.is_some() {
+            accepted
+        } else {
+            values[k]
+        };
+    }
+    output
+}
+
+/// Finds bursts of at least `min_transitions` transitions on each digital
+/// channel, where consecutive transitions are at most `interval` seconds apart.
+pub fn detect_chatter(
+    channels: &[SerializableDigitalChannel],
+    timestamps: &[f64],
+    options: &ChatterOptions,
+) -> Vec<ChannelChatter> {
+    let interval = options.interval.unwrap_or(DEFAULT_INTERVAL);
+    let min_transitions = options
+        .min_transitions
+        .unwrap_or(DEFAULT_MIN_TRANSITIONS)
+        .max(2);
+    let debounce_time = options.debounce_time.unwrap_or(interval);
+    let time = |s: usize| timestamps.get(s).copied().unwrap_or_default();
+
+    let mut results = Vec::new();
+    for (channel_index, channel) in channels.iter().enumerate() {
+        let samples = channel.values.len().min(timestamps.len());
+        let transitions: Vec<usize> = (1..samples)
+            .filter(|&k| channel.values[k] != channel.values[k - 1])
+            .collect();
+
+        let mut bursts = Vec::new();
+        let mut group_start = 0;
+        for i in 0..transitions.len() {
+            let ends_group = i + 1 == transitions.len()
+                || time(transitions[i + 1]) - time(transitions[i]) > interval;
+            if !ends_group {
+                continue;
+            }
+            let count = i + 1 - group_start;
+            if count >= min_transitions {
+                let (start, end) = (transitions[group_start], transitions[i]);
+                bursts.push(ChatterBurst {
+                    start_sample: start,
+                    end_sample: end,
+                    start_time: time(start),
+                    end_time: time(end),
+                    transitions: count,
+                });
+            }
+            group_start = i + 1;
+        }
+
+        if bursts.is_empty() {
+            continue;
+        }
+        let debounced = options.debounce.then(|| SerializableDigitalChannel {
+            name: format!("{} (debounced)", channel.name),
+            values: debounce(&channel.values, timestamps, debounce_time),
+            ..channel.clone()
+        });
+        results.push(ChannelChatter {
+            channel_index,
+            channel: channel.name.clone(),
+            total_transitions: transitions.len(),
+            bursts,
+            debounced,
+        });
+    }
+    results
+}
+
+/// Converts chatter results into diagnostics, one per burst.
+pub fn chatter_diagnostics(chatter: &[ChannelChatter]) -> Vec<Diagnostic> {
+    chatter
+        .iter()
+        .flat_map(|result| {
+            result.bursts.iter().map(|burst| {
+                Diagnostic::warning(
+                    "contact-chatter",
+                    format!(
+                        "{} transitions within {:.1} ms; the contact is bouncing or chattering.",
+                        burst.transitions,
+                        (burst.end_time - burst.start_time) * 1000.0
+                    ),
+                )
+                .on_channel(&result.channel)
+                .at_sample(burst.start_sample)
+                .between(burst.start_time, burst.end_time)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, digital_channel, recording};
+
+    #[test]
+    fn test_detect_chatter() {
+        let mut contact = digital_channel(1, "52A", 0);
+        // Clean close at 10, bounce at 50-54 settling closed, chatter at 200-230.
+        contact.values = (0..400)
+            .map(|k| match k {
+                10..40 => 1,
+                50 | 52..=54 | 60..=150 => 1,
+                200..=230 => (k % 4 < 2) as u8,
+                _ => 0,
+            })
+            .collect();
+        let analog = analog_channel(1, "VA", "V", "A", vec![0.0; 400]);
+        let info = recording(1000.0, 50.0, vec![analog], vec![contact]);
+
+        let options = ChatterOptions {
+            interval: Some(0.006),
+            debounce: true,
+            ..Default::default()
+        };
+        let chatter = detect_chatter(&info.digital_channels, &info.timestamps, &options);
+        assert_eq!(chatter.len(), 1);
+        let bursts = &chatter[0].bursts;
+        assert_eq!(bursts.len(), 2);
+        assert_eq!((bursts[0].start_sample, bursts[0].end_sample), (50, 60));
+        assert_eq!(bursts[1].start_sample, 200);
+        assert_eq!(chatter_diagnostics(&chatter).len(), 2);
+
+        let debounced = &chatter[0].debounced.as_ref().unwrap().values;
+        assert_eq!(debounced[49..52], [0, 1, 1]);
+        assert!(debounced[50..=150].iter().all(|&v| v == 1));
+        assert!(debounced[151..].iter().all(|&v| v == 0));
+        assert!(debounced[10..40].iter().all(|&v| v == 1));
+    }
+}
@@ -0,0 +1,168 @@
+// comtrade_rust/src/clipping.rs
+// This file contains detection of analog samples pinned at the ADC limits declared in the CFG.
+// This file exists so clipped intervals are reported and values derived from them can be flagged as unreliable.
+// RELEVANT FILES: comtrade_rust/src/diagnostics.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::SerializableAnalogChannel;
+use crate::diagnostics::Diagnostic;
+
+/// The number of consecutive samples at a rail before a run counts as clipping.
+/// Shorter runs occur naturally at waveform peaks of a well-ranged channel.
+pub const MIN_CLIPPED_RUN: usize = 3;
+
+/// A run of consecutive samples at or beyond one of the ADC limits of a channel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ClippedInterval {
+    /// The position of the channel in `analog_channels`.
+    pub channel_index: usize,
+    /// The channel name.
+    pub channel: String,
+    /// Which limit the samples are pinned at: "max" or "min".
+    pub rail: String,
+    /// The first clipped sample (0-based, inclusive).
+    pub start_sample: usize,
+    /// The last clipped sample (0-based, inclusive).
+    pub end_sample: usize,
+    /// The time of the first clipped sample as Unix seconds.
+    pub start_time: f64,
+    /// The time of the last clipped sample as Unix seconds.
+    pub end_time: f64,
+}
+
+/// Returns the channel's `min_value` and `max_value` converted to the units of
+/// `values`, lowest first, with a tolerance of half a raw count.
+fn scaled_rails(channel: &SerializableAnalogChannel) -> Option<(f64, f64, f64)> {
+    if channel.min_value >= channel.max_value || channel.multiplier == 0.0 {
+        return None;
+    }
+    let a = channel.multiplier * channel.min_value + channel.offset_adder;
+    let b = channel.multiplier * channel.max_value + channel.offset_adder;
+    let tolerance = channel.multiplier.abs() * 0.5;
+    Some((a.min(b), a.max(b), tolerance))
+}
+
+/// Finds runs of at least `min_run` consecutive samples at the ADC limits of
+/// each analog channel. `timestamps` are the sample times in Unix seconds.
+pub fn detect_clipping(
+    channels: &[SerializableAnalogChannel],
+    timestamps: &[f64],
+    min_run: usize,
+) -> Vec<ClippedInterval> {
+    let mut intervals = Vec::new();
+    let min_run = min_run.max(1);
+
+    for (channel_index, channel) in channels.iter().enumerate() {
+        let Some((low, high, tolerance)) = scaled_rails(channel) else {
+            continue;
+        };
+        let rail_of = |v: f64| {
+            if v >= high - tolerance {
+                Some("max")
+            } else if v <= low + tolerance {
+                Some("min")
+            } else {
+                None
+            }
+        };
+
+        let mut run: Option<(usize, &str)> = None;
+        let values = &channel.values;
+        for i in 0..=values.len() {
+            let rail = values.get(i).and_then(|&v| rail_of(v));
+            match (run, rail) {
+                (Some((_, current)), Some(rail)) if current == rail => {}
+                _ => {
+                    if let Some((start, current)) = run.take()
+                        && i - start >= min_run
+                    {
+                        let time = |s: usize| timestamps.get(s).copied().unwrap_or_default();
+                        intervals.push(ClippedInterval {
+                            channel_index,
+                            channel: channel.name.clone(),
+                            rail: current.to_string(),
+                            start_sample: start,
+                            end_sample: i - 1,
+                            start_time: time(start),
+                            end_time: time(i - 1),
+                        });
+                    }
+                    run = rail.map(|rail| (i, rail));
+                }
+            }
+        }
+    }
+
+    intervals
+}
+
+/// Converts clipped intervals into diagnostics, one per interval.
+pub fn clipping_diagnostics(intervals: &[ClippedInterval]) -> Vec<Diagnostic> {
+    intervals
+        .iter()
+        .map(|interval| {
+            Diagnostic::warning(
+                "signal-clipping",
+                format!(
+                    "{} sample(s) are pinned at the {} ADC limit; RMS, phasor and fault location values computed over this interval are unreliable.",
+                    interval.end_sample - interval.start_sample + 1,
+                    interval.rail
+                ),
+            )
+            .on_channel(&interval.channel)
+            .at_sample(interval.start_sample)
+            .between(interval.start_time, interval.end_time)
+        })
+        .collect()
+}
+
+/// Returns true if any sample from `start_sample` to `end_sample` (inclusive)
+/// of the channel at `channel_index` is clipped.
+pub fn is_clipped(
+    intervals: &[ClippedInterval],
+    channel_index: usize,
+    start_sample: usize,
+    end_sample: usize,
+) -> bool {
+    intervals.iter().any(|interval| {
+        interval.channel_index == channel_index
+            && interval.start_sample <= end_sample
+            && interval.end_sample >= start_sample
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording, sine};
+
+    #[test]
+    fn test_detect_clipping() {
+        let values: Vec<f64> = sine(150.0, 50.0, 0.0, 1000.0, 40)
+            .into_iter()
+            .map(|v| v.clamp(-100.0, 100.0))
+            .collect();
+        let mut channel = analog_channel(1, "IA", "A", "A", values);
+        channel.min_value = -100.0;
+        channel.max_value = 100.0;
+        let unclipped = analog_channel(2, "IB", "A", "B", sine(50.0, 50.0, 0.0, 1000.0, 40));
+        let info = recording(1000.0, 50.0, vec![channel, unclipped], vec![]);
+
+        let intervals = detect_clipping(&info.analog_channels, &info.timestamps, MIN_CLIPPED_RUN);
+        assert_eq!(intervals.len(), 4);
+        assert!(intervals.iter().all(|i| i.channel_index == 0));
+        assert_eq!(intervals[0].rail, "max");
+        assert_eq!(intervals[1].rail, "min");
+
+        let first = &intervals[0];
+        assert!(is_clipped(
+            &intervals,
+            0,
+            first.start_sample,
+            first.start_sample
+        ));
+        assert!(!is_clipped(&intervals, 1, 0, 39));
+        assert_eq!(clipping_diagnostics(&intervals).len(), 4);
+    }
+}
@@ -0,0 +1,157 @@
+// comtrade_rust/src/config.rs
+// This file contains the serializable echo of the COMTRADE CFG header.
+// This file exists so the frontend can show exactly what the CFG declared, independent of what was parsed from the DAT file.
+// RELEVANT FILES: comtrade_rust/src/lib.rs, app/src/routes/info/+page.svelte
+
+use comtrade::{Comtrade, FormatRevision, LeapSecondStatus, TimeQuality};
+use serde::{Deserialize, Serialize};
+
+use crate::data_format_to_str;
+
+/// A single sampling rate section from the CFG file.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SerializableSamplingRate {
+    /// The sampling rate in Hz.
+    pub rate_hz: f64,
+    /// The last sample number that uses this sampling rate.
+    pub end_sample_number: u32,
+}
+
+/// The CFG header items as declared in the configuration file.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ComtradeConfig {
+    /// The station name (line 1, field 1).
+    pub station_name: String,
+    /// The recording device identifier (line 1, field 2).
+    pub recording_device_id: String,
+    /// The standard revision year (line 1, field 3). Files without a year are 1991.
+    pub revision_year: u16,
+    /// The declared total number of channels (line 2, field 1).
+    pub total_channels: u32,
+    /// The declared number of analog channels (line 2, field 2, without the `A` suffix).
+    pub analog_channels: u32,
+    /// The declared number of digital/status channels (line 2, field 3, without the `D` suffix).
+    pub digital_channels: u32,
+    /// The nominal line frequency in Hz.
+    pub line_frequency: f64,
+    /// The number of sampling rate sections (`nrates`).
+    pub num_sampling_rates: usize,
+    /// The sampling rate sections in file order.
+    pub sampling_rates: Vec<SerializableSamplingRate>,
+    /// The timestamp of the first data point.
+    pub start_time: String,
+    /// The timestamp of the trigger point.
+    pub trigger_time: String,
+    /// The data file type (e.g., "ASCII", "BINARY").
+    pub data_format: String,
+    /// The multiplication factor for the DAT timestamp field (`timemult`).
+    pub timestamp_multiplier: f64,
+    /// The time code of the recording relative to UTC (2013 revision only).
+    pub time_code: Option<String>,
+    /// The local time code relative to UTC (2013 revision only).
+    pub local_code: Option<String>,
+    /// The time quality indicator of the recording device clock as the CFG
+    /// hexadecimal digit, "0" (locked) to "F" (clock failure) (2013 revision only).
+    pub time_quality: Option<String>,
+    /// The leap second indicator as the CFG digit: "0" none, "1" added,
+    /// "2" subtracted, "3" no capability (2013 revision only).
+    pub leap_second: Option<String>,
+}
+
+fn revision_to_year(revision: &FormatRevision) -> u16 {
+    match revision {
+        FormatRevision::Revision1991 => 1991,
+        FormatRevision::Revision1999 => 1999,
+        FormatRevision::Revision2013 => 2013,
+    }
+}
+
+/// Returns the CFG time quality code, a single hexadecimal digit.
+fn time_quality_code(quality: &TimeQuality) -> String {
+    format!("{:X}", quality.0)
+}
+
+/// Returns the CFG leap second indicator digit.
+fn leap_second_code(status: &LeapSecondStatus) -> String {
+    match status {
+        LeapSecondStatus::None => "0",
+        LeapSecondStatus::Added => "1",
+        LeapSecondStatus::Subtracted => "2",
+        LeapSecondStatus::NoCapability => "3",
+    }
+    .to_string()
+}
+
+impl From<&Comtrade> for ComtradeConfig {
+    fn from(comtrade: &Comtrade) -> Self {
+        Self {
+            station_name: comtrade.station_name.clone(),
+            recording_device_id: comtrade.recording_device_id.clone(),
+            revision_year: revision_to_year(&comtrade.revision),
+            total_channels: comtrade.declared_total_channels as u32,
+            analog_channels: comtrade.declared_analog_channels as u32,
+            digital_channels: comtrade.declared_status_channels as u32,
+            line_frequency: comtrade.line_frequency,
+            num_sampling_rates: comtrade.sampling_rates.len(),
+            sampling_rates: comtrade
+                .sampling_rates
+                .iter()
+                .map(|rate| SerializableSamplingRate {
+                    rate_hz: rate.rate_hz as f64,
+                    end_sample_number: rate.end_sample_number as u32,
+                })
+                .collect(),
+            start_time: comtrade.start_time.to_string(),
+            trigger_time: comtrade.trigger_time.to_string(),
+            data_format: data_format_to_str(&comtrade.data_format).to_string(),
+            timestamp_multiplier: comtrade.timestamp_multiplication_factor,
+            time_code: comtrade.time_offset.map(|offset| offset.to_string()),
+            local_code: comtrade.local_offset.map(|offset| offset.to_string()),
+            time_quality: comtrade.time_quality.as_ref().map(time_quality_code),
+            leap_second: comtrade.leap_second_status.as_ref().map(leap_second_code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use comtrade::{DataFormat, NaiveDateTime, SamplingRate};
+
+    #[test]
+    fn test_comtrade_config() {
+        let time =
+            NaiveDateTime::parse_from_str("2024-03-01 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let comtrade = Comtrade {
+            station_name: "North".to_string(),
+            recording_device_id: "R1".to_string(),
+            revision: FormatRevision::Revision2013,
+            line_frequency: 50.0,
+            sampling_rates: vec![SamplingRate {
+                rate_hz: 1000.0,
+                end_sample_number: 200,
+            }],
+            start_time: time,
+            trigger_time: time,
+            data_format: DataFormat::Ascii,
+            timestamp_multiplication_factor: 1.0,
+            time_offset: None,
+            local_offset: None,
+            time_quality: Some(TimeQuality(11)),
+            leap_second_status: Some(LeapSecondStatus::NoCapability),
+            declared_total_channels: 0,
+            declared_analog_channels: 0,
+            declared_status_channels: 0,
+            analog_channels: Vec::new(),
+            status_channels: Vec::new(),
+        };
+
+        let config = ComtradeConfig::from(&comtrade);
+        assert_eq!(config.revision_year, 2013);
+        assert_eq!(config.num_sampling_rates, 1);
+        assert_eq!(config.sampling_rates[0].end_sample_number, 200);
+        assert_eq!(config.data_format, "ASCII");
+        assert_eq!(config.time_quality.as_deref(), Some("B"));
+        assert_eq!(config.leap_second.as_deref(), Some("3"));
+    }
+}
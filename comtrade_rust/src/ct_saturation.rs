@@ -0,0 +1,251 @@
+// comtrade_rust/src/ct_saturation.rs
+// This file contains detection of current transformer saturation on current channels.
+// This file exists because saturated CT secondaries distort every downstream calculation during high-current faults.
+// RELEVANT FILES: comtrade_rust/src/dsp.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+use crate::SerializableAnalogChannel;
+use crate::diagnostics::Diagnostic;
+use crate::dsp::{cycle_windows, dft, nominal_frequency, rms, sample_rate_at};
+
+/// A cycle counts as fault current when its RMS exceeds the pre-fault RMS by this factor.
+const FAULT_CURRENT_FACTOR: f64 = 2.0;
+/// How far the third difference may exceed that of a pure sinusoid of the
+/// same peak before it is treated as a discontinuity.
+const THIRD_DIFFERENCE_FACTOR: f64 = 4.0;
+/// The minimum ratio of (2nd + 3rd harmonic) to fundamental in a saturated cycle.
+const HARMONIC_RATIO: f64 = 0.1;
+/// Below this many samples per cycle the derivative heuristic is not meaningful.
+const MIN_SAMPLES_PER_CYCLE: usize = 8;
+
+/// A run of consecutive saturated cycles.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SaturationInterval {
+    /// The first sample of the first saturated cycle.
+    pub start_sample: usize,
+    /// The last sample of the last saturated cycle.
+    pub end_sample: usize,
+    /// The time of `start_sample` as Unix seconds.
+    pub start_time: f64,
+    /// The time of `end_sample` as Unix seconds.
+    pub end_time: f64,
+}
+
+/// CT saturation found on one current channel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CtSaturation {
+    /// The position of the channel in `analog_channels`.
+    pub channel_index: usize,
+    /// The channel name.
+    pub channel: String,
+    /// The sample at which saturation begins in each saturated cycle.
+    pub onset_samples: Vec<usize>,
+    /// The time of each onset sample as Unix seconds.
+    pub onset_times: Vec<f64>,
+    /// The (2nd + 3rd harmonic) to fundamental ratio of each saturated cycle.
+    pub harmonic_ratios: Vec<f64>,
+    /// Consecutive saturated cycles merged into intervals.
+    pub intervals: Vec<SaturationInterval>,
+}
+
+/// Returns the third difference of `values`; the first three entries are zero.
+fn third_difference(values: &[f64]) -> Vec<f64> {
+    (0..values.len())
+        .map(|k| {
+            if k < 3 {
+                0.0
+            } else {
+                values[k] - 3.0 * values[k - 1] + 3.0 * values[k - 2] - values[k - 3]
+            }
+        })
+        .collect()
+}
+
+/// Detects CT saturation on one channel, returning `None` if no cycle is saturated.
+fn detect_channel(
+    channel_index: usize,
+    channel: &SerializableAnalogChannel,
+    timestamps: &[f64],
+    frequency: f64,
+) -> Option<CtSaturation> {
+    let values = &channel.values;
+    let samples = values.len().min(timestamps.len());
+    // Windows spanning a change of sampling rate are skipped.
+    let windows = cycle_windows(timestamps, samples, frequency, 1.0);
+    let &(first_start, first_end) = windows.first()?;
+    if first_end - first_start < MIN_SAMPLES_PER_CYCLE || windows.len() < 3 {
+        return None;
+    }
+
+    let d3 = third_difference(&values[..samples]);
+    let pre_fault_rms = rms(&values[first_start..first_end]);
+    // The spread of the third difference over the pre-fault cycle is the noise floor.
+    let noise = 6.0 * rms(&d3[first_start + 3..first_end]);
+
+    let mut result = CtSaturation {
+        channel_index,
+        channel: channel.name.clone(),
+        onset_samples: Vec::new(),
+        onset_times: Vec::new(),
+        harmonic_ratios: Vec::new(),
+        intervals: Vec::new(),
+    };
+
+    let mut previous_faulted = false;
+    let mut previous_saturated = false;
+    let mut previous_window = None;
+    for &(start, end) in &windows {
+        let n = end - start;
+        let Some(rate) = sample_rate_at(timestamps, start) else {
+            continue;
+        };
+        let window = &values[start..end];
+        let faulted = rms(window) > FAULT_CURRENT_FACTOR * pre_fault_rms.max(f64::EPSILON);
+        // The cycle containing fault inception has a discontinuity of its own,
+        // and the third difference only spans cycles of the same rate.
+        let follows = previous_window.is_some_and(|(s, e)| e == start && e - s == n);
+        let evaluate = faulted && previous_faulted && follows && n >= MIN_SAMPLES_PER_CYCLE;
+        previous_faulted = faulted;
+        previous_window = Some((start, end));
+        if !evaluate {
+            previous_saturated = false;
+            continue;
+        }
+        let omega_dt = 2.0 * PI * frequency / rate;
+
+        let fundamental = dft(window, 1).norm();
+        let harmonic_ratio = (dft(window, 2).norm() + dft(window, 3).norm()) / fundamental;
+        let peak = window.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+        let threshold = THIRD_DIFFERENCE_FACTOR * peak * omega_dt.powi(3) + noise;
+        // Saturation collapses the secondary current; the recovery at the next
+        // zero crossing is a discontinuity too but with rising magnitude.
+        let onset = (start.max(1)..end)
+            .find(|&k| d3[k].abs() > threshold && values[k].abs() < values[k - 1].abs());
+
+        match onset {
+            Some(onset) if harmonic_ratio > HARMONIC_RATIO => {
+                let time = |s: usize| timestamps.get(s).copied().unwrap_or_default();
+                result.onset_samples.push(onset);
+                result.onset_times.push(time(onset));
+                result.harmonic_ratios.push(harmonic_ratio);
+
+                let last = end - 1;
+                match result.intervals.last_mut() {
+                    Some(interval) if previous_saturated => {
+                        interval.end_sample = last;
+                        interval.end_time = time(last);
+                    }
+                    _ => result.intervals.push(SaturationInterval {
+                        start_sample: onset,
+                        end_sample: last,
+                        start_time: time(onset),
+                        end_time: time(last),
+                    }),
+                }
+                previous_saturated = true;
+            }
+            _ => previous_saturated = false,
+        }
+    }
+
+    (!result.onset_samples.is_empty()).then_some(result)
+}
+
+/// Detects CT saturation on every current channel ("A" or "kA").
+///
+/// A cycle is saturated when, during fault current, the third difference of
+/// the waveform shows a discontinuity no sinusoid of that amplitude can
+/// produce, and the cycle carries significant 2nd and 3rd harmonics.
+pub fn detect_ct_saturation(
+    channels: &[SerializableAnalogChannel],
+    timestamps: &[f64],
+    line_frequency: f64,
+) -> Vec<CtSaturation> {
+    let frequency = nominal_frequency(line_frequency);
+    channels
+        .iter()
+        .enumerate()
+        .filter(|(_, channel)| channel.is_current())
+        .filter_map(|(i, channel)| detect_channel(i, channel, timestamps, frequency))
+        .collect()
+}
+
+/// Converts CT saturation results into diagnostics, one per interval.
+/// `recording_start` is the time of the first sample, so the messages give
+/// seconds into the recording.
+pub fn ct_saturation_diagnostics(
+    saturation: &[CtSaturation],
+    recording_start: f64,
+) -> Vec<Diagnostic> {
+    saturation
+        .iter()
+        .flat_map(|result| {
+            result.intervals.iter().map(|interval| {
+                Diagnostic::warning(
+                    "ct-saturation",
+                    format!(
+                        "Possible CT saturation from {:.4} s to {:.4} s; current magnitudes and phasors in this interval are distorted.",
+                        interval.start_time - recording_start,
+                        interval.end_time - recording_start
+                    ),
+                )
+                .on_channel(&result.channel)
+                .at_sample(interval.start_sample)
+                .between(interval.start_time, interval.end_time)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording};
+
+    /// A 50 Hz current sampled at 2 kHz with 10 A load for two cycles and a
+    /// 200 A fault afterwards. When `saturate` is set, the current collapses
+    /// after each peak until the next zero crossing, as a saturated CT does.
+    fn fault_current(saturate: bool) -> Vec<f64> {
+        (0..400)
+            .map(|i| {
+                let phase = (i % 40) as f64 * 9.0;
+                let amplitude = if i < 80 { 10.0 } else { 200.0 };
+                let value = amplitude * phase.to_radians().sin();
+                let collapsed = (120.0..180.0).contains(&phase) || (300.0..360.0).contains(&phase);
+                if saturate && i >= 80 && collapsed {
+                    value * 0.05
+                } else {
+                    value
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_ct_saturation() {
+        let mut info = recording(
+            2000.0,
+            50.0,
+            vec![
+                analog_channel(1, "IA", "A", "A", fault_current(true)),
+                analog_channel(2, "IB", "A", "B", fault_current(false)),
+                analog_channel(3, "VA", "kV", "A", fault_current(true)),
+            ],
+            vec![],
+        );
+
+        // Unix seconds, as parsed recordings have.
+        info.timestamps.iter_mut().for_each(|t| *t += 1000.0);
+        let saturation = detect_ct_saturation(&info.analog_channels, &info.timestamps, 50.0);
+        assert_eq!(saturation.len(), 1);
+        assert_eq!(saturation[0].channel, "IA");
+        assert_eq!(saturation[0].onset_samples.len(), 7);
+        assert_eq!(saturation[0].onset_samples[0] % 40, 14);
+        assert_eq!(saturation[0].intervals.len(), 1);
+        let diagnostics = ct_saturation_diagnostics(&saturation, 1000.0);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("from 0.0670 s"));
+    }
+}
@@ -0,0 +1,423 @@
+// comtrade_rust/src/dc_offset.rs
+// This file contains the fit of the decaying DC offset and its time constant on fault currents, with the X/R ratio and peak asymmetry.
+// This file exists so the X/R ratio of the fault loop can be read from a recording, e.g. to check breaker and CT ratings.
+// RELEVANT FILES: comtrade_rust/src/ct_saturation.rs, comtrade_rust/src/dsp.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+use crate::clipping::is_clipped;
+use crate::dsp::{cycle_windows, nominal_frequency, rms, sample_at};
+use crate::{ComtradeInfo, WasmComtradeError};
+
+/// A channel carries fault current when a cycle's RMS exceeds the pre-fault RMS by this factor.
+const FAULT_CURRENT_FACTOR: f64 = 2.0;
+/// Inception is where the cycle-to-cycle change first reaches this fraction of its maximum.
+const INCEPTION_FRACTION: f64 = 0.1;
+const DEFAULT_FIT_CYCLES: f64 = 3.0;
+/// The range of time constants searched, in seconds.
+const MIN_TIME_CONSTANT: f64 = 1e-3;
+const MAX_TIME_CONSTANT: f64 = 1.0;
+const GRID_POINTS: usize = 120;
+/// Below this ratio of DC offset to AC peak there is no time constant to fit.
+const MIN_DC_RATIO: f64 = 0.02;
+
+/// Options for the DC offset fit. Every field is optional.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct DcOffsetOptions {
+    /// The positions in `analog_channels` of the channels to fit. Defaults
+    /// to every current channel that carries fault current.
+    pub channels: Option<Vec<usize>>,
+    /// The fault inception as Unix seconds. Defaults to the time detected on
+    /// each channel.
+    pub inception_time: Option<f64>,
+    /// The length of the fit after inception in cycles. Shorten it if the
+    /// breaker clears sooner. Defaults to 3.
+    pub fit_cycles: Option<f64>,
+}
+
+/// The fitted DC offset of one channel, in amperes (primary).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DcOffset {
+    /// The position of the channel in `analog_channels`.
+    pub channel_index: usize,
+    /// The channel name.
+    pub channel: String,
+    /// The fault inception as Unix seconds.
+    pub inception_time: f64,
+    /// The peak of the fitted fundamental.
+    pub ac_amplitude: f64,
+    /// The DC offset at inception, signed.
+    pub dc_offset: f64,
+    /// |dc_offset| / ac_amplitude: 0 for a symmetrical fault current, 1 for
+    /// full offset.
+    pub dc_ratio: f64,
+    /// The decay time constant in seconds. `None` when the offset is too
+    /// small to fit.
+    pub time_constant: Option<f64>,
+    /// The X/R ratio of the fault loop, ω × time constant.
+    pub x_over_r: Option<f64>,
+    /// The largest instantaneous current in the fit window.
+    pub peak_current: f64,
+    /// peak_current / ac_amplitude, between 1 (symmetrical) and 2.
+    pub peak_asymmetry: f64,
+    /// The peak factor κ = 1.02 + 0.98·e^(−3R/X) of IEC 60909 for the
+    /// fitted X/R, to compare with `peak_asymmetry`.
+    pub iec_peak_factor: Option<f64>,
+    /// The RMS fit residual relative to `ac_amplitude`.
+    pub residual: f64,
+    /// False if the fit window has clipped samples or CT saturation.
+    pub reliable: bool,
+}
+
+/// The least-squares fit of a cos ωt + b sin ωt + d e^(−t/τ) for one τ.
+struct Fit {
+    coefficients: [f64; 3],
+    residual: f64,
+}
+
+/// Solves the 3×3 system `m x = v` by Gaussian elimination with partial
+/// pivoting, or `None` if it is singular.
+fn solve3(mut m: [[f64; 3]; 3], mut v: [f64; 3]) -> Option<[f64; 3]> {
+    for col in 0..3 {
+        let pivot = (col..3).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+        if m[pivot][col].abs() < 1e-300 {
+            return None;
+        }
+        m.swap(col, pivot);
+        v.swap(col, pivot);
+        let pivot_row = m[col];
+        for row in col + 1..3 {
+            let factor = m[row][col] / pivot_row[col];
+            for (value, pivot) in m[row].iter_mut().zip(pivot_row).skip(col) {
+                *value -= factor * pivot;
+            }
+            v[row] -= factor * v[col];
+        }
+    }
+    let mut x = [0.0; 3];
+    for row in (0..3).rev() {
+        let sum: f64 = (row + 1..3).map(|k| m[row][k] * x[k]).sum();
+        x[row] = (v[row] - sum) / m[row][row];
+    }
+    Some(x)
+}
+
+/// Fits the fundamental and a DC offset decaying with `tau` to `values` at
+/// `times` (seconds after inception).
+fn fit(times: &[f64], values: &[f64], omega: f64, tau: f64) -> Option<Fit> {
+    let basis = |t: f64| [(omega * t).cos(), (omega * t).sin(), (-t / tau).exp()];
+    let mut m = [[0.0; 3]; 3];
+    let mut v = [0.0; 3];
+    for (&t, &y) in times.iter().zip(values) {
+        let f = basis(t);
+        for i in 0..3 {
+            v[i] += f[i] * y;
+            for j in 0..3 {
+                m[i][j] += f[i] * f[j];
+            }
+        }
+    }
+    let coefficients = solve3(m, v)?;
+    let residual = times
+        .iter()
+        .zip(values)
+        .map(|(&t, &y)| {
+            let f = basis(t);
+            let model: f64 = (0..3).map(|i| coefficients[i] * f[i]).sum();
+            (y - model).powi(2)
+        })
+        .sum::<f64>();
+    Some(Fit {
+        coefficients,
+        residual,
+    })
+}
+
+/// Returns the time constant with the smallest fit residual: the best of a
+/// logarithmic grid, refined by golden-section search between its neighbours.
+fn best_time_constant(times: &[f64], values: &[f64], omega: f64) -> Option<(f64, Fit)> {
+    let (low, high) = (MIN_TIME_CONSTANT.ln(), MAX_TIME_CONSTANT.ln());
+    let step = (high - low) / (GRID_POINTS - 1) as f64;
+    let residual = |log_tau: f64| {
+        fit(times, values, omega, log_tau.exp()).map_or(f64::INFINITY, |f| f.residual)
+    };
+    let best = (0..GRID_POINTS)
+        .map(|k| low + step * k as f64)
+        .min_by(|a, b| residual(*a).total_cmp(&residual(*b)))?;
+
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut a, mut b) = ((best - step).max(low), (best + step).min(high));
+    for _ in 0..40 {
+        let c = b - ratio * (b - a);
+        let d = a + ratio * (b - a);
+        if residual(c) < residual(d) {
+            b = d;
+        } else {
+            a = c;
+        }
+    }
+    let tau = ((a + b) / 2.0).exp();
+    Some((tau, fit(times, values, omega, tau)?))
+}
+
+/// Returns the change of each sample from `first` on since one cycle
+/// earlier. The earlier value is interpolated, since after a change of
+/// sampling rate it falls between samples; the largest error the linear
+/// interpolation can make there is taken off, so a steady wave shows no change.
+fn cycle_changes(timestamps: &[f64], values: &[f64], period: f64, first: usize) -> Vec<f64> {
+    let curvature = |j: usize| {
+        if j > 0 && j + 1 < values.len() {
+            (values[j - 1] - 2.0 * values[j] + values[j + 1]).abs()
+        } else {
+            0.0
+        }
+    };
+    (first..values.len())
+        .map(|k| {
+            let time = timestamps[k] - period;
+            let j = sample_at(timestamps, time).max(1);
+            let (t0, t1) = (timestamps[j - 1], timestamps[j]);
+            let fraction = if t1 > t0 {
+                ((time - t0) / (t1 - t0)).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let earlier = values[j - 1] + (values[j] - values[j - 1]) * fraction;
+            let error = fraction * (1.0 - fraction) / 2.0 * curvature(j - 1).max(curvature(j));
+            ((values[k] - earlier).abs() - error).max(0.0)
+        })
+        .collect()
+}
+
+/// Returns the fault inception sample of a channel, or `None` if it never
+/// carries fault current: the first sample whose cycle-to-cycle change is
+/// significant, moved back to where the change leaves the pre-fault noise.
+fn detect_inception(timestamps: &[f64], values: &[f64], frequency: f64) -> Option<usize> {
+    let windows = cycle_windows(timestamps, values.len(), frequency, 1.0);
+    if windows.len() < 3 {
+        return None;
+    }
+    let (first_start, first_end) = windows[0];
+    let pre_fault = rms(&values[first_start..first_end]);
+    let faulted = windows.iter().any(|&(start, end)| {
+        rms(&values[start..end]) > FAULT_CURRENT_FACTOR * pre_fault.max(f64::EPSILON)
+    });
+    if !faulted {
+        return None;
+    }
+    let period = 1.0 / frequency;
+    let first = sample_at(timestamps, timestamps[0] + period);
+    let delta = cycle_changes(timestamps, values, period, first);
+    let largest = delta.iter().fold(0.0f64, |m, d| m.max(*d));
+    // The second cycle of the recording sets the noise floor.
+    let quiet = (sample_at(timestamps, timestamps[0] + 2.0 * period) - first).min(delta.len());
+    let noise = 3.0 * delta[..quiet].iter().fold(0.0f64, |m, d| m.max(*d)) + 1e-9 * largest;
+    let mut k = delta
+        .iter()
+        .position(|&d| d >= INCEPTION_FRACTION * largest)?;
+    while k > 0 && delta[k - 1] > noise {
+        k -= 1;
+    }
+    Some(k + first)
+}
+
+/// Fits the DC offset of the channel at `index` from `inception_time`, or
+/// from the detected inception. The error says why the channel cannot be fitted.
+fn fit_channel(
+    info: &ComtradeInfo,
+    index: usize,
+    inception_time: Option<f64>,
+    fit_cycles: f64,
+) -> Result<DcOffset, &'static str> {
+    let frequency = nominal_frequency(info.frequency);
+    let omega = 2.0 * PI * frequency;
+    let channel = &info.analog_channels[index];
+    let samples = channel.primary_values.len().min(info.timestamps.len());
+    let timestamps = &info.timestamps[..samples];
+    let scale = channel.si_scale();
+    let values: Vec<f64> = channel.primary_values[..samples]
+        .iter()
+        .map(|v| v * scale)
+        .collect();
+    let start = match inception_time {
+        Some(time) => Some(sample_at(timestamps, time)),
+        None => detect_inception(timestamps, &values, frequency),
+    };
+    let start = start
+        .filter(|&s| s < samples)
+        .ok_or("no fault inception was found; give the inception time")?;
+    let inception_time = timestamps[start];
+    let end = timestamps.partition_point(|&t| t <= inception_time + fit_cycles / frequency);
+    if end < start + 4 {
+        return Err("fewer than four samples follow the inception");
+    }
+    let times: Vec<f64> = timestamps[start..end]
+        .iter()
+        .map(|t| t - inception_time)
+        .collect();
+    let window = &values[start..end];
+    let (tau, fitted) =
+        best_time_constant(&times, window, omega).ok_or("the current could not be fitted")?;
+
+    let [a, b, d] = fitted.coefficients;
+    let ac_amplitude = a.hypot(b);
+    if ac_amplitude <= 0.0 {
+        return Err("it has no fundamental current after the inception");
+    }
+    let dc_ratio = d.abs() / ac_amplitude;
+    let time_constant = (dc_ratio >= MIN_DC_RATIO).then_some(tau);
+    let x_over_r = time_constant.map(|tau| omega * tau);
+    let peak_current = window.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+    let saturated = info.ct_saturation.iter().any(|s| {
+        s.channel_index == index
+            && s.intervals
+                .iter()
+                .any(|i| i.start_sample < end && i.end_sample >= start)
+    });
+    Ok(DcOffset {
+        channel_index: index,
+        channel: channel.name.clone(),
+        inception_time,
+        ac_amplitude,
+        dc_offset: d,
+        dc_ratio,
+        time_constant,
+        x_over_r,
+        peak_current,
+        peak_asymmetry: peak_current / ac_amplitude,
+        iec_peak_factor: x_over_r.map(|xr| 1.02 + 0.98 * (-3.0 / xr).exp()),
+        residual: (fitted.residual / window.len() as f64).sqrt() / ac_amplitude,
+        reliable: !saturated && !is_clipped(&info.clipped_intervals, index, start, end - 1),
+    })
+}
+
+/// Fits the decaying DC offset of the fault current on each current channel.
+///
+/// From fault inception over `fit_cycles`, the primary current is fitted as
+/// a fundamental plus a DC offset decaying exponentially from inception. The
+/// time constant gives X/R = ωτ. Current channels without fault current are
+/// left out; a channel chosen explicitly that cannot be fitted, e.g. because
+/// no inception is found on it and no `inception_time` is given, is an error.
+pub fn fit_dc_offsets(
+    info: &ComtradeInfo,
+    options: &DcOffsetOptions,
+) -> Result<Vec<DcOffset>, WasmComtradeError> {
+    let fit_cycles = options
+        .fit_cycles
+        .filter(|c| *c > 0.0)
+        .unwrap_or(DEFAULT_FIT_CYCLES);
+    let chosen: Vec<usize> = match &options.channels {
+        Some(channels) => {
+            for &index in channels {
+                info.analog_channel(index)?;
+            }
+            channels.clone()
+        }
+        None => (0..info.analog_channels.len())
+            .filter(|&i| info.analog_channels[i].is_current())
+            .collect(),
+    };
+
+    let mut results = Vec::new();
+    for index in chosen {
+        match fit_channel(info, index, options.inception_time, fit_cycles) {
+            Ok(offset) => results.push(offset),
+            Err(reason) if options.channels.is_some() => {
+                return Err(WasmComtradeError::AnalysisError(format!(
+                    "The DC offset of channel '{}' cannot be fitted: {}.",
+                    info.analog_channels[index].name, reason
+                )));
+            }
+            Err(_) => {}
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording};
+
+    #[test]
+    fn test_fit_dc_offsets() {
+        // 100 A of load, then at 40 ms a fully offset 10 kA fault with τ = 50 ms.
+        let omega = 2.0 * PI * 50.0;
+        let fault: Vec<f64> = (0..400)
+            .map(|k| {
+                let t = k as f64 / 2000.0;
+                if k < 80 {
+                    0.1 * (omega * t + 1.0).cos()
+                } else {
+                    let t = t - 0.04;
+                    10.0 * ((omega * t).cos() - (-t / 0.05).exp())
+                }
+            })
+            .collect();
+        let load = (0..400)
+            .map(|k| 0.1 * (omega * k as f64 / 2000.0).cos())
+            .collect();
+        let info = recording(
+            2000.0,
+            50.0,
+            vec![
+                analog_channel(1, "IA", "kA", "A", fault),
+                analog_channel(2, "IB", "kA", "B", load),
+            ],
+            vec![],
+        );
+
+        let offsets = fit_dc_offsets(&info, &DcOffsetOptions::default()).unwrap();
+        assert_eq!(offsets.len(), 1);
+        let ia = &offsets[0];
+        assert_eq!(ia.channel, "IA");
+        assert!((ia.inception_time - 0.04).abs() < 1e-9);
+        assert!((ia.ac_amplitude - 10_000.0).abs() < 1.0);
+        assert!((ia.dc_offset + 10_000.0).abs() < 1.0);
+        assert!((ia.time_constant.unwrap() - 0.05).abs() < 1e-5);
+        assert!((ia.x_over_r.unwrap() - omega * 0.05).abs() < 1e-3);
+        let expected_peak = 1.0 + (-0.01f64 / 0.05).exp();
+        assert!((ia.peak_asymmetry - expected_peak).abs() < 0.01);
+        assert!((ia.iec_peak_factor.unwrap() - expected_peak).abs() < 0.05);
+        assert!(ia.residual < 1e-6 && ia.reliable);
+
+        // IB has no fault current, so choosing it needs the inception time.
+        let mut options = DcOffsetOptions {
+            channels: Some(vec![0, 1]),
+            ..Default::default()
+        };
+        let error = fit_dc_offsets(&info, &options).unwrap_err().to_string();
+        assert!(error.contains("'IB'") && error.contains("no fault inception"));
+        options.inception_time = Some(0.04);
+        assert_eq!(fit_dc_offsets(&info, &options).unwrap().len(), 2);
+
+        // The recorder switches from 2 kHz to 5 kHz 10 ms before the fault.
+        let timestamps: Vec<f64> = (0..60)
+            .map(|k| k as f64 / 2000.0)
+            .chain((0..500).map(|k| 0.03 + k as f64 / 5000.0))
+            .collect();
+        let fault = timestamps
+            .iter()
+            .map(|&t| {
+                if t < 0.04 {
+                    0.1 * (omega * t + 1.0).cos()
+                } else {
+                    let t = t - 0.04;
+                    10.0 * ((omega * t).cos() - (-t / 0.05).exp())
+                }
+            })
+            .collect();
+        let mut info = recording(
+            2000.0,
+            50.0,
+            vec![analog_channel(1, "IA", "kA", "A", fault)],
+            vec![],
+        );
+        info.timestamps = timestamps;
+        let offsets = fit_dc_offsets(&info, &DcOffsetOptions::default()).unwrap();
+        assert!((offsets[0].inception_time - 0.04).abs() < 1e-9);
+        assert!((offsets[0].time_constant.unwrap() - 0.05).abs() < 1e-5);
+    }
+}
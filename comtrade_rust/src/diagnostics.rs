@@ -0,0 +1,104 @@
+// comtrade_rust/src/diagnostics.rs
+// This file contains the structured diagnostic type shared by the validator and the signal-quality detectors.
+// This file exists so each finding names the rule it violates and where in the recording it was found.
+// RELEVANT FILES: comtrade_rust/src/validation.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+/// How serious a diagnostic is.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The recording violates the standard or is internally inconsistent.
+    Error,
+    /// The recording is usable but the finding may affect results.
+    Warning,
+    /// An observation that does not indicate a problem by itself.
+    Info,
+}
+
+/// A single finding about a recording, with the rule it concerns and its location.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// How serious the finding is.
+    pub severity: Severity,
+    /// A stable identifier of the rule, e.g. "cfg-field-length" or "dat-sample-order".
+    pub rule: String,
+    /// A human-readable description of the finding.
+    pub message: String,
+    /// The file the finding refers to ("CFG" or "DAT"), if any.
+    pub file: Option<String>,
+    /// The 1-based line number in the file, if any.
+    pub line: Option<usize>,
+    /// The name of the CFG field, if any (e.g. "ph", "uu").
+    pub field: Option<String>,
+    /// The name of the affected channel, if any.
+    pub channel: Option<String>,
+    /// The 0-based index of the first affected sample, if any.
+    pub sample: Option<usize>,
+    /// The start of the affected interval as Unix seconds, if any.
+    pub start_time: Option<f64>,
+    /// The end of the affected interval as Unix seconds, if any.
+    pub end_time: Option<f64>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, rule: &str, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            rule: rule.to_string(),
+            message: message.into(),
+            file: None,
+            line: None,
+            field: None,
+            channel: None,
+            sample: None,
+            start_time: None,
+            end_time: None,
+        }
+    }
+
+    pub fn error(rule: &str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, rule, message)
+    }
+
+    pub fn warning(rule: &str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, rule, message)
+    }
+
+    pub fn info(rule: &str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Info, rule, message)
+    }
+
+    /// Sets the file and 1-based line the finding refers to.
+    pub fn at_line(mut self, file: &str, line: usize) -> Self {
+        self.file = Some(file.to_string());
+        self.line = Some(line);
+        self
+    }
+
+    /// Sets the CFG field the finding refers to.
+    pub fn in_field(mut self, field: &str) -> Self {
+        self.field = Some(field.to_string());
+        self
+    }
+
+    /// Sets the channel the finding refers to.
+    pub fn on_channel(mut self, channel: &str) -> Self {
+        self.channel = Some(channel.to_string());
+        self
+    }
+
+    /// Sets the first affected sample.
+    pub fn at_sample(mut self, sample: usize) -> Self {
+        self.sample = Some(sample);
+        self
+    }
+
+    /// Sets the affected time interval in Unix seconds.
+    pub fn between(mut self, start_time: f64, end_time: f64) -> Self {
+        self.start_time = Some(start_time);
+        self.end_time = Some(end_time);
+        self
+    }
+}
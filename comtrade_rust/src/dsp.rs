@@ -0,0 +1,337 @@
+// comtrade_rust/src/dsp.rs
+// This file contains the signal processing primitives shared by the analysis modules.
+// This file exists so phasor, harmonic and RMS calculations use the same windowing on variable-rate recordings.
+// RELEVANT FILES: comtrade_rust/src/calculated.rs, comtrade_rust/src/ct_saturation.rs, comtrade_rust/src/fault_location.rs, comtrade_rust/src/filter.rs, comtrade_rust/src/harmonics.rs, comtrade_rust/src/impedance.rs, comtrade_rust/src/merge.rs, comtrade_rust/src/resample.rs, comtrade_rust/src/writer.rs, comtrade_rust/src/lib.rs
+
+use num_complex::Complex64;
+use std::f64::consts::PI;
+
+use crate::ComtradeInfo;
+
+/// The line frequency assumed when the CFG declares 0 Hz.
+pub const DEFAULT_FREQUENCY: f64 = 50.0;
+
+/// How much the sample spacing may vary within a window before it is treated
+/// as a change of sampling rate rather than timestamp jitter.
+const RATE_TOLERANCE: f64 = 0.1;
+
+/// Returns the nominal frequency to analyse at, falling back to 50 Hz when the
+/// CFG line frequency is missing or not positive.
+pub fn nominal_frequency(line_frequency: f64) -> f64 {
+    if line_frequency > 0.0 {
+        line_frequency
+    } else {
+        DEFAULT_FREQUENCY
+    }
+}
+
+/// Returns the sampling rate around sample `i`, derived from the spacing of
+/// the timestamps (in seconds).
+pub fn sample_rate_at(timestamps: &[f64], i: usize) -> Option<f64> {
+    let (a, b) = if i + 1 < timestamps.len() {
+        (timestamps[i], timestamps[i + 1])
+    } else if i > 0 && i < timestamps.len() {
+        (timestamps[i - 1], timestamps[i])
+    } else {
+        return None;
+    };
+    let dt = b - a;
+    (dt > 0.0).then(|| 1.0 / dt)
+}
+
+/// Returns the sampling rate over samples `start..end` (averaged from the
+/// timestamps), or `None` if the window straddles a change of sampling rate.
+pub fn uniform_rate(timestamps: &[f64], start: usize, end: usize) -> Option<f64> {
+    let window = timestamps.get(start..end)?;
+    if window.len() < 2 {
+        return None;
+    }
+    let (min, max) = window
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .fold((f64::INFINITY, 0.0f64), |(min, max), dt| {
+            (min.min(dt), max.max(dt))
+        });
+    if min <= 0.0 || max > min * (1.0 + RATE_TOLERANCE) {
+        return None;
+    }
+    Some((window.len() - 1) as f64 / (window[window.len() - 1] - window[0]))
+}
+
+/// Returns the index of the first sample at or after `time` (Unix seconds).
+pub fn sample_at(timestamps: &[f64], time: f64) -> usize {
+    timestamps.partition_point(|&t| t < time)
+}
+
+/// Returns `values`, sampled at `times`, linearly interpolated at `time`, or
+/// `None` outside the samples.
+pub fn interpolate(times: &[f64], values: &[f64], time: f64) -> Option<f64> {
+    let n = times.len().min(values.len());
+    if n == 0 || time < times[0] || time > times[n - 1] {
+        return None;
+    }
+    let k = times[..n].partition_point(|&t| t <= time);
+    if k >= n {
+        return Some(values[n - 1]);
+    }
+    let (t0, t1) = (times[k - 1], times[k]);
+    let fraction = if t1 > t0 {
+        (time - t0) / (t1 - t0)
+    } else {
+        0.0
+    };
+    Some(values[k - 1] + (values[k] - values[k - 1]) * fraction)
+}
+
+/// Splits the samples into runs of constant sampling rate, as (first
+/// sample, one past the last) pairs covering every sample. A sample belongs
+/// to the run of the spacing that follows it, the last sample to the run
+/// before it.
+pub fn rate_sections(timestamps: &[f64]) -> Vec<(usize, usize)> {
+    let n = timestamps.len();
+    let mut sections = Vec::new();
+    let mut start = 0;
+    while start < n {
+        let mut end = start + 1;
+        if let Some(first) = timestamps.get(start + 1).map(|t| t - timestamps[start]) {
+            while end < n
+                && (timestamps[end] - timestamps[end - 1] - first).abs() <= first * RATE_TOLERANCE
+            {
+                end += 1;
+            }
+            // The spacing after sample `end - 1` differs, so it starts the next run.
+            if end < n {
+                end -= 1;
+            }
+        }
+        sections.push((start, end));
+        start = end;
+    }
+    sections
+}
+
+/// Returns the number of samples in one cycle of `frequency` at `rate`, at least 1.
+pub fn samples_per_cycle(rate: f64, frequency: f64) -> usize {
+    ((rate / frequency).round() as usize).max(1)
+}
+
+/// Returns the one-cycle windows, advanced by `step_cycles`, as (first
+/// sample, one past the last) pairs. Windows spanning a change of sampling
+/// rate are skipped.
+pub fn cycle_windows(
+    timestamps: &[f64],
+    samples: usize,
+    frequency: f64,
+    step_cycles: f64,
+) -> Vec<(usize, usize)> {
+    let mut windows = Vec::new();
+    let mut start = 0;
+    while start < samples {
+        let Some(rate) = sample_rate_at(timestamps, start) else {
+            break;
+        };
+        let n = samples_per_cycle(rate, frequency);
+        if start + n > samples {
+            break;
+        }
+        if uniform_rate(timestamps, start, start + n).is_some() {
+            windows.push((start, start + n));
+        }
+        start += ((n as f64 * step_cycles).round() as usize).max(1);
+    }
+    windows
+}
+
+/// Computes the phasor at DFT bin `bin` over `window`. When the window spans
+/// exactly one cycle of the fundamental, `bin` is the harmonic order; over `m`
+/// cycles, harmonic `h` is at bin `h * m`.
+///
+/// The result is scaled to the peak amplitude, with the angle referenced to a
+/// cosine at the first sample of the window. Bin 0 gives the mean.
+pub fn dft(window: &[f64], bin: usize) -> Complex64 {
+    let n = window.len();
+    if n == 0 {
+        return Complex64::new(0.0, 0.0);
+    }
+    let step = -2.0 * PI * bin as f64 / n as f64;
+    let sum: Complex64 = window
+        .iter()
+        .enumerate()
+        .map(|(k, &x)| Complex64::from_polar(x, step * k as f64))
+        .sum();
+    if bin == 0 {
+        sum / n as f64
+    } else {
+        sum * (2.0 / n as f64)
+    }
+}
+
+/// Returns the samples, start inclusive and end exclusive, of the uniformly
+/// sampled cycle ending at the first sample at or after `time`.
+pub fn phasor_window(timestamps: &[f64], frequency: f64, time: f64) -> Option<(usize, usize)> {
+    let end = sample_at(timestamps, time) + 1;
+    let rate = uniform_rate(timestamps, end.checked_sub(2)?, end)?;
+    let start = end.checked_sub(samples_per_cycle(rate, frequency))?;
+    uniform_rate(timestamps, start, end)?;
+    Some((start, end))
+}
+
+/// Returns the fundamental phasors of `channels` (positions in
+/// `analog_channels`) in volts or amperes from primary values, over the cycle
+/// ending at the first sample at or after `time`. The angles are referenced
+/// to `time`, so phasors from two recordings on the same time base compare
+/// directly. `None` if there is no uniformly sampled cycle there.
+pub fn phasors_at<const N: usize>(
+    info: &ComtradeInfo,
+    channels: [usize; N],
+    frequency: f64,
+    time: f64,
+) -> Option<[Complex64; N]> {
+    let timestamps = &info.timestamps;
+    let (start, end) = phasor_window(timestamps, frequency, time)?;
+    let reference = Complex64::from_polar(1.0, -2.0 * PI * frequency * (timestamps[start] - time));
+
+    let mut phasors = [Complex64::new(0.0, 0.0); N];
+    for (phasor, &index) in phasors.iter_mut().zip(&channels) {
+        let channel = info.analog_channels.get(index)?;
+        let window = channel.primary_values.get(start..end)?;
+        *phasor = dft(window, 1) * channel.si_scale() * reference;
+    }
+    Some(phasors)
+}
+
+/// Designs a linear-phase low-pass FIR filter with `taps` coefficients
+/// (rounded up to odd) by the Blackman-windowed sinc method, with unity gain
+/// at DC. `cutoff` and `rate` are in Hz.
+pub fn lowpass_fir(cutoff: f64, rate: f64, taps: usize) -> Vec<f64> {
+    let taps = taps.max(1) | 1;
+    let middle = (taps / 2) as f64;
+    let fc = cutoff / rate;
+    let mut coefficients: Vec<f64> = (0..taps)
+        .map(|k| {
+            let x = k as f64 - middle;
+            let sinc = if x == 0.0 {
+                2.0 * fc
+            } else {
+                (2.0 * PI * fc * x).sin() / (PI * x)
+            };
+            let phase = 2.0 * PI * k as f64 / (taps - 1).max(1) as f64;
+            let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+            sinc * if taps == 1 { 1.0 } else { window }
+        })
+        .collect();
+    let sum: f64 = coefficients.iter().sum();
+    if sum != 0.0 {
+        coefficients.iter_mut().for_each(|c| *c /= sum);
+    }
+    coefficients
+}
+
+/// Applies an odd-length linear-phase FIR filter centred on each sample, so
+/// the output has no delay. The first and last values are repeated beyond
+/// the ends.
+pub fn fir_centred(values: &[f64], coefficients: &[f64]) -> Vec<f64> {
+    let n = values.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let middle = coefficients.len() / 2;
+    (0..n)
+        .map(|i| {
+            coefficients
+                .iter()
+                .enumerate()
+                .map(|(k, c)| {
+                    let j = (i + k).saturating_sub(middle).min(n - 1);
+                    c * values[j]
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// The 16-bit raw range declared for channels derived by the analysis modules.
+pub const RAW_LIMIT: f64 = 32767.0;
+
+/// The raw count the extremes of a derived channel are mapped to, leaving
+/// headroom below `RAW_LIMIT` so they are not taken for clipping.
+const RAW_PEAK: f64 = 32000.0;
+
+/// A CFG conversion, value = multiplier * raw + offset, with the raw range it declares.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RawScaling {
+    pub multiplier: f64,
+    pub offset: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl RawScaling {
+    /// Spreads the finite `values` over the 16-bit raw range, so a derived
+    /// channel keeps its resolution when it is written as raw counts.
+    pub fn spanning(values: &[f64]) -> Self {
+        let (multiplier, offset) = match finite_range(values) {
+            Some((low, high)) if high > low => {
+                ((high - low) / (2.0 * RAW_PEAK), (low + high) / 2.0)
+            }
+            Some((low, _)) => (1.0, low),
+            None => (1.0, 0.0),
+        };
+        RawScaling {
+            multiplier,
+            offset,
+            min: -RAW_LIMIT,
+            max: RAW_LIMIT,
+        }
+    }
+
+    /// Converts a value to the nearest raw count.
+    pub fn raw(&self, value: f64) -> f64 {
+        ((value - self.offset) / self.multiplier).round()
+    }
+}
+
+/// Returns the lowest and highest finite value, or `None` if there are none.
+pub fn finite_range(values: &[f64]) -> Option<(f64, f64)> {
+    values
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold(None, |range, &v| match range {
+            Some((low, high)) => Some((v.min(low), v.max(high))),
+            None => Some((v, v)),
+        })
+}
+
+/// Returns the RMS value of `window`.
+pub fn rms(window: &[f64]) -> f64 {
+    if window.is_empty() {
+        return 0.0;
+    }
+    (window.iter().map(|x| x * x).sum::<f64>() / window.len() as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::sine;
+
+    #[test]
+    fn test_dft_recovers_harmonics() {
+        let fundamental = sine(100.0, 50.0, 0.0, 1000.0, 20);
+        let third = sine(10.0, 150.0, 0.0, 1000.0, 20);
+        let window: Vec<f64> = fundamental.iter().zip(&third).map(|(a, b)| a + b).collect();
+
+        let h1 = dft(&window, 1);
+        assert!((h1.norm() - 100.0).abs() < 1e-9);
+        // A sine lags the cosine reference by 90°.
+        assert!((h1.arg().to_degrees() + 90.0).abs() < 1e-9);
+        assert!((dft(&window, 3).norm() - 10.0).abs() < 1e-9);
+        assert!(dft(&window, 2).norm() < 1e-9);
+
+        let scaling = RawScaling::spanning(&[f64::NAN, -1.0, 3.0]);
+        assert_eq!((scaling.multiplier, scaling.offset), (2.0 / 32000.0, 1.0));
+        assert_eq!((scaling.min, scaling.max), (-RAW_LIMIT, RAW_LIMIT));
+        assert_eq!(RawScaling::spanning(&[2.0, 2.0]).offset, 2.0);
+        assert!((rms(&fundamental) - 100.0 / 2f64.sqrt()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,529 @@
+// comtrade_rust/src/fault_location.rs
+// This file contains the two-ended fault location from the negative sequence voltages and currents at both line terminals.
+// This file exists so faults on long transmission lines can be located without the fault resistance and infeed errors of single-ended methods.
+// RELEVANT FILES: comtrade_rust/src/merge.rs, comtrade_rust/src/phases.rs, comtrade_rust/src/dsp.rs, comtrade_rust/src/clipping.rs, comtrade_rust/src/lib.rs
+
+use num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+use crate::clipping::is_clipped;
+use crate::dsp::{cycle_windows, nominal_frequency, phasor_window, phasors_at};
+use crate::phases::{is_phase_to_phase, sequence_components, three_phase_set};
+use crate::series::DerivedSeries;
+use crate::{ComtradeInfo, WasmComtradeError};
+
+/// The spacing of the estimates by default, in cycles.
+const DEFAULT_STEP_CYCLES: f64 = 0.25;
+/// The fault windows are those whose summed sequence current reaches this
+/// fraction of its maximum.
+const FAULT_CURRENT_FRACTION: f64 = 0.5;
+/// Below this ratio of negative to positive sequence current the fault is
+/// taken as balanced and the positive sequence is used instead.
+const BALANCED_RATIO: f64 = 0.05;
+/// The clock error used to estimate the sensitivity to it, in seconds.
+const CLOCK_STEP: f64 = 1e-3;
+
+/// Options for the two-ended fault location. The line impedance is required.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FaultLocationOptions {
+    /// The positive sequence impedance of the whole line in primary ohms.
+    /// The negative sequence impedance of a line is the same.
+    pub z1_magnitude: Option<f64>,
+    /// The angle of the line impedance in degrees. Defaults to 85.
+    pub z1_angle_deg: Option<f64>,
+    /// The line length in any unit, e.g. km; the distances are reported in
+    /// the same unit. Defaults to 1, so distances are per unit of the line.
+    pub line_length: Option<f64>,
+    /// The positions in `analog_channels` of the A, B and C phase-to-neutral
+    /// voltages at the local terminal. Defaults to the first such set.
+    pub local_voltages: Option<[usize; 3]>,
+    /// The local A, B and C currents, flowing into the line. Defaults to the
+    /// first three-phase current set.
+    pub local_currents: Option<[usize; 3]>,
+    /// The remote voltages. Defaults to the first set of the remote
+    /// recording, or the second set when both ends are in one recording.
+    pub remote_voltages: Option<[usize; 3]>,
+    /// The remote currents, flowing into the line, chosen like the voltages.
+    pub remote_currents: Option<[usize; 3]>,
+    /// Seconds added to the remote timestamps to express them on the local
+    /// time base, e.g. the `offset` of an `Alignment`. Defaults to 0.
+    pub remote_offset: Option<f64>,
+    /// The uncertainty of the clock alignment in seconds, e.g. the
+    /// `uncertainty` of an `Alignment`. Defaults to unknown.
+    pub clock_uncertainty: Option<f64>,
+    /// The acceptable location error in the unit of `line_length`. Defaults
+    /// to 1% of the line.
+    pub target_accuracy: Option<f64>,
+    /// The spacing of the estimates in cycles. Defaults to 0.25.
+    pub step_cycles: Option<f64>,
+}
+
+/// The located fault and how far the result depends on the clocks.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FaultLocation {
+    /// "negative", or "positive" for a balanced fault.
+    pub sequence: String,
+    /// The local voltage and current channels used.
+    pub local_channels: Vec<String>,
+    /// The remote voltage and current channels used.
+    pub remote_channels: Vec<String>,
+    /// The distance from the local terminal using the phasors as aligned,
+    /// the median over the fault windows.
+    pub distance: f64,
+    /// `distance` as a percentage of the line length.
+    pub distance_percent: f64,
+    /// The distance from the local terminal that does not depend on the
+    /// clocks: both ends see the same voltage magnitude at the fault.
+    pub unsynchronised_distance: f64,
+    /// How far the remote clock runs ahead of the local one after
+    /// `remote_offset`, in seconds, implied by `unsynchronised_distance`.
+    /// Ambiguous by whole cycles.
+    pub clock_error: f64,
+    /// The change of `distance` per millisecond of clock error.
+    pub sensitivity: f64,
+    /// The clock alignment needed to locate the fault within the target
+    /// accuracy, in seconds.
+    pub required_alignment: Option<f64>,
+    /// The location error the given clock uncertainty may cause.
+    pub expected_error: Option<f64>,
+    /// The synchronised distance at each fault window.
+    pub estimates: DerivedSeries,
+    /// False if a voltage or current at either terminal is clipped in a
+    /// fault window used for `distance`.
+    pub reliable: bool,
+    /// Reasons to doubt the location.
+    pub warnings: Vec<String>,
+}
+
+/// The zero, positive and negative sequence voltages and currents of a
+/// terminal.
+type Sequences = ([Complex64; 3], [Complex64; 3]);
+
+/// The voltage and current channels of one line terminal.
+struct Terminal<'a> {
+    info: &'a ComtradeInfo,
+    voltages: [usize; 3],
+    currents: [usize; 3],
+    /// Seconds added to the timestamps to reach the local time base.
+    offset: f64,
+}
+
+impl Terminal<'_> {
+    /// Returns the zero, positive and negative sequence voltages and currents
+    /// over the cycle ending at local `time`.
+    fn sequences(&self, frequency: f64, time: f64) -> Option<Sequences> {
+        let time = time - self.offset;
+        let voltages = phasors_at(self.info, self.voltages, frequency, time)?;
+        let currents = phasors_at(self.info, self.currents, frequency, time)?;
+        Some((sequence_components(voltages), sequence_components(currents)))
+    }
+
+    /// Returns true if any of the channels is clipped in the cycle ending at
+    /// local `time`.
+    fn clipped(&self, frequency: f64, time: f64) -> bool {
+        phasor_window(&self.info.timestamps, frequency, time - self.offset).is_some_and(
+            |(start, end)| {
+                self.voltages
+                    .iter()
+                    .chain(&self.currents)
+                    .any(|&index| is_clipped(&self.info.clipped_intervals, index, start, end - 1))
+            },
+        )
+    }
+
+    fn channel_names(&self) -> Vec<String> {
+        self.voltages
+            .iter()
+            .chain(&self.currents)
+            .map(|&i| self.info.analog_channels[i].name.clone())
+            .collect()
+    }
+}
+
+/// Returns an explicit channel set after checking it, or the first
+/// three-phase voltage or current set not already used.
+fn terminal_set(
+    info: &ComtradeInfo,
+    explicit: Option<[usize; 3]>,
+    used: &[usize],
+    voltage: bool,
+    what: &str,
+) -> Result<[usize; 3], WasmComtradeError> {
+    if let Some(set) = explicit {
+        for index in set {
+            info.analog_channel(index)?;
+        }
+        return Ok(set);
+    }
+    let channels = &info.analog_channels;
+    three_phase_set(channels, |c| {
+        let kind = if voltage {
+            c.is_voltage() && !is_phase_to_phase(&c.phase)
+        } else {
+            c.is_current()
+        };
+        kind && !used.iter().any(|&u| std::ptr::eq(&channels[u], c))
+    })
+    .ok_or_else(|| {
+        WasmComtradeError::AnalysisError(format!(
+            "No A, B and C {} channels were found for the {} terminal.",
+            if voltage { "voltage" } else { "current" },
+            what
+        ))
+    })
+}
+
+/// Returns the fault position in per unit from the local terminal with
+/// synchronised phasors, from V_S − m·Z·I_S = V_R − (1 − m)·Z·I_R.
+fn synchronised(vs: Complex64, is: Complex64, vr: Complex64, ir: Complex64, z: Complex64) -> f64 {
+    let denominator = z * (is + ir);
+    ((vs - vr + z * ir) * denominator.conj()).re / denominator.norm_sqr()
+}
+
+/// Returns the fault position in per unit from the local terminal that
+/// gives equal fault voltage magnitudes from both ends, whatever the angle
+/// between the clocks, and that angle in radians.
+fn unsynchronised(
+    vs: Complex64,
+    is: Complex64,
+    vr: Complex64,
+    ir: Complex64,
+    z: Complex64,
+) -> Option<(f64, f64)> {
+    // |A − mB| = |C + mD|
+    let (a, b, c, d) = (vs, z * is, vr - z * ir, z * ir);
+    let qa = b.norm_sqr() - d.norm_sqr();
+    let qb = -2.0 * ((a * b.conj()).re + (c * d.conj()).re);
+    let qc = a.norm_sqr() - c.norm_sqr();
+    let roots = if qa.abs() < 1e-9 * (b.norm_sqr() + d.norm_sqr()) {
+        vec![-qc / qb]
+    } else {
+        let discriminant = qb * qb - 4.0 * qa * qc;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        vec![(-qb + root) / (2.0 * qa), (-qb - root) / (2.0 * qa)]
+    };
+    let m = roots.into_iter().filter(|m| m.is_finite()).min_by(|x, y| {
+        let outside = |m: f64| (m - m.clamp(0.0, 1.0)).abs();
+        outside(*x).total_cmp(&outside(*y))
+    })?;
+    let angle = ((a - m * b) / (c + m * d)).arg();
+    Some((m, angle))
+}
+
+/// Returns the median of `values`, which must not be empty.
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let n = values.len();
+    if n % 2 == 1 {
+        values[n / 2]
+    } else {
+        (values[n / 2 - 1] + values[n / 2]) / 2.0
+    }
+}
+
+/// Locates a line fault from the voltages and currents at both terminals.
+///
+/// The negative sequence is used, so the result does not depend on the
+/// fault resistance, the load or the source impedances behind either end.
+/// The remote terminal comes from `remote_recording` when given, otherwise
+/// from the second voltage and current sets of `info`. Besides the distance
+/// with the clocks as aligned, an unsynchronised solution is given together
+/// with the clock error it implies and the alignment quality the
+/// synchronised distance needs.
+pub fn locate_fault(
+    info: &ComtradeInfo,
+    remote_recording: Option<&ComtradeInfo>,
+    options: &FaultLocationOptions,
+) -> Result<FaultLocation, WasmComtradeError> {
+    let magnitude = options.z1_magnitude.filter(|z| *z > 0.0).ok_or_else(|| {
+        WasmComtradeError::AnalysisError(
+            "The line impedance (z1_magnitude) is required for fault location.".to_string(),
+        )
+    })?;
+    let z = Complex64::from_polar(magnitude, options.z1_angle_deg.unwrap_or(85.0).to_radians());
+    let length = options.line_length.filter(|l| *l > 0.0).unwrap_or(1.0);
+    let target = options
+        .target_accuracy
+        .filter(|t| *t > 0.0)
+        .unwrap_or(length / 100.0);
+    let step_cycles = options
+        .step_cycles
+        .filter(|s| *s > 0.0)
+        .unwrap_or(DEFAULT_STEP_CYCLES);
+    let frequency = nominal_frequency(info.frequency);
+    let omega = 2.0 * PI * frequency;
+
+    let local_voltages = terminal_set(info, options.local_voltages, &[], true, "local")?;
+    let local_currents = terminal_set(info, options.local_currents, &[], false, "local")?;
+    let local = Terminal {
+        info,
+        voltages: local_voltages,
+        currents: local_currents,
+        offset: 0.0,
+    };
+    let remote_info = remote_recording.unwrap_or(info);
+    let (used_voltages, used_currents): (&[usize], &[usize]) = if remote_recording.is_some() {
+        (&[], &[])
+    } else {
+        (&local_voltages, &local_currents)
+    };
+    let remote = Terminal {
+        info: remote_info,
+        voltages: terminal_set(
+            remote_info,
+            options.remote_voltages,
+            used_voltages,
+            true,
+            "remote",
+        )?,
+        currents: terminal_set(
+            remote_info,
+            options.remote_currents,
+            used_currents,
+            false,
+            "remote",
+        )?,
+        offset: options.remote_offset.unwrap_or(0.0),
+    };
+
+    let samples = local_voltages
+        .iter()
+        .chain(&local_currents)
+        .map(|&i| info.analog_channels[i].primary_values.len())
+        .min()
+        .unwrap_or(0)
+        .min(info.timestamps.len());
+    let windows = cycle_windows(&info.timestamps, samples, frequency, step_cycles);
+    let points: Vec<(f64, Sequences, Sequences)> = windows
+        .iter()
+        .filter_map(|&(_, end)| {
+            let time = info.timestamps[end - 1];
+            Some((
+                time,
+                local.sequences(frequency, time)?,
+                remote.sequences(frequency, time)?,
+            ))
+        })
+        .collect();
+    if points.is_empty() {
+        return Err(WasmComtradeError::AnalysisError(
+            "The two terminals do not overlap in time.".to_string(),
+        ));
+    }
+
+    let mut warnings = Vec::new();
+    let peak = |sequence: usize| {
+        points
+            .iter()
+            .map(|(_, (_, is), (_, ir))| is[sequence].norm() + ir[sequence].norm())
+            .fold(0.0, f64::max)
+    };
+    let sequence = if peak(2) < BALANCED_RATIO * peak(1) {
+        warnings.push(
+            "The negative sequence current is negligible, so the fault is taken as balanced and \
+             the positive sequence is used; load current and fault resistance affect the result."
+                .to_string(),
+        );
+        1
+    } else {
+        2
+    };
+    let threshold = FAULT_CURRENT_FRACTION * peak(sequence);
+
+    let mut estimates = DerivedSeries::new("Fault distance", "");
+    let mut distances = Vec::new();
+    let mut free_distances = Vec::new();
+    let mut angles = Vec::new();
+    let mut sensitivities = Vec::new();
+    let mut clipped = false;
+    for (time, (vs, is), (vr, ir)) in &points {
+        let (vs, is, vr, ir) = (vs[sequence], is[sequence], vr[sequence], ir[sequence]);
+        if is.norm() + ir.norm() < threshold || threshold == 0.0 {
+            continue;
+        }
+        clipped |= local.clipped(frequency, *time) || remote.clipped(frequency, *time);
+        let m = synchronised(vs, is, vr, ir, z);
+        estimates.push(*time, m * length);
+        distances.push(m * length);
+        // The remote phasors as if the remote clock ran ahead by ±CLOCK_STEP.
+        let shifted = |error: f64| {
+            let rotation = Complex64::from_polar(1.0, -omega * error);
+            synchronised(vs, is, vr * rotation, ir * rotation, z)
+        };
+        sensitivities.push((shifted(CLOCK_STEP) - shifted(-CLOCK_STEP)).abs() / 2.0 * length);
+        if let Some((m, angle)) = unsynchronised(vs, is, vr, ir, z) {
+            free_distances.push(m * length);
+            angles.push(angle);
+        }
+    }
+    if distances.is_empty() {
+        return Err(WasmComtradeError::AnalysisError(
+            "No fault current was found at the two terminals.".to_string(),
+        ));
+    }
+
+    let distance = median(&mut distances);
+    if !(0.0..=length).contains(&distance) {
+        warnings.push(format!(
+            "The fault appears outside the line ({:.3} of {}); check the line impedance, the CT \
+             polarity and the clock alignment.",
+            distance, length
+        ));
+    }
+    let (unsynchronised_distance, clock_error) = if free_distances.is_empty() {
+        warnings.push("The unsynchronised solution has no real root.".to_string());
+        (f64::NAN, f64::NAN)
+    } else {
+        (median(&mut free_distances), median(&mut angles) / omega)
+    };
+    if clipped {
+        warnings.push(
+            "A voltage or current is clipped during the fault; the location is unreliable."
+                .to_string(),
+        );
+    }
+    // Per millisecond, from the estimate over ±CLOCK_STEP.
+    let sensitivity = median(&mut sensitivities) * 1e-3 / CLOCK_STEP;
+    let required_alignment = (sensitivity > 0.0).then(|| target / sensitivity * 1e-3);
+    let expected_error = options
+        .clock_uncertainty
+        .filter(|u| u.is_finite())
+        .map(|u| sensitivity * u * 1e3);
+    if let (Some(required), Some(uncertainty)) = (required_alignment, options.clock_uncertainty)
+        && uncertainty > required
+    {
+        warnings.push(format!(
+            "The clocks are aligned to {} s but {} s is needed for the target accuracy; prefer \
+             the unsynchronised distance.",
+            uncertainty, required
+        ));
+    }
+
+    Ok(FaultLocation {
+        sequence: if sequence == 2 {
+            "negative"
+        } else {
+            "positive"
+        }
+        .to_string(),
+        local_channels: local.channel_names(),
+        remote_channels: remote.channel_names(),
+        distance,
+        distance_percent: distance / length * 100.0,
+        unsynchronised_distance,
+        clock_error,
+        sensitivity,
+        required_alignment,
+        expected_error,
+        estimates,
+        reliable: !clipped,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipping::ClippedInterval;
+    use crate::phases::operator_a;
+    use crate::test_support::{analog_channel, recording};
+
+    /// Builds the A, B and C channels of a terminal from its sequence
+    /// phasors before and after the fault at sample 100.
+    fn channels(
+        prefix: &str,
+        units: &str,
+        healthy: Complex64,
+        fault: [Complex64; 2],
+    ) -> Vec<crate::SerializableAnalogChannel> {
+        let a = operator_a();
+        let phases = |[p, n]: [Complex64; 2]| [p + n, a * a * p + a * n, a * p + a * a * n];
+        let before = phases([healthy, Complex64::new(0.0, 0.0)]);
+        let after = phases(fault);
+        ["A", "B", "C"]
+            .iter()
+            .enumerate()
+            .map(|(k, p)| {
+                let values = (0..300)
+                    .map(|s| {
+                        let phasor = if s < 100 { before[k] } else { after[k] };
+                        let t = s as f64 / 1000.0;
+                        (phasor * Complex64::from_polar(1.0, 2.0 * PI * 50.0 * t)).re
+                    })
+                    .collect();
+                analog_channel(1, &format!("{}{}", prefix, p), units, p, values)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_locate_fault() {
+        // A fault 30 km along a 100 km line of 40 Ω at 85°.
+        let z = Complex64::from_polar(40.0, 85f64.to_radians());
+        let m = 0.3;
+        let fault_voltage = Complex64::from_polar(20.0, 170f64.to_radians());
+        let is2 = Complex64::from_polar(1.0, -80f64.to_radians());
+        let ir2 = Complex64::from_polar(0.6, -70f64.to_radians());
+        let vs2 = fault_voltage + m * z * is2;
+        let vr2 = fault_voltage + (1.0 - m) * z * ir2;
+        let v1 = Complex64::new(180.0, 0.0);
+        let i1 = Complex64::from_polar(1.5, -20f64.to_radians());
+
+        let mut local = channels("VS", "kV", v1, [v1 * 0.8, vs2]);
+        local.extend(channels("IS", "kA", i1, [i1 * 2.0, is2]));
+        let mut remote = channels("VR", "kV", v1, [v1 * 0.85, vr2]);
+        remote.extend(channels("IR", "kA", -i1, [-i1, ir2]));
+        let local = recording(1000.0, 50.0, local, vec![]);
+        let mut remote = recording(1000.0, 50.0, remote, vec![]);
+        // The remote clock runs 1 ms ahead.
+        for t in &mut remote.timestamps {
+            *t += 1e-3;
+        }
+
+        let mut options = FaultLocationOptions {
+            z1_magnitude: Some(40.0),
+            line_length: Some(100.0),
+            clock_uncertainty: Some(1e-3),
+            ..Default::default()
+        };
+        let location = locate_fault(&local, Some(&remote), &options).unwrap();
+        assert_eq!(location.sequence, "negative");
+        assert_eq!(location.remote_channels[0], "VRA");
+        assert!((location.unsynchronised_distance - 30.0).abs() < 1e-6);
+        assert!((location.clock_error - 1e-3).abs() < 1e-9);
+        assert!((location.distance - 30.0).abs() > 1.0);
+        let expected_error = location.expected_error.unwrap();
+        assert!((expected_error - location.sensitivity).abs() < 1e-9);
+        assert!(location.required_alignment.unwrap() < 1e-3);
+        assert!(!location.warnings.is_empty());
+
+        options.remote_offset = Some(-1e-3);
+        let location = locate_fault(&local, Some(&remote), &options).unwrap();
+        assert!((location.distance - 30.0).abs() < 1e-6);
+        assert!((location.distance_percent - 30.0).abs() < 1e-6);
+        assert!(location.clock_error.abs() < 1e-9);
+        assert!(location.reliable);
+
+        // A remote current pinned at its rail during the fault.
+        remote.clipped_intervals = vec![ClippedInterval {
+            channel_index: 3,
+            channel: "IRA".to_string(),
+            rail: "max".to_string(),
+            start_sample: 150,
+            end_sample: 152,
+            start_time: remote.timestamps[150],
+            end_time: remote.timestamps[152],
+        }];
+        let location = locate_fault(&local, Some(&remote), &options).unwrap();
+        assert!((location.distance - 30.0).abs() < 1e-6);
+        assert!(!location.reliable);
+
+        options.z1_magnitude = None;
+        assert!(locate_fault(&local, Some(&remote), &options).is_err());
+    }
+}
@@ -0,0 +1,534 @@
+// comtrade_rust/src/filter.rs
+// This file contains the Butterworth IIR and windowed FIR filters that produce filtered copies of analog channels.
+// This file exists so harmonics or DC offset can be stripped from a waveform for reading without exporting to MATLAB.
+// RELEVANT FILES: comtrade_rust/src/dsp.rs, comtrade_rust/src/calculated.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+use crate::dsp::{
+    RawScaling, fir_centred, lowpass_fir, nominal_frequency, rate_sections, uniform_rate,
+};
+use crate::{ComtradeInfo, SerializableAnalogChannel, WasmComtradeError};
+
+const DEFAULT_ORDER: usize = 4;
+/// The default FIR length, in cycles of the line frequency.
+const DEFAULT_FIR_CYCLES: f64 = 4.0;
+/// The default notch width in Hz.
+const DEFAULT_NOTCH_BANDWIDTH: f64 = 5.0;
+
+/// The frequency response of a filter.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    /// Passes frequencies below `cutoff`.
+    #[default]
+    LowPass,
+    /// Passes frequencies above `cutoff`.
+    HighPass,
+    /// Passes frequencies between `low_cutoff` and `high_cutoff`.
+    BandPass,
+    /// Removes a narrow band around `center`.
+    Notch,
+    /// Subtracts the mean of each one-cycle window, removing the DC offset
+    /// while leaving the fundamental and its harmonics untouched.
+    DcRemoval,
+}
+
+/// How the filter is realised.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterDesign {
+    /// A Butterworth IIR filter, maximally flat in the pass band, built from
+    /// second-order sections by the bilinear transform. A band-pass is a
+    /// high-pass at `low_cutoff` followed by a low-pass at `high_cutoff`; a
+    /// notch is a single second-order section.
+    #[default]
+    Butterworth,
+    /// A linear-phase FIR filter designed by the Blackman-windowed sinc
+    /// method.
+    Fir,
+}
+
+/// A filter applied to one analog channel to produce a new one.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FilterDefinition {
+    /// The position in `analog_channels` of the channel to filter.
+    pub channel: usize,
+    /// The name of the new channel. Defaults to the source name followed by
+    /// the filter, e.g. "IA LP 150 Hz".
+    pub name: Option<String>,
+    /// The response. Defaults to `low_pass`.
+    pub kind: FilterKind,
+    /// The realisation. Defaults to `butterworth`.
+    pub design: FilterDesign,
+    /// The corner frequency in Hz of a low-pass or high-pass filter.
+    pub cutoff: Option<f64>,
+    /// The lower edge of a band-pass filter in Hz.
+    pub low_cutoff: Option<f64>,
+    /// The upper edge of a band-pass filter in Hz.
+    pub high_cutoff: Option<f64>,
+    /// The centre of a notch in Hz. Defaults to the line frequency.
+    pub center: Option<f64>,
+    /// The width of a notch in Hz. Defaults to 5.
+    pub bandwidth: Option<f64>,
+    /// The Butterworth order, or the number of FIR taps. Defaults to 4, or
+    /// to four cycles of the line frequency for FIR; narrow FIR bands need
+    /// more taps.
+    pub order: Option<usize>,
+    /// Cancels the phase shift: the Butterworth filter is run forwards and
+    /// backwards, which also squares its response, and the FIR filter is
+    /// centred on each sample instead of delaying the output by half its
+    /// length.
+    pub zero_phase: bool,
+}
+
+/// A second-order IIR section, normalised so a0 = 1.
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    /// A Butterworth low-pass or high-pass section with quality `q`, where
+    /// `k` is the prewarped tan(π fc / fs).
+    fn butterworth(k: f64, q: f64, high_pass: bool) -> Self {
+        let norm = 1.0 / (1.0 + k / q + k * k);
+        let a = [2.0 * (k * k - 1.0) * norm, (1.0 - k / q + k * k) * norm];
+        let b = if high_pass {
+            [norm, -2.0 * norm, norm]
+        } else {
+            let b0 = k * k * norm;
+            [b0, 2.0 * b0, b0]
+        };
+        Biquad { b, a }
+    }
+
+    /// A first-order Butterworth section, for odd orders.
+    fn first_order(k: f64, high_pass: bool) -> Self {
+        let norm = 1.0 / (1.0 + k);
+        let b = if high_pass {
+            [norm, -norm, 0.0]
+        } else {
+            [k * norm, k * norm, 0.0]
+        };
+        Biquad {
+            b,
+            a: [(k - 1.0) * norm, 0.0],
+        }
+    }
+
+    /// A notch at `w0` radians per sample with quality `q`.
+    fn notch(w0: f64, q: f64) -> Self {
+        let alpha = w0.sin() / (2.0 * q);
+        let norm = 1.0 / (1.0 + alpha);
+        let c = -2.0 * w0.cos() * norm;
+        Biquad {
+            b: [norm, c, norm],
+            a: [c, (1.0 - alpha) * norm],
+        }
+    }
+
+    /// Filters `values` in place, starting from the steady state of the
+    /// first value so a constant input gives no start-up transient.
+    fn run(&self, values: &mut [f64]) {
+        let Some(&first) = values.first() else {
+            return;
+        };
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+        let gain = (b0 + b1 + b2) / (1.0 + a1 + a2);
+        let steady = if gain.is_finite() { gain * first } else { 0.0 };
+        let mut z2 = b2 * first - a2 * steady;
+        let mut z1 = b1 * first - a1 * steady + z2;
+        for x in values.iter_mut() {
+            let y = b0 * *x + z1;
+            z1 = b1 * *x - a1 * y + z2;
+            z2 = b2 * *x - a2 * y;
+            *x = y;
+        }
+    }
+}
+
+/// Returns the Butterworth low-pass or high-pass sections of `order`.
+fn butterworth(order: usize, cutoff: f64, rate: f64, high_pass: bool) -> Vec<Biquad> {
+    let k = (PI * cutoff / rate).tan();
+    let mut sections: Vec<Biquad> = (0..order / 2)
+        .map(|i| {
+            let q = 1.0 / (2.0 * (PI * (2 * i + 1) as f64 / (2 * order) as f64).sin());
+            Biquad::butterworth(k, q, high_pass)
+        })
+        .collect();
+    if order % 2 == 1 {
+        sections.push(Biquad::first_order(k, high_pass));
+    }
+    sections
+}
+
+/// Returns `coefficients` with the sign flipped and one added at the centre,
+/// turning a low-pass into the complementary high-pass.
+fn spectral_inversion(mut coefficients: Vec<f64>) -> Vec<f64> {
+    coefficients.iter_mut().for_each(|c| *c = -*c);
+    let middle = coefficients.len() / 2;
+    coefficients[middle] += 1.0;
+    coefficients
+}
+
+/// A filter designed for one sampling rate.
+enum Design {
+    Iir(Vec<Biquad>),
+    Fir(Vec<f64>),
+    DcRemoval(usize),
+}
+
+impl FilterDefinition {
+    /// Returns a short description of the filter, e.g. "LP 150 Hz".
+    fn label(&self, frequency: f64) -> String {
+        let hz = |v: Option<f64>| v.map_or_else(|| "?".to_string(), |v| format!("{}", v));
+        match self.kind {
+            FilterKind::LowPass => format!("LP {} Hz", hz(self.cutoff)),
+            FilterKind::HighPass => format!("HP {} Hz", hz(self.cutoff)),
+            FilterKind::BandPass => {
+                format!("BP {}-{} Hz", hz(self.low_cutoff), hz(self.high_cutoff))
+            }
+            FilterKind::Notch => format!("notch {} Hz", self.center.unwrap_or(frequency)),
+            FilterKind::DcRemoval => "DC removed".to_string(),
+        }
+    }
+
+    /// Designs the filter for a section sampled at `rate`.
+    fn design(&self, rate: f64, frequency: f64) -> Result<Design, WasmComtradeError> {
+        let nyquist = rate / 2.0;
+        let corner = |value: Option<f64>, what: &str| {
+            let value = value.ok_or_else(|| {
+                WasmComtradeError::AnalysisError(format!("The filter needs a {}.", what))
+            })?;
+            if value <= 0.0 || value >= nyquist {
+                return Err(WasmComtradeError::AnalysisError(format!(
+                    "The {} of {} Hz must lie between 0 and half the sampling rate ({} Hz).",
+                    what, value, nyquist
+                )));
+            }
+            Ok(value)
+        };
+        let order = self.order.filter(|o| *o > 0);
+        let taps = order
+            .unwrap_or((DEFAULT_FIR_CYCLES * rate / frequency).round() as usize)
+            .max(3)
+            | 1;
+        let iir_order = order.unwrap_or(DEFAULT_ORDER);
+
+        if self.kind == FilterKind::DcRemoval {
+            return Ok(Design::DcRemoval(
+                ((rate / frequency).round() as usize).max(1),
+            ));
+        }
+        if self.kind == FilterKind::Notch {
+            let center = corner(Some(self.center.unwrap_or(frequency)), "notch centre")?;
+            let bandwidth = self
+                .bandwidth
+                .filter(|b| *b > 0.0)
+                .unwrap_or(DEFAULT_NOTCH_BANDWIDTH);
+            return Ok(match self.design {
+                FilterDesign::Butterworth => Design::Iir(vec![Biquad::notch(
+                    2.0 * PI * center / rate,
+                    center / bandwidth,
+                )]),
+                FilterDesign::Fir => {
+                    let low = lowpass_fir(center - bandwidth / 2.0, rate, taps);
+                    let high = lowpass_fir(center + bandwidth / 2.0, rate, taps);
+                    let band: Vec<f64> = high.iter().zip(&low).map(|(h, l)| h - l).collect();
+                    Design::Fir(spectral_inversion(band))
+                }
+            });
+        }
+
+        let (low, high) = match self.kind {
+            FilterKind::LowPass => (None, Some(corner(self.cutoff, "cutoff")?)),
+            FilterKind::HighPass => (Some(corner(self.cutoff, "cutoff")?), None),
+            _ => {
+                let low = corner(self.low_cutoff, "low cutoff")?;
+                let high = corner(self.high_cutoff, "high cutoff")?;
+                if low >= high {
+                    return Err(WasmComtradeError::AnalysisError(format!(
+                        "The low cutoff ({} Hz) must be below the high cutoff ({} Hz).",
+                        low, high
+                    )));
+                }
+                (Some(low), Some(high))
+            }
+        };
+        Ok(match self.design {
+            FilterDesign::Butterworth => {
+                let mut sections = Vec::new();
+                if let Some(low) = low {
+                    sections.extend(butterworth(iir_order, low, rate, true));
+                }
+                if let Some(high) = high {
+                    sections.extend(butterworth(iir_order, high, rate, false));
+                }
+                Design::Iir(sections)
+            }
+            FilterDesign::Fir => {
+                let pass_below = |cutoff: Option<f64>| match cutoff {
+                    Some(cutoff) => lowpass_fir(cutoff, rate, taps),
+                    None => spectral_inversion(vec![0.0; taps]),
+                };
+                let below_high = pass_below(high);
+                let below_low = low.map(|l| lowpass_fir(l, rate, taps));
+                Design::Fir(match below_low {
+                    Some(below_low) => below_high
+                        .iter()
+                        .zip(&below_low)
+                        .map(|(h, l)| h - l)
+                        .collect(),
+                    None => below_high,
+                })
+            }
+        })
+    }
+}
+
+/// Subtracts the mean of a window of `n` samples, ending at each sample or
+/// centred on it. The first windows of the trailing mean are shorter.
+fn remove_dc(values: &[f64], n: usize, centred: bool) -> Vec<f64> {
+    let len = values.len();
+    let mut prefix = vec![0.0; len + 1];
+    for (i, v) in values.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + v;
+    }
+    (0..len)
+        .map(|i| {
+            let (start, end) = if centred {
+                let start = i.saturating_sub(n / 2);
+                (start, (start + n).min(len))
+            } else {
+                ((i + 1).saturating_sub(n), i + 1)
+            };
+            // A centred window near the end moves back to keep a whole cycle.
+            let start = end.saturating_sub(n).min(start);
+            values[i] - (prefix[end] - prefix[start]) / (end - start) as f64
+        })
+        .collect()
+}
+
+/// Applies a designed filter to one section of samples.
+fn apply(design: &Design, values: &[f64], zero_phase: bool) -> Vec<f64> {
+    match design {
+        Design::Iir(sections) => {
+            let mut output = values.to_vec();
+            for section in sections {
+                section.run(&mut output);
+            }
+            if zero_phase {
+                output.reverse();
+                for section in sections {
+                    section.run(&mut output);
+                }
+                output.reverse();
+            }
+            output
+        }
+        Design::Fir(coefficients) if zero_phase => fir_centred(values, coefficients),
+        Design::Fir(coefficients) => {
+            // Delaying the centred output by half the length makes it causal.
+            let delay = coefficients.len() / 2;
+            let centred = fir_centred(values, coefficients);
+            let first = values
+                .first()
+                .map_or(0.0, |v| v * coefficients.iter().sum::<f64>());
+            (0..values.len())
+                .map(|i| if i < delay { first } else { centred[i - delay] })
+                .collect()
+        }
+        Design::DcRemoval(n) => remove_dc(values, *n, zero_phase),
+    }
+}
+
+/// Adds a filtered copy of an analog channel to the recording.
+///
+/// The values, primary values and secondary values are each filtered. A
+/// recording with several sampling rates is filtered section by section,
+/// with the filter designed for each rate. The new channel is given a
+/// conversion that spreads it over 16-bit raw counts.
+pub fn add_filtered_channel(
+    info: &mut ComtradeInfo,
+    definition: &FilterDefinition,
+) -> Result<(), WasmComtradeError> {
+    let source = info.analog_channel(definition.channel)?;
+    let frequency = nominal_frequency(info.frequency);
+    let label = definition.label(frequency);
+    let name = definition
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("{} {}", source.name.trim(), label));
+    let name = name.trim();
+    let taken = info.analog_channels.iter().any(|c| c.name.trim() == name)
+        || info.digital_channels.iter().any(|c| c.name.trim() == name);
+    if taken {
+        return Err(WasmComtradeError::AnalysisError(format!(
+            "A channel named '{}' already exists.",
+            name
+        )));
+    }
+
+    let timestamps = &info.timestamps;
+    let samples = source.values.len().min(timestamps.len());
+    let mut designs = Vec::new();
+    for (start, end) in rate_sections(&timestamps[..samples]) {
+        let rate = uniform_rate(timestamps, start, (end + 1).min(timestamps.len()));
+        let design = match rate {
+            Some(rate) => Some(definition.design(rate, frequency)?),
+            None => None,
+        };
+        designs.push((start, end, design));
+    }
+    if designs.iter().all(|(_, _, d)| d.is_none()) {
+        return Err(WasmComtradeError::AnalysisError(
+            "The channel needs at least two samples to be filtered.".to_string(),
+        ));
+    }
+    let filter = |values: &[f64]| -> Vec<f64> {
+        let mut output = values[..samples].to_vec();
+        for (start, end, design) in &designs {
+            if let Some(design) = design {
+                let section = apply(design, &values[*start..*end], definition.zero_phase);
+                output[*start..*end].copy_from_slice(&section);
+            }
+        }
+        output
+    };
+
+    let values = filter(&source.values);
+    let raw = RawScaling::spanning(&values);
+    let index = info
+        .analog_channels
+        .iter()
+        .map(|c| c.index)
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let channel = SerializableAnalogChannel {
+        index,
+        name: name.to_string(),
+        min_value: raw.min,
+        max_value: raw.max,
+        multiplier: raw.multiplier,
+        offset_adder: raw.offset,
+        primary_values: filter(&source.primary_values),
+        secondary_values: filter(&source.secondary_values),
+        values,
+        expression: Some(format!("{} of {}", label, source.name.trim())),
+        ..source.clone()
+    };
+    info.analog_channels.push(channel);
+    Ok(())
+}
+
+/// Adds each filtered channel in order, so a filter may take the output of
+/// an earlier one.
+pub fn add_filtered_channels(
+    info: &mut ComtradeInfo,
+    definitions: &[FilterDefinition],
+) -> Result<(), WasmComtradeError> {
+    definitions
+        .iter()
+        .try_for_each(|definition| add_filtered_channel(info, definition))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipping::detect_clipping;
+    use crate::dsp::dft;
+    use crate::test_support::{analog_channel, recording};
+
+    #[test]
+    fn test_filtered_channels() {
+        // A 50 Hz wave with a 30% fifth harmonic and a DC offset, at 5 kHz.
+        let values = (0..1000)
+            .map(|k| {
+                let t = k as f64 / 5000.0;
+                (2.0 * PI * 50.0 * t).cos() + 0.3 * (2.0 * PI * 250.0 * t).cos() + 0.5
+            })
+            .collect();
+        let mut info = recording(
+            5000.0,
+            50.0,
+            vec![analog_channel(1, "IA", "A", "A", values)],
+            vec![],
+        );
+        let filter = |kind, design, zero_phase| FilterDefinition {
+            kind,
+            design,
+            zero_phase,
+            cutoff: Some(150.0),
+            low_cutoff: Some(125.0),
+            high_cutoff: Some(500.0),
+            center: Some(250.0),
+            bandwidth: Some(50.0),
+            ..Default::default()
+        };
+        add_filtered_channels(
+            &mut info,
+            &[
+                filter(FilterKind::LowPass, FilterDesign::Butterworth, true),
+                filter(FilterKind::HighPass, FilterDesign::Fir, true),
+                filter(FilterKind::BandPass, FilterDesign::Butterworth, true),
+                filter(FilterKind::Notch, FilterDesign::Butterworth, false),
+                filter(FilterKind::DcRemoval, FilterDesign::Butterworth, false),
+            ],
+        )
+        .unwrap();
+
+        let names: Vec<&str> = info
+            .analog_channels
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "IA",
+                "IA LP 150 Hz",
+                "IA HP 150 Hz",
+                "IA BP 125-500 Hz",
+                "IA notch 250 Hz",
+                "IA DC removed"
+            ]
+        );
+        assert_eq!(info.analog_channels[1].index, 2);
+        // One cycle in the middle of the recording.
+        let cycle = |channel: usize| &info.analog_channels[channel].primary_values[500..600];
+        let [mean, fundamental, fifth] = [0, 1, 5].map(|bin| {
+            (1..6)
+                .map(|c| dft(cycle(c), bin).norm())
+                .collect::<Vec<f64>>()
+        });
+        // Low-pass: keeps DC and 50 Hz, removes 250 Hz.
+        assert!((mean[0] - 0.5).abs() < 1e-3 && (fundamental[0] - 1.0).abs() < 0.01);
+        assert!(fifth[0] < 0.01);
+        // High-pass and band-pass: keep only 250 Hz.
+        for c in [1, 2] {
+            assert!(mean[c] < 1e-3 && fundamental[c] < 0.02);
+            assert!((fifth[c] - 0.3).abs() < 0.01);
+        }
+        // Notch: removes only 250 Hz.
+        assert!((fundamental[3] - 1.0).abs() < 0.01 && fifth[3] < 0.01);
+        // DC removal: keeps both waves.
+        assert!(mean[4] < 1e-9);
+        assert!((fundamental[4] - 1.0).abs() < 1e-9 && (fifth[4] - 0.3).abs() < 1e-9);
+        // The filtered extremes are well inside the declared raw range.
+        assert!(detect_clipping(&info.analog_channels[1..], &info.timestamps, 1).is_empty());
+
+        let above_nyquist = FilterDefinition {
+            cutoff: Some(3000.0),
+            ..Default::default()
+        };
+        assert!(add_filtered_channel(&mut info, &above_nyquist).is_err());
+        let duplicate = filter(FilterKind::LowPass, FilterDesign::Butterworth, true);
+        assert!(add_filtered_channel(&mut info, &duplicate).is_err());
+    }
+}
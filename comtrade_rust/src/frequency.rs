@@ -0,0 +1,356 @@
+// comtrade_rust/src/frequency.rs
+// This file contains the measurement of system frequency and ROCOF over time from voltage channels.
+// This file exists because the CFG only declares the nominal frequency, while load shedding and islanding reviews need the measured one.
+// RELEVANT FILES: comtrade_rust/src/phases.rs, comtrade_rust/src/series.rs, comtrade_rust/src/lib.rs
+
+use num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+use crate::dsp::{nominal_frequency, sample_rate_at, samples_per_cycle, uniform_rate};
+use crate::phases::{sequence_components, three_phase_set};
+use crate::series::DerivedSeries;
+use crate::{ComtradeInfo, WasmComtradeError};
+
+/// The ROCOF window used by default, in cycles of the nominal frequency.
+const DEFAULT_ROCOF_WINDOW_CYCLES: usize = 5;
+/// Points where the voltage is below this fraction of its largest value are
+/// dropped, since the angle of a collapsed voltage is meaningless.
+const MIN_VOLTAGE_FRACTION: f64 = 0.1;
+
+/// How the frequency is measured.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FrequencyMethod {
+    /// The period between successive positive-going zero crossings, one
+    /// point per cycle.
+    ZeroCrossing,
+    /// The rotation of the fundamental phasor between consecutive one-cycle
+    /// windows, four points per cycle. Uses the positive sequence voltage
+    /// when all three phase voltages are present.
+    #[default]
+    PhasorAngle,
+}
+
+/// Options for the frequency tracking. Every field is optional.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FrequencyOptions {
+    /// The measurement method. Defaults to `phasor_angle`.
+    pub method: FrequencyMethod,
+    /// The position in `analog_channels` of the voltage channel to measure.
+    /// Defaults to the positive sequence of the three phase voltages, or to
+    /// the first voltage channel.
+    pub channel_index: Option<usize>,
+    /// The window over which ROCOF is fitted, in nominal cycles. Defaults to 5.
+    pub rocof_window_cycles: Option<usize>,
+}
+
+/// Measured frequency and ROCOF over the recording.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FrequencyTrack {
+    /// The channel(s) the frequency was measured on.
+    pub source: String,
+    /// The measurement method used.
+    pub method: FrequencyMethod,
+    /// The measured frequency in Hz.
+    pub frequency: DerivedSeries,
+    /// The rate of change of frequency in Hz/s.
+    pub rocof: DerivedSeries,
+    /// The lowest measured frequency, if any point was measured.
+    pub min_frequency: Option<f64>,
+    /// The highest measured frequency, if any point was measured.
+    pub max_frequency: Option<f64>,
+    /// The largest ROCOF magnitude, if any point was measured.
+    pub max_abs_rocof: Option<f64>,
+}
+
+/// Computes the fundamental phasor of `values[start..start + n]` at `omega`
+/// rad/s, with the angle referenced to `t0` so windows can be compared.
+fn phasor(
+    values: &[f64],
+    timestamps: &[f64],
+    start: usize,
+    n: usize,
+    omega: f64,
+    t0: f64,
+) -> Complex64 {
+    let sum: Complex64 = (start..start + n)
+        .map(|k| Complex64::from_polar(values[k], -omega * (timestamps[k] - t0)))
+        .sum();
+    sum * (2.0 / n as f64)
+}
+
+/// Measures frequency from the phasor rotation between consecutive one-cycle
+/// windows. Returns (time, frequency, magnitude) points.
+fn phasor_angle_points(
+    sources: &[&[f64]],
+    timestamps: &[f64],
+    frequency: f64,
+) -> Vec<(f64, f64, f64)> {
+    let samples = sources
+        .iter()
+        .map(|values| values.len())
+        .min()
+        .unwrap_or(0)
+        .min(timestamps.len());
+    let omega = 2.0 * PI * frequency;
+    let t0 = timestamps.first().copied().unwrap_or_default();
+
+    let measure = |start: usize, n: usize| -> Complex64 {
+        let phasors: Vec<Complex64> = sources
+            .iter()
+            .map(|values| phasor(values, timestamps, start, n, omega, t0))
+            .collect();
+        match phasors[..] {
+            [a, b, c] => sequence_components([a, b, c])[1],
+            _ => phasors[0],
+        }
+    };
+
+    let mut points = Vec::new();
+    let mut k = 1;
+    while k < samples {
+        let Some(rate) = sample_rate_at(timestamps, k) else {
+            break;
+        };
+        let n = samples_per_cycle(rate, frequency);
+        let step = (n / 4).max(1);
+        if k < n {
+            k = n;
+            continue;
+        }
+        if k + n > samples {
+            break;
+        }
+        if uniform_rate(timestamps, k - n, k + n).is_some() {
+            let before = measure(k - n, n);
+            let after = measure(k, n);
+            let rotation = (after * before.conj()).arg();
+            let dt = timestamps[k] - timestamps[k - n];
+            points.push((
+                timestamps[k],
+                frequency + rotation / (2.0 * PI * dt),
+                after.norm().min(before.norm()),
+            ));
+        }
+        k += step;
+    }
+    points
+}
+
+/// Measures frequency from the period between positive-going zero crossings.
+/// Returns (time, frequency, magnitude) points.
+fn zero_crossing_points(
+    values: &[f64],
+    timestamps: &[f64],
+    frequency: f64,
+) -> Vec<(f64, f64, f64)> {
+    let samples = values.len().min(timestamps.len());
+    let peak = values.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+    // The signal must dip below -hysteresis before the next crossing counts,
+    // so noise around zero does not produce extra crossings.
+    let hysteresis = MIN_VOLTAGE_FRACTION * peak;
+
+    let mut crossings = Vec::new();
+    let mut armed = false;
+    for k in 1..samples {
+        if values[k - 1] < -hysteresis {
+            armed = true;
+        }
+        if armed && values[k - 1] < 0.0 && values[k] >= 0.0 {
+            let fraction = -values[k - 1] / (values[k] - values[k - 1]);
+            crossings.push(timestamps[k - 1] + fraction * (timestamps[k] - timestamps[k - 1]));
+            armed = false;
+        }
+    }
+
+    crossings
+        .windows(2)
+        .filter_map(|w| {
+            let period = w[1] - w[0];
+            // A missing crossing (e.g. during an interruption) spans several cycles.
+            let plausible = period > 0.5 / frequency && period < 2.0 / frequency;
+            plausible.then(|| ((w[0] + w[1]) / 2.0, 1.0 / period, peak))
+        })
+        .collect()
+}
+
+/// Fits the slope of `frequency` over a sliding window of `window` seconds.
+fn rocof(frequency: &DerivedSeries, window: f64) -> Vec<f64> {
+    let times = &frequency.times;
+    let values = &frequency.values;
+    (0..times.len())
+        .map(|i| {
+            let lo = times.partition_point(|&t| t < times[i] - window / 2.0);
+            let hi = times.partition_point(|&t| t <= times[i] + window / 2.0);
+            let count = (hi - lo) as f64;
+            if hi - lo < 3 {
+                return 0.0;
+            }
+            let mean_t = times[lo..hi].iter().sum::<f64>() / count;
+            let mean_f = values[lo..hi].iter().sum::<f64>() / count;
+            let (covariance, variance) = (lo..hi).fold((0.0, 0.0), |(c, v), j| {
+                let dt = times[j] - mean_t;
+                (c + dt * (values[j] - mean_f), v + dt * dt)
+            });
+            if variance > 0.0 {
+                covariance / variance
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Measures the system frequency and ROCOF over the recording from its
+/// voltage channels.
+pub fn track_frequency(
+    info: &ComtradeInfo,
+    options: &FrequencyOptions,
+) -> Result<FrequencyTrack, WasmComtradeError> {
+    let frequency = nominal_frequency(info.frequency);
+    let channels = &info.analog_channels;
+
+    let indices: Vec<usize> = match options.channel_index {
+        Some(index) => {
+            info.analog_channel(index)?;
+            vec![index]
+        }
+        None => {
+            let set = three_phase_set(channels, |c| c.is_voltage())
+                .filter(|_| options.method == FrequencyMethod::PhasorAngle);
+            match set {
+                Some(set) => set.to_vec(),
+                None => vec![
+                    channels
+                        .iter()
+                        .position(|c| c.is_voltage())
+                        .ok_or_else(|| {
+                            WasmComtradeError::AnalysisError(
+                                "The recording has no voltage channel to measure frequency on."
+                                    .to_string(),
+                            )
+                        })?,
+                ],
+            }
+        }
+    };
+    let names: Vec<&str> = indices.iter().map(|&i| channels[i].name.as_str()).collect();
+    let source = if indices.len() == 3 {
+        format!("Positive sequence ({})", names.join(", "))
+    } else {
+        names.join(", ")
+    };
+
+    let points = match options.method {
+        FrequencyMethod::PhasorAngle => {
+            let sources: Vec<&[f64]> = indices
+                .iter()
+                .map(|&i| channels[i].primary_values.as_slice())
+                .collect();
+            phasor_angle_points(&sources, &info.timestamps, frequency)
+        }
+        FrequencyMethod::ZeroCrossing => zero_crossing_points(
+            &channels[indices[0]].primary_values,
+            &info.timestamps,
+            frequency,
+        ),
+    };
+
+    let largest = points.iter().fold(0.0f64, |m, p| m.max(p.2));
+    let mut measured = DerivedSeries::new(format!("Frequency ({})", source), "Hz");
+    for &(time, value, magnitude) in &points {
+        if magnitude >= MIN_VOLTAGE_FRACTION * largest {
+            measured.push(time, value);
+        }
+    }
+
+    let window = options
+        .rocof_window_cycles
+        .unwrap_or(DEFAULT_ROCOF_WINDOW_CYCLES)
+        .max(1) as f64
+        / frequency;
+    let mut rate_of_change = DerivedSeries::new(format!("ROCOF ({})", source), "Hz/s");
+    rate_of_change.times = measured.times.clone();
+    rate_of_change.values = rocof(&measured, window);
+
+    let min_frequency = measured.values.iter().copied().reduce(f64::min);
+    let max_frequency = measured.values.iter().copied().reduce(f64::max);
+    let max_abs_rocof = rate_of_change
+        .values
+        .iter()
+        .map(|v| v.abs())
+        .reduce(f64::max);
+
+    Ok(FrequencyTrack {
+        source,
+        method: options.method,
+        frequency: measured,
+        rocof: rate_of_change,
+        min_frequency,
+        max_frequency,
+        max_abs_rocof,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording};
+
+    /// Three phase voltages at 2 kHz whose frequency ramps from 50 Hz at 1 Hz/s.
+    fn ramping_voltages() -> Vec<Vec<f64>> {
+        [0.0, -120.0, 120.0]
+            .iter()
+            .map(|shift: &f64| {
+                (0..4000)
+                    .map(|i| {
+                        let t = i as f64 / 2000.0;
+                        let angle = 2.0 * PI * (50.0 * t + 0.5 * t * t) + shift.to_radians();
+                        100.0 * angle.cos()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_track_frequency() {
+        let channels = ramping_voltages()
+            .into_iter()
+            .zip(["VA", "VB", "VC"])
+            .enumerate()
+            .map(|(i, (values, name))| analog_channel(i as u32 + 1, name, "kV", "", values))
+            .collect();
+        let info = recording(2000.0, 50.0, channels, vec![]);
+
+        for method in [FrequencyMethod::PhasorAngle, FrequencyMethod::ZeroCrossing] {
+            let options = FrequencyOptions {
+                method,
+                ..Default::default()
+            };
+            let track = track_frequency(&info, &options).unwrap();
+            assert!(!track.frequency.values.is_empty());
+            for (&t, &f) in track.frequency.times.iter().zip(&track.frequency.values) {
+                assert!(
+                    (f - (50.0 + t)).abs() < 0.02,
+                    "{:?} at {}: {}",
+                    method,
+                    t,
+                    f
+                );
+            }
+            let middle = track.rocof.values.len() / 2;
+            assert!((track.rocof.values[middle] - 1.0).abs() < 0.05);
+        }
+
+        let options = FrequencyOptions::default();
+        assert!(
+            track_frequency(&info, &options)
+                .unwrap()
+                .source
+                .starts_with("Positive sequence")
+        );
+    }
+}
@@ -0,0 +1,344 @@
+// comtrade_rust/src/harmonics.rs
+// This file contains the harmonic spectrum, THD and TDD analysis of analog channels.
+// This file exists so power quality complaints can be investigated in the frequency domain with the same recordings.
+// RELEVANT FILES: comtrade_rust/src/dsp.rs, comtrade_rust/src/clipping.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::clipping::is_clipped;
+use crate::dsp::{dft, nominal_frequency, rms, sample_at, sample_rate_at, uniform_rate};
+use crate::{ComtradeInfo, WasmComtradeError};
+
+/// The highest harmonic order evaluated by default, as in IEC 61000-4-7.
+const DEFAULT_MAX_ORDER: usize = 50;
+/// The default window length in seconds: 10 cycles at 50 Hz, 12 cycles at 60 Hz.
+const DEFAULT_WINDOW_SECONDS: f64 = 0.2;
+
+/// Options for the harmonic analysis. Every field is optional.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct HarmonicOptions {
+    /// The highest harmonic order to report. Defaults to 50 and is limited to
+    /// the orders below the Nyquist frequency.
+    pub max_order: Option<usize>,
+    /// The window length in cycles of the line frequency. Defaults to 200 ms
+    /// (10 cycles at 50 Hz, 12 cycles at 60 Hz).
+    pub window_cycles: Option<usize>,
+    /// The start of the (first) window as Unix seconds. Defaults to the first sample.
+    pub start_time: Option<f64>,
+    /// The end of the last window of a trend as Unix seconds. Defaults to the last sample.
+    pub end_time: Option<f64>,
+    /// How far consecutive windows of a trend advance, in cycles. Defaults to
+    /// `window_cycles`, i.e. non-overlapping windows.
+    pub step_cycles: Option<usize>,
+    /// The maximum demand load current in primary amperes, used as the
+    /// denominator of TDD (IEEE 519). TDD is only computed when this is set.
+    pub demand_current: Option<f64>,
+}
+
+/// One harmonic of a spectrum.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HarmonicComponent {
+    /// The harmonic order; 1 is the fundamental.
+    pub order: usize,
+    /// The nominal frequency of the harmonic in Hz.
+    pub frequency: f64,
+    /// The RMS magnitude in primary units.
+    pub magnitude: f64,
+    /// The angle in degrees, referenced to a cosine at the start of the window.
+    pub angle_deg: f64,
+    /// The magnitude as a percentage of the fundamental.
+    pub percent_of_fundamental: f64,
+}
+
+/// The harmonic content of one channel over one window.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HarmonicSpectrum {
+    /// The position of the channel in `analog_channels`.
+    pub channel_index: usize,
+    /// The channel name.
+    pub channel: String,
+    /// The channel units; magnitudes are primary values in these units.
+    pub units: String,
+    /// The first sample of the window (inclusive).
+    pub start_sample: usize,
+    /// The last sample of the window (inclusive).
+    pub end_sample: usize,
+    /// The time of `start_sample` as Unix seconds.
+    pub start_time: f64,
+    /// The time of `end_sample` as Unix seconds.
+    pub end_time: f64,
+    /// The DC component of the window.
+    pub dc: f64,
+    /// The true RMS of the window, including DC and all frequencies.
+    pub rms: f64,
+    /// Harmonics 1 to the highest order evaluated.
+    pub components: Vec<HarmonicComponent>,
+    /// Total harmonic distortion in percent of the fundamental.
+    pub thd_percent: f64,
+    /// Total demand distortion in percent of the demand current, for current
+    /// channels when a demand current was given.
+    pub tdd_percent: Option<f64>,
+    /// False when the window contains clipped samples.
+    pub reliable: bool,
+}
+
+fn analysis_error(message: String) -> WasmComtradeError {
+    WasmComtradeError::AnalysisError(message)
+}
+
+/// Resolved window parameters shared by the single-window and trend analyses.
+struct WindowSettings {
+    frequency: f64,
+    window_cycles: usize,
+    max_order: usize,
+}
+
+/// Returns the number of samples nearest to `cycles` cycles of `frequency`
+/// at `rate`, at least one.
+fn cycles_to_samples(cycles: usize, rate: f64, frequency: f64) -> usize {
+    ((cycles as f64 * rate / frequency).round() as usize).max(1)
+}
+
+impl WindowSettings {
+    fn new(info: &ComtradeInfo, options: &HarmonicOptions) -> Self {
+        let frequency = nominal_frequency(info.frequency);
+        Self {
+            frequency,
+            window_cycles: options
+                .window_cycles
+                .unwrap_or_else(|| (DEFAULT_WINDOW_SECONDS * frequency).round() as usize)
+                .max(1),
+            max_order: options.max_order.unwrap_or(DEFAULT_MAX_ORDER).max(1),
+        }
+    }
+
+    /// Returns the window length in samples at `rate`, rounded once for the
+    /// whole window so it spans `window_cycles` as closely as the rate allows.
+    fn length(&self, rate: f64) -> usize {
+        cycles_to_samples(self.window_cycles, rate, self.frequency)
+    }
+}
+
+/// Computes the spectrum of the window starting at sample `start`, or explains
+/// why it cannot.
+fn spectrum_at(
+    info: &ComtradeInfo,
+    channel_index: usize,
+    settings: &WindowSettings,
+    options: &HarmonicOptions,
+    start: usize,
+) -> Result<HarmonicSpectrum, WasmComtradeError> {
+    let channel = info.analog_channel(channel_index)?;
+    let values = &channel.primary_values;
+    let timestamps = &info.timestamps;
+
+    let rate = sample_rate_at(timestamps, start)
+        .ok_or_else(|| analysis_error(format!("No sampling rate is known at sample {}.", start)))?;
+    let n = settings.length(rate);
+    let end = start + n;
+    if end > values.len() || end > timestamps.len() {
+        return Err(analysis_error(format!(
+            "A {}-cycle window starting at sample {} extends beyond the end of the recording.",
+            settings.window_cycles, start
+        )));
+    }
+    let rate = uniform_rate(timestamps, start, end).ok_or_else(|| {
+        analysis_error(format!(
+            "The window from sample {} to {} spans a change of sampling rate.",
+            start,
+            end - 1
+        ))
+    })?;
+
+    // Harmonics at or above the Nyquist frequency cannot be resolved.
+    let nyquist_order = ((rate / 2.0 / settings.frequency).ceil() as usize).saturating_sub(1);
+    let max_order = settings.max_order.min(nyquist_order);
+    if max_order == 0 {
+        return Err(analysis_error(format!(
+            "A sampling rate of {:.1} Hz cannot resolve the {:.1} Hz fundamental.",
+            rate, settings.frequency
+        )));
+    }
+
+    let window = &values[start..end];
+    let cycles = settings.window_cycles;
+    let phasors: Vec<_> = (1..=max_order).map(|h| dft(window, h * cycles)).collect();
+    let fundamental = phasors[0].norm() / 2f64.sqrt();
+    let percent = |magnitude: f64| {
+        if fundamental > 0.0 {
+            100.0 * magnitude / fundamental
+        } else {
+            0.0
+        }
+    };
+
+    let components: Vec<HarmonicComponent> = phasors
+        .iter()
+        .enumerate()
+        .map(|(i, phasor)| {
+            let magnitude = phasor.norm() / 2f64.sqrt();
+            HarmonicComponent {
+                order: i + 1,
+                frequency: (i + 1) as f64 * settings.frequency,
+                magnitude,
+                angle_deg: phasor.arg().to_degrees(),
+                percent_of_fundamental: percent(magnitude),
+            }
+        })
+        .collect();
+
+    let distortion = components[1..]
+        .iter()
+        .map(|c| c.magnitude * c.magnitude)
+        .sum::<f64>()
+        .sqrt();
+    let tdd_percent = options
+        .demand_current
+        .filter(|&demand| demand > 0.0 && channel.is_current())
+        .map(|demand| 100.0 * distortion * channel.si_scale() / demand);
+
+    let time = |s: usize| timestamps.get(s).copied().unwrap_or_default();
+    Ok(HarmonicSpectrum {
+        channel_index,
+        channel: channel.name.clone(),
+        units: channel.units.clone(),
+        start_sample: start,
+        end_sample: end - 1,
+        start_time: time(start),
+        end_time: time(end - 1),
+        dc: dft(window, 0).re,
+        rms: rms(window),
+        components,
+        thd_percent: percent(distortion),
+        tdd_percent,
+        reliable: !is_clipped(&info.clipped_intervals, channel_index, start, end - 1),
+    })
+}
+
+/// Computes the harmonic spectrum, THD and TDD of one analog channel over a
+/// single window starting at `options.start_time`.
+///
+/// The window spans `window_cycles` nominal cycles, rounded to the nearest
+/// sample, and harmonic h is read at DFT bin h × `window_cycles`. The bins
+/// fall exactly on the harmonics when `window_cycles` × rate / frequency is a
+/// whole number, as for the default 200 ms window at 50 or 60 Hz and any
+/// sampling rate in whole hertz; otherwise some spectral leakage remains.
+/// Magnitudes are RMS primary values.
+pub fn harmonic_spectrum(
+    info: &ComtradeInfo,
+    channel_index: usize,
+    options: &HarmonicOptions,
+) -> Result<HarmonicSpectrum, WasmComtradeError> {
+    let settings = WindowSettings::new(info, options);
+    let start = options
+        .start_time
+        .map_or(0, |time| sample_at(&info.timestamps, time));
+    spectrum_at(info, channel_index, &settings, options, start)
+}
+
+/// Computes the harmonic spectrum of one analog channel over a sliding window,
+/// so harmonic content can be tracked across the recording.
+///
+/// Windows that straddle a change of sampling rate are skipped.
+pub fn harmonic_trend(
+    info: &ComtradeInfo,
+    channel_index: usize,
+    options: &HarmonicOptions,
+) -> Result<Vec<HarmonicSpectrum>, WasmComtradeError> {
+    let channel = info.analog_channel(channel_index)?;
+    let settings = WindowSettings::new(info, options);
+    let step_cycles = options.step_cycles.unwrap_or(settings.window_cycles).max(1);
+    let timestamps = &info.timestamps;
+    let samples = channel.primary_values.len().min(timestamps.len());
+
+    let mut start = options
+        .start_time
+        .map_or(0, |time| sample_at(timestamps, time));
+    let end_time = options.end_time.unwrap_or(f64::INFINITY);
+
+    let mut spectra = Vec::new();
+    while start < samples && timestamps[start] <= end_time {
+        let Some(rate) = sample_rate_at(timestamps, start) else {
+            break;
+        };
+        let end = start + settings.length(rate);
+        if end > samples || timestamps[end - 1] > end_time {
+            break;
+        }
+        if uniform_rate(timestamps, start, end).is_some() {
+            spectra.push(spectrum_at(info, channel_index, &settings, options, start)?);
+        }
+        start += cycles_to_samples(step_cycles, rate, settings.frequency);
+    }
+
+    Ok(spectra)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording, sine};
+
+    #[test]
+    fn test_harmonic_spectrum() {
+        let fundamental = sine(100.0, 50.0, 0.0, 2000.0, 800);
+        let fifth = sine(20.0, 250.0, 30.0, 2000.0, 800);
+        let values: Vec<f64> = fundamental.iter().zip(&fifth).map(|(a, b)| a + b).collect();
+        let info = recording(
+            2000.0,
+            50.0,
+            vec![analog_channel(1, "IA", "A", "A", values)],
+            vec![],
+        );
+
+        let options = HarmonicOptions {
+            demand_current: Some(100.0 / 2f64.sqrt()),
+            ..Default::default()
+        };
+        let spectrum = harmonic_spectrum(&info, 0, &options).unwrap();
+        assert_eq!(spectrum.end_sample, 399);
+        // 2 kHz resolves harmonics up to the 19th at 50 Hz.
+        assert_eq!(spectrum.components.len(), 19);
+        assert!((spectrum.components[0].magnitude - 100.0 / 2f64.sqrt()).abs() < 1e-9);
+        assert!((spectrum.components[4].percent_of_fundamental - 20.0).abs() < 1e-9);
+        assert!((spectrum.components[4].angle_deg + 60.0).abs() < 1e-6);
+        assert!((spectrum.thd_percent - 20.0).abs() < 1e-9);
+        assert!((spectrum.tdd_percent.unwrap() - 20.0).abs() < 1e-9);
+        assert!(spectrum.reliable);
+
+        let trend = harmonic_trend(&info, 0, &HarmonicOptions::default()).unwrap();
+        assert_eq!(trend.len(), 2);
+        assert_eq!(trend[1].start_sample, 400);
+        assert!(harmonic_spectrum(&info, 1, &options).is_err());
+
+        // 12 cycles of 60 Hz at 1 kHz are exactly 200 samples, although one
+        // cycle is not a whole number of samples.
+        let values = sine(100.0, 60.0, 0.0, 1000.0, 400);
+        let info = recording(
+            1000.0,
+            60.0,
+            vec![analog_channel(1, "IA", "A", "A", values)],
+            vec![],
+        );
+        let spectrum = harmonic_spectrum(&info, 0, &HarmonicOptions::default()).unwrap();
+        assert_eq!(spectrum.end_sample, 199);
+        assert!((spectrum.components[0].magnitude - 100.0 / 2f64.sqrt()).abs() < 1e-9);
+        assert!(spectrum.thd_percent < 1e-9);
+
+        // The demand current is in amperes, also for a kA channel.
+        let values: Vec<f64> = fundamental
+            .iter()
+            .zip(&fifth)
+            .map(|(a, b)| (a + b) / 1000.0)
+            .collect();
+        let info = recording(
+            2000.0,
+            50.0,
+            vec![analog_channel(1, "IA", "kA", "A", values)],
+            vec![],
+        );
+        let spectrum = harmonic_spectrum(&info, 0, &options).unwrap();
+        assert!((spectrum.tdd_percent.unwrap() - 20.0).abs() < 1e-9);
+    }
+}
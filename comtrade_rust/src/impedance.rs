@@ -0,0 +1,382 @@
+// comtrade_rust/src/impedance.rs
+// This file contains the apparent impedance trajectories of the six distance protection loops and their zone entry times.
+// This file exists so distance relay operation can be checked against mho and quadrilateral characteristics without a separate tool.
+// RELEVANT FILES: comtrade_rust/src/phases.rs, comtrade_rust/src/dsp.rs, comtrade_rust/src/lib.rs
+
+use num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+
+use crate::clipping::is_clipped;
+use crate::dsp::{cycle_windows, dft, nominal_frequency};
+use crate::phases::{is_phase_to_phase, three_phase_set};
+use crate::series::DerivedSeries;
+use crate::{ComtradeInfo, WasmComtradeError};
+
+/// The spacing of the impedance points by default, in cycles.
+const DEFAULT_STEP_CYCLES: f64 = 0.25;
+/// Points where the loop current is below this fraction of its largest value
+/// are dropped, since the impedance of a near-zero current is meaningless.
+const MIN_CURRENT_FRACTION: f64 = 0.05;
+const LOOP_NAMES: [&str; 6] = ["AG", "BG", "CG", "AB", "BC", "CA"];
+
+/// The shape of a distance zone on the R-X plane, in ohms.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum ZoneShape {
+    /// A circle through the origin (or through `-offset` when offset) whose
+    /// diameter lies along `angle_deg` and reaches `reach` ohms forward.
+    Mho {
+        reach: f64,
+        angle_deg: f64,
+        #[serde(default)]
+        offset: f64,
+    },
+    /// The parallelogram between R = ±`resistive_reach` along lines at
+    /// `angle_deg`, above X = 0 and below X = `reactive_reach`.
+    Quadrilateral {
+        reactive_reach: f64,
+        resistive_reach: f64,
+        angle_deg: f64,
+    },
+}
+
+impl ZoneShape {
+    /// Returns true if the impedance lies inside or on the characteristic.
+    fn contains(&self, z: Complex64) -> bool {
+        match *self {
+            ZoneShape::Mho {
+                reach,
+                angle_deg,
+                offset,
+            } => {
+                let direction = Complex64::from_polar(1.0, angle_deg.to_radians());
+                let centre = direction * (reach - offset) / 2.0;
+                (z - centre).norm() <= (reach + offset) / 2.0
+            }
+            ZoneShape::Quadrilateral {
+                reactive_reach,
+                resistive_reach,
+                angle_deg,
+            } => {
+                let along_line = z.im / angle_deg.to_radians().tan();
+                z.im >= 0.0
+                    && z.im <= reactive_reach
+                    && (z.re - along_line).abs() <= resistive_reach
+            }
+        }
+    }
+}
+
+/// A user-supplied distance zone.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ZoneCharacteristic {
+    /// The zone name, e.g. "Z1".
+    pub name: String,
+    #[serde(flatten)]
+    pub shape: ZoneShape,
+    /// The loops the zone applies to, e.g. ["AG", "BG", "CG"]. Empty applies
+    /// it to all six.
+    #[serde(default)]
+    pub loops: Vec<String>,
+}
+
+/// Options for the impedance calculation. Every field is optional.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ImpedanceOptions {
+    /// The magnitude of the zero-sequence compensation factor
+    /// k0 = (Z0 - Z1) / 3Z1. Defaults to 0.
+    pub k0_magnitude: Option<f64>,
+    /// The angle of k0 in degrees. Defaults to 0.
+    pub k0_angle_deg: Option<f64>,
+    /// Calculate in secondary ohms, as relay settings are usually given,
+    /// instead of primary ohms.
+    pub secondary: bool,
+    /// The positions in `analog_channels` of the A, B and C phase-to-neutral
+    /// voltages. Defaults to the channels found by phase.
+    pub voltage_channels: Option<[usize; 3]>,
+    /// The positions in `analog_channels` of the A, B and C currents.
+    /// Defaults to the channels found by phase.
+    pub current_channels: Option<[usize; 3]>,
+    /// The spacing of the points in cycles. Defaults to 0.25.
+    pub step_cycles: Option<f64>,
+    /// The zones whose entry times are reported.
+    pub zones: Vec<ZoneCharacteristic>,
+}
+
+/// The apparent impedance seen by one measuring loop over time.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LoopImpedance {
+    /// "AG", "BG", "CG", "AB", "BC" or "CA".
+    pub name: String,
+    /// The resistance in ohms, over one-cycle windows ending at each point.
+    pub resistance: DerivedSeries,
+    /// The reactance in ohms at the same points.
+    pub reactance: DerivedSeries,
+    /// False if a channel of the loop is clipped somewhere in the recording.
+    pub reliable: bool,
+}
+
+/// When the impedance of a loop entered a zone.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ZoneEntry {
+    /// The zone name.
+    pub zone: String,
+    /// The loop name.
+    pub loop_name: String,
+    /// The first point inside the zone, as Unix seconds.
+    pub entry_time: f64,
+    /// The first point outside the zone after `entry_time`, or `None` if the
+    /// impedance was still inside at the end.
+    pub exit_time: Option<f64>,
+}
+
+/// Loop impedance trajectories and zone entries.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ImpedanceAnalysis {
+    /// True for secondary ohms, false for primary ohms.
+    pub secondary: bool,
+    /// The voltage channels used, A, B and C.
+    pub voltage_channels: Vec<String>,
+    /// The current channels used, A, B and C.
+    pub current_channels: Vec<String>,
+    /// One trajectory per loop.
+    pub loops: Vec<LoopImpedance>,
+    /// The first entry of each loop into each zone that applies to it.
+    pub zone_entries: Vec<ZoneEntry>,
+}
+
+/// Returns the channel set from the options or found by phase, checking each position.
+fn channel_set(
+    info: &ComtradeInfo,
+    chosen: Option<[usize; 3]>,
+    found: Option<[usize; 3]>,
+    what: &str,
+) -> Result<[usize; 3], WasmComtradeError> {
+    match chosen {
+        Some(set) => {
+            for index in set {
+                info.analog_channel(index)?;
+            }
+            Ok(set)
+        }
+        None => found.ok_or_else(|| {
+            WasmComtradeError::AnalysisError(format!(
+                "The recording has no A, B and C {} channels to calculate impedance from.",
+                what
+            ))
+        }),
+    }
+}
+
+/// Computes the apparent impedance of the three phase-to-ground loops
+/// (V / (I + k0·3I0)) and the three phase-to-phase loops (ΔV / ΔI) from the
+/// fundamental phasors, and the time each loop first enters each zone.
+pub fn loop_impedances(
+    info: &ComtradeInfo,
+    options: &ImpedanceOptions,
+) -> Result<ImpedanceAnalysis, WasmComtradeError> {
+    let channels = &info.analog_channels;
+    let voltages = channel_set(
+        info,
+        options.voltage_channels,
+        three_phase_set(channels, |c| c.is_voltage() && !is_phase_to_phase(&c.phase)),
+        "phase-to-neutral voltage",
+    )?;
+    let currents = channel_set(
+        info,
+        options.current_channels,
+        three_phase_set(channels, |c| c.is_current()),
+        "current",
+    )?;
+
+    let values = |index: usize| -> Vec<f64> {
+        let channel = &channels[index];
+        let scale = channel.si_scale();
+        let values = if options.secondary {
+            &channel.secondary_values
+        } else {
+            &channel.primary_values
+        };
+        values.iter().map(|v| v * scale).collect()
+    };
+    let v = voltages.map(values);
+    let i = currents.map(values);
+    let samples = v
+        .iter()
+        .chain(&i)
+        .map(Vec::len)
+        .min()
+        .unwrap_or(0)
+        .min(info.timestamps.len());
+
+    let step_cycles = options
+        .step_cycles
+        .filter(|s| *s > 0.0)
+        .unwrap_or(DEFAULT_STEP_CYCLES);
+    let frequency = nominal_frequency(info.frequency);
+    let windows = cycle_windows(&info.timestamps, samples, frequency, step_cycles);
+    let k0 = Complex64::from_polar(
+        options.k0_magnitude.unwrap_or(0.0),
+        options.k0_angle_deg.unwrap_or(0.0).to_radians(),
+    );
+
+    // (time, loop voltage, loop current) for each window and loop.
+    let mut points: [Vec<(f64, Complex64, Complex64)>; 6] = Default::default();
+    for &(start, end) in &windows {
+        let time = info.timestamps[end - 1];
+        let vp = [0, 1, 2].map(|p| dft(&v[p][start..end], 1));
+        let ip = [0, 1, 2].map(|p| dft(&i[p][start..end], 1));
+        let residual = ip[0] + ip[1] + ip[2];
+        for p in 0..3 {
+            points[p].push((time, vp[p], ip[p] + k0 * residual));
+            let q = (p + 1) % 3;
+            points[p + 3].push((time, vp[p] - vp[q], ip[p] - ip[q]));
+        }
+    }
+
+    let mut loops = Vec::new();
+    let mut zone_entries = Vec::new();
+    for (l, name) in LOOP_NAMES.iter().enumerate() {
+        let largest = points[l].iter().fold(0.0f64, |m, p| m.max(p.2.norm()));
+        let mut resistance = DerivedSeries::new(format!("R {}", name), "Ω");
+        let mut reactance = DerivedSeries::new(format!("X {}", name), "Ω");
+        let mut trajectory = Vec::new();
+        for &(time, voltage, current) in &points[l] {
+            if current.norm() > 0.0 && current.norm() >= MIN_CURRENT_FRACTION * largest {
+                let z = voltage / current;
+                resistance.push(time, z.re);
+                reactance.push(time, z.im);
+                trajectory.push((time, z));
+            }
+        }
+
+        for zone in &options.zones {
+            let applies = zone.loops.is_empty()
+                || zone
+                    .loops
+                    .iter()
+                    .any(|l| l.trim().eq_ignore_ascii_case(name));
+            if !applies {
+                continue;
+            }
+            let Some(entry) = trajectory.iter().position(|&(_, z)| zone.shape.contains(z)) else {
+                continue;
+            };
+            let exit_time = trajectory[entry..]
+                .iter()
+                .find(|&&(_, z)| !zone.shape.contains(z))
+                .map(|&(time, _)| time);
+            zone_entries.push(ZoneEntry {
+                zone: zone.name.clone(),
+                loop_name: name.to_string(),
+                entry_time: trajectory[entry].0,
+                exit_time,
+            });
+        }
+
+        let (p, q) = if l < 3 { (l, l) } else { (l - 3, (l - 2) % 3) };
+        let reliable = samples > 0
+            && [voltages[p], voltages[q], currents[p], currents[q]]
+                .iter()
+                .all(|&index| !is_clipped(&info.clipped_intervals, index, 0, samples - 1));
+        loops.push(LoopImpedance {
+            name: name.to_string(),
+            resistance,
+            reactance,
+            reliable,
+        });
+    }
+    zone_entries.sort_by(|a, b| a.entry_time.total_cmp(&b.entry_time));
+
+    let names = |set: [usize; 3]| set.iter().map(|&i| channels[i].name.clone()).collect();
+    Ok(ImpedanceAnalysis {
+        secondary: options.secondary,
+        voltage_channels: names(voltages),
+        current_channels: names(currents),
+        loops,
+        zone_entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording, sine};
+
+    #[test]
+    fn test_loop_impedances() {
+        let rate = 1000.0;
+        let samples = 300;
+        let fault = 100;
+        let mut channels = Vec::new();
+        for (p, shift) in [("A", 0.0), ("B", -120.0), ("C", 120.0)] {
+            channels.push(analog_channel(
+                1,
+                &format!("V{}", p),
+                "kV",
+                p,
+                sine(60.0, 50.0, shift, rate, samples),
+            ));
+        }
+        // 60 kV over a 100 Ω ∠20° load, then a three-phase fault at 10 Ω ∠80°.
+        for (p, shift) in [("A", 0.0), ("B", -120.0), ("C", 120.0)] {
+            let load = sine(0.6, 50.0, shift - 20.0, rate, samples);
+            let faulted = sine(6.0, 50.0, shift - 80.0, rate, samples);
+            let i: Vec<f64> = (0..samples)
+                .map(|k| if k < fault { load[k] } else { faulted[k] })
+                .collect();
+            channels.push(analog_channel(1, &format!("I{}", p), "kA", p, i));
+        }
+        let info = recording(rate, 50.0, channels, vec![]);
+
+        let zone = |name: &str, shape: ZoneShape| ZoneCharacteristic {
+            name: name.to_string(),
+            shape,
+            loops: Vec::new(),
+        };
+        let options = ImpedanceOptions {
+            k0_magnitude: Some(0.8),
+            zones: vec![
+                zone(
+                    "Z1",
+                    ZoneShape::Mho {
+                        reach: 12.0,
+                        angle_deg: 80.0,
+                        offset: 0.0,
+                    },
+                ),
+                zone(
+                    "Z2",
+                    ZoneShape::Quadrilateral {
+                        reactive_reach: 5.0,
+                        resistive_reach: 10.0,
+                        angle_deg: 80.0,
+                    },
+                ),
+            ],
+            ..Default::default()
+        };
+        let analysis = loop_impedances(&info, &options).unwrap();
+        assert_eq!(analysis.loops.len(), 6);
+
+        let ag = &analysis.loops[0];
+        let z_load = Complex64::new(ag.resistance.values[0], ag.reactance.values[0]);
+        assert!((z_load.norm() - 100.0).abs() < 1e-6);
+        let last = ag.resistance.values.len() - 1;
+        let z_fault = Complex64::new(ag.resistance.values[last], ag.reactance.values[last]);
+        assert!((z_fault.norm() - 10.0).abs() < 1e-6);
+        assert!((z_fault.arg().to_degrees() - 80.0).abs() < 1e-6);
+
+        let ab = &analysis.loops[3];
+        assert!((ab.reactance.values[last] - z_fault.im).abs() < 1e-6);
+
+        // Every loop enters Z1 once the window holds only fault samples; none enter Z2.
+        assert_eq!(analysis.zone_entries.len(), 6);
+        for entry in &analysis.zone_entries {
+            assert_eq!(entry.zone, "Z1");
+            assert!(entry.entry_time > 0.1 && entry.entry_time <= 0.12);
+            assert_eq!(entry.exit_time, None);
+        }
+    }
+}
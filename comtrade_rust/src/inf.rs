@@ -0,0 +1,122 @@
+// comtrade_rust/src/inf.rs
+// This file contains a parser for COMTRADE information (.inf) files.
+// This file exists to expose public and vendor-specific private INF sections to the frontend as structured data.
+// RELEVANT FILES: comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+/// A single `key=value` entry from an INF section.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InfEntry {
+    /// The entry name, trimmed of surrounding whitespace.
+    pub key: String,
+    /// The entry value, trimmed of surrounding whitespace. Empty if the line had no `=`.
+    pub value: String,
+}
+
+/// A section of an INF file, such as `[Public Record_Information]`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InfSection {
+    /// "Public", "Private", or "Unknown" when the header has neither prefix.
+    pub visibility: String,
+    /// For private sections, the company name that precedes the section name.
+    pub vendor: Option<String>,
+    /// The section name (e.g., "Record_Information").
+    pub name: String,
+    /// The entries in file order.
+    pub entries: Vec<InfEntry>,
+}
+
+/// The parsed contents of an INF file.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct InfFile {
+    /// The sections in file order. Entries before the first section header are
+    /// collected in an "Unknown" section with an empty name.
+    pub sections: Vec<InfSection>,
+}
+
+fn parse_section_header(header: &str) -> InfSection {
+    let header = header.trim();
+    let (first, rest) = match header.split_once(char::is_whitespace) {
+        Some((first, rest)) => (first, rest.trim()),
+        None => (header, ""),
+    };
+
+    let (visibility, vendor, name) = if first.eq_ignore_ascii_case("public") {
+        ("Public", None, rest.to_string())
+    } else if first.eq_ignore_ascii_case("private") {
+        match rest.split_once(char::is_whitespace) {
+            Some((vendor, name)) => ("Private", Some(vendor.to_string()), name.trim().to_string()),
+            None => ("Private", None, rest.to_string()),
+        }
+    } else {
+        ("Unknown", None, header.to_string())
+    };
+
+    InfSection {
+        visibility: visibility.to_string(),
+        vendor,
+        name,
+        entries: Vec::new(),
+    }
+}
+
+/// Parses the text of an INF file into its sections and entries.
+///
+/// Blank lines and comment lines starting with `;` are skipped.
+pub fn parse_inf(text: &str) -> InfFile {
+    let mut sections: Vec<InfSection> = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push(parse_section_header(header));
+            continue;
+        }
+
+        if sections.is_empty() {
+            sections.push(InfSection {
+                visibility: "Unknown".to_string(),
+                vendor: None,
+                name: String::new(),
+                entries: Vec::new(),
+            });
+        }
+
+        let (key, value) = line.split_once('=').unwrap_or((line, ""));
+        if let Some(section) = sections.last_mut() {
+            section.entries.push(InfEntry {
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+            });
+        }
+    }
+
+    InfFile { sections }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_inf_sections() {
+        let text = "; comment\r\n[Public Record_Information]\r\nSource=Relay 1\r\nRecord_Information= Fault \r\n\r\n[Private ACME Relay_Settings]\r\nZ1 Reach=80%\r\nFLAG\r\n";
+        let inf = parse_inf(text);
+
+        assert_eq!(inf.sections.len(), 2);
+        assert_eq!(inf.sections[0].visibility, "Public");
+        assert_eq!(inf.sections[0].name, "Record_Information");
+        assert_eq!(inf.sections[0].entries[1].value, "Fault");
+
+        assert_eq!(inf.sections[1].visibility, "Private");
+        assert_eq!(inf.sections[1].vendor.as_deref(), Some("ACME"));
+        assert_eq!(inf.sections[1].name, "Relay_Settings");
+        assert_eq!(inf.sections[1].entries[0].key, "Z1 Reach");
+        assert_eq!(inf.sections[1].entries[1].value, "");
+    }
+}
@@ -0,0 +1,349 @@
+// comtrade_rust/src/inrush.rs
+// This file contains the classification of current events as transformer inrush, overexcitation or fault.
+// This file exists because energisation events look like faults to the sag and trip heuristics and to differential relays.
+// RELEVANT FILES: comtrade_rust/src/dsp.rs, comtrade_rust/src/harmonics.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::SerializableAnalogChannel;
+use crate::clipping::{ClippedInterval, is_clipped};
+use crate::dsp::{cycle_windows, dft, nominal_frequency};
+
+/// A cycle belongs to the event when its fundamental exceeds the pre-event
+/// fundamental by this factor.
+const EVENT_CURRENT_FACTOR: f64 = 2.0;
+/// Cycles whose fundamental is below this fraction of the channel's largest
+/// fundamental are treated as no current, e.g. before energisation.
+const CURRENT_FLOOR: f64 = 0.05;
+/// The second harmonic ratio above which a cycle looks like inrush; typical
+/// differential relays block at 15 to 20 %.
+const INRUSH_SECOND_HARMONIC: f64 = 0.15;
+/// The fifth harmonic ratio above which a cycle looks like overexcitation;
+/// typical differential relays block at 30 to 35 %.
+const OVEREXCITATION_FIFTH_HARMONIC: f64 = 0.3;
+/// Below this many samples per cycle the fifth harmonic cannot be resolved.
+const MIN_SAMPLES_PER_CYCLE: usize = 12;
+
+/// What caused the current event in a recording.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventClassification {
+    /// Transformer energisation: high second harmonic.
+    Inrush,
+    /// Transformer overexcitation: high fifth harmonic with little second harmonic.
+    Overexcitation,
+    /// Neither harmonic signature: a fault (or load) current.
+    Fault,
+}
+
+/// The harmonic ratios of one cycle of a current channel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HarmonicRatioPoint {
+    /// The time of the last sample of the cycle as Unix seconds.
+    pub time: f64,
+    /// The RMS fundamental of the cycle in primary units.
+    pub fundamental: f64,
+    /// The second harmonic as a fraction of the fundamental.
+    pub second_harmonic_ratio: f64,
+    /// The fifth harmonic as a fraction of the fundamental.
+    pub fifth_harmonic_ratio: f64,
+}
+
+/// The inrush classification of one current channel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InrushChannel {
+    /// The position of the channel in `analog_channels`.
+    pub channel_index: usize,
+    /// The channel name.
+    pub channel: String,
+    /// The harmonic ratios of every cycle carrying current, in time order.
+    pub ratios: Vec<HarmonicRatioPoint>,
+    /// The median second harmonic ratio over the event cycles.
+    pub second_harmonic_ratio: f64,
+    /// The median fifth harmonic ratio over the event cycles.
+    pub fifth_harmonic_ratio: f64,
+    /// The classification of the event on this channel.
+    pub classification: EventClassification,
+    /// False when any event cycle contains clipped samples.
+    pub reliable: bool,
+}
+
+/// The classification of the current event in a recording.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InrushAnalysis {
+    /// Inrush when any phase shows it, since inrush on one phase is enough to
+    /// block a differential relay; otherwise overexcitation, otherwise fault.
+    pub classification: EventClassification,
+    /// The time of the first event cycle on any channel as Unix seconds.
+    pub start_time: f64,
+    /// The current channels that carry the event.
+    pub channels: Vec<InrushChannel>,
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Classifies the event on one channel, returning `None` if the channel
+/// carries no current event.
+fn classify_channel(
+    channel_index: usize,
+    channel: &SerializableAnalogChannel,
+    timestamps: &[f64],
+    frequency: f64,
+    clipped_intervals: &[ClippedInterval],
+) -> Option<(InrushChannel, f64)> {
+    let values = &channel.primary_values;
+    let samples = values.len().min(timestamps.len());
+    // Windows spanning a change of sampling rate are skipped, and so are
+    // cycles with too few samples to resolve the fifth harmonic.
+    let windows = cycle_windows(timestamps, samples, frequency, 1.0);
+    let &(first_start, first_end) = windows.first()?;
+    if first_end - first_start < MIN_SAMPLES_PER_CYCLE {
+        return None;
+    }
+    let cycles: Vec<(usize, usize, [f64; 3])> = windows
+        .into_iter()
+        .filter(|(start, end)| end - start >= MIN_SAMPLES_PER_CYCLE)
+        .map(|(start, end)| {
+            let window = &values[start..end];
+            let h = [1, 2, 5].map(|order| dft(window, order).norm() / 2f64.sqrt());
+            (start, end, h)
+        })
+        .collect();
+    if cycles.len() < 3 {
+        return None;
+    }
+
+    let largest = cycles.iter().fold(0.0f64, |m, (_, _, h)| m.max(h[0]));
+    let floor = CURRENT_FLOOR * largest;
+    let threshold = (EVENT_CURRENT_FACTOR * cycles[0].2[0]).max(floor);
+    if largest <= threshold {
+        return None;
+    }
+
+    let time = |s: usize| timestamps.get(s).copied().unwrap_or_default();
+    let ratios: Vec<HarmonicRatioPoint> = cycles
+        .iter()
+        .filter(|(_, _, h)| h[0] > floor)
+        .map(|&(_, end, h)| HarmonicRatioPoint {
+            time: time(end - 1),
+            fundamental: h[0],
+            second_harmonic_ratio: h[1] / h[0],
+            fifth_harmonic_ratio: h[2] / h[0],
+        })
+        .collect();
+
+    // The cycle containing the onset mixes pre-event and event samples, so
+    // only cycles directly following another event cycle are evaluated.
+    let mut event_start = None;
+    let mut evaluated = Vec::new();
+    let mut previous_event = None;
+    for &(start, end, h) in &cycles {
+        let event = h[0] > threshold;
+        if event && event_start.is_none() {
+            event_start = Some(start);
+        }
+        if event && previous_event == Some(start) {
+            evaluated.push((start, end, h));
+        }
+        previous_event = event.then_some(end);
+    }
+    let event_start = event_start?;
+    if evaluated.is_empty() {
+        return None;
+    }
+
+    let second_harmonic_ratio = median(evaluated.iter().map(|(_, _, h)| h[1] / h[0]).collect());
+    let fifth_harmonic_ratio = median(evaluated.iter().map(|(_, _, h)| h[2] / h[0]).collect());
+    let classification = if second_harmonic_ratio >= INRUSH_SECOND_HARMONIC {
+        EventClassification::Inrush
+    } else if fifth_harmonic_ratio >= OVEREXCITATION_FIFTH_HARMONIC {
+        EventClassification::Overexcitation
+    } else {
+        EventClassification::Fault
+    };
+    let reliable = !evaluated
+        .iter()
+        .any(|&(start, end, _)| is_clipped(clipped_intervals, channel_index, start, end - 1));
+
+    let result = InrushChannel {
+        channel_index,
+        channel: channel.name.clone(),
+        ratios,
+        second_harmonic_ratio,
+        fifth_harmonic_ratio,
+        classification,
+        reliable,
+    };
+    Some((result, time(event_start)))
+}
+
+/// Computes the second and fifth harmonic ratios per cycle on every current
+/// channel ("A" or "kA") and classifies the current event as inrush,
+/// overexcitation or fault.
+///
+/// Returns `None` when no current channel carries an event, i.e. no cycle
+/// exceeds twice the first cycle's current.
+pub fn detect_inrush(
+    channels: &[SerializableAnalogChannel],
+    timestamps: &[f64],
+    line_frequency: f64,
+    clipped_intervals: &[ClippedInterval],
+) -> Option<InrushAnalysis> {
+    let frequency = nominal_frequency(line_frequency);
+    let results: Vec<(InrushChannel, f64)> = channels
+        .iter()
+        .enumerate()
+        .filter(|(_, channel)| channel.is_current())
+        .filter_map(|(i, channel)| {
+            classify_channel(i, channel, timestamps, frequency, clipped_intervals)
+        })
+        .collect();
+
+    let start_time = results.iter().map(|(_, t)| *t).reduce(f64::min)?;
+    let channels: Vec<InrushChannel> = results.into_iter().map(|(c, _)| c).collect();
+    let any = |class| channels.iter().any(|c| c.classification == class);
+    let classification = if any(EventClassification::Inrush) {
+        EventClassification::Inrush
+    } else if any(EventClassification::Overexcitation) {
+        EventClassification::Overexcitation
+    } else {
+        EventClassification::Fault
+    };
+
+    Some(InrushAnalysis {
+        classification,
+        start_time,
+        channels,
+    })
+}
+
+/// Describes the classification for `analysis_notes`. `recording_start` is
+/// the time of the first sample, so the note gives seconds into the recording.
+pub fn inrush_note(analysis: &InrushAnalysis, recording_start: f64) -> String {
+    let matching: Vec<&InrushChannel> = analysis
+        .channels
+        .iter()
+        .filter(|c| c.classification == analysis.classification)
+        .collect();
+    let names: Vec<&str> = matching.iter().map(|c| c.channel.as_str()).collect();
+    let at = analysis.start_time - recording_start;
+
+    match analysis.classification {
+        EventClassification::Inrush => format!(
+            "Transformer inrush detected at {:.4} seconds on {}: second harmonic up to {:.1}% of fundamental. This is energisation, not a fault; differential relays should have blocked.",
+            at,
+            names.join(", "),
+            100.0
+                * matching
+                    .iter()
+                    .fold(0.0f64, |m, c| m.max(c.second_harmonic_ratio))
+        ),
+        EventClassification::Overexcitation => format!(
+            "Transformer overexcitation detected at {:.4} seconds on {}: fifth harmonic up to {:.1}% of fundamental with little second harmonic.",
+            at,
+            names.join(", "),
+            100.0
+                * matching
+                    .iter()
+                    .fold(0.0f64, |m, c| m.max(c.fifth_harmonic_ratio))
+        ),
+        EventClassification::Fault => format!(
+            "Current event at {:.4} seconds on {} has no inrush or overexcitation harmonic signature; it is consistent with a fault.",
+            at,
+            names.join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording, sine};
+
+    /// Two cycles of 10 A load followed by eight cycles of 200 A with the given
+    /// second and fifth harmonic content, at 2 kHz.
+    fn event_current(second: f64, fifth: f64) -> Vec<f64> {
+        let fundamental = sine(200.0, 50.0, 0.0, 2000.0, 400);
+        let h2 = sine(200.0 * second, 100.0, 0.0, 2000.0, 400);
+        let h5 = sine(200.0 * fifth, 250.0, 0.0, 2000.0, 400);
+        (0..400)
+            .map(|i| {
+                if i < 80 {
+                    fundamental[i] / 20.0
+                } else {
+                    fundamental[i] + h2[i] + h5[i]
+                }
+            })
+            .collect()
+    }
+
+    fn classify(second: f64, fifth: f64) -> InrushAnalysis {
+        let channel = analog_channel(1, "IA", "A", "A", event_current(second, fifth));
+        let info = recording(2000.0, 50.0, vec![channel], vec![]);
+        detect_inrush(&info.analog_channels, &info.timestamps, info.frequency, &[]).unwrap()
+    }
+
+    #[test]
+    fn test_detect_inrush() {
+        let inrush = classify(0.4, 0.05);
+        assert_eq!(inrush.classification, EventClassification::Inrush);
+        assert!((inrush.channels[0].second_harmonic_ratio - 0.4).abs() < 1e-9);
+        assert!((inrush.start_time - 0.04).abs() < 1e-9);
+        assert!(inrush_note(&inrush, 0.0).contains("inrush"));
+
+        assert_eq!(
+            classify(0.05, 0.4).classification,
+            EventClassification::Overexcitation
+        );
+        assert_eq!(
+            classify(0.0, 0.0).classification,
+            EventClassification::Fault
+        );
+
+        let steady = analog_channel(1, "IA", "A", "A", sine(10.0, 50.0, 0.0, 2000.0, 400));
+        let info = recording(2000.0, 50.0, vec![steady], vec![]);
+        assert!(detect_inrush(&info.analog_channels, &info.timestamps, 50.0, &[]).is_none());
+
+        // The recorder switches from 2 kHz to 5 kHz at energisation.
+        let timestamps: Vec<f64> = (0..80)
+            .map(|k| k as f64 / 2000.0)
+            .chain((0..800).map(|k| 0.04 + k as f64 / 5000.0))
+            .collect();
+        let values = timestamps
+            .iter()
+            .map(|&t| {
+                let angle = 2.0 * std::f64::consts::PI * 50.0 * t;
+                if t < 0.04 {
+                    10.0 * angle.sin()
+                } else {
+                    200.0 * angle.sin() + 80.0 * (2.0 * angle).sin() + 10.0 * (5.0 * angle).sin()
+                }
+            })
+            .collect();
+        let mut info = recording(
+            2000.0,
+            50.0,
+            vec![analog_channel(1, "IA", "A", "A", values)],
+            vec![],
+        );
+        info.timestamps = timestamps;
+        let inrush = detect_inrush(&info.analog_channels, &info.timestamps, 50.0, &[]).unwrap();
+        assert_eq!(inrush.classification, EventClassification::Inrush);
+        assert!((inrush.channels[0].second_harmonic_ratio - 0.4).abs() < 1e-9);
+        assert!((inrush.channels[0].fifth_harmonic_ratio - 0.05).abs() < 1e-9);
+        assert!((inrush.start_time - 0.04).abs() < 1e-9);
+        // The first cycle at 5 kHz ends 99 samples after energisation.
+        assert!((inrush.channels[0].ratios[0].time - 0.0598).abs() < 1e-9);
+    }
+}
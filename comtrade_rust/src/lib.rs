@@ -3,13 +3,106 @@
 // This file exists to parse COMTRADE files and return the information to the Svelte frontend.
 // RELEVANT FILES: app/src/routes/info/+page.svelte
 
+mod archive;
+mod breaker;
+mod calculated;
+mod chatter;
+mod clipping;
+mod config;
+mod ct_saturation;
+mod dc_offset;
+mod diagnostics;
+mod differential;
+mod dsp;
+mod fault_location;
+mod filter;
+mod frequency;
+mod harmonics;
+mod impedance;
+mod inf;
+mod inrush;
+mod merge;
+mod phases;
+mod power;
+mod ratios;
+mod resample;
+mod sequence_of_events;
+mod series;
+#[cfg(test)]
+mod test_support;
+mod text_encoding;
+mod validation;
+mod voltage_events;
+mod wiring;
+mod writer;
+
 use comtrade::{ComtradeParserBuilder, DataFormat, StatusChannel};
 use encoding_rs;
 use regex::bytes::Regex as BytesRegex;
-use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::panic;
 use wasm_bindgen::prelude::*;
 
+pub use archive::{ZipRecording, list_recordings, read_recording};
+pub use breaker::{
+    BreakerAnalysis, BreakerOperation, BreakerOptions, PoleOperation, RecloseAttempt,
+    analyze_breaker, breaker_notes,
+};
+pub use calculated::{CalculatedChannel, add_calculated_channel, add_calculated_channels};
+pub use chatter::{
+    ChannelChatter, ChatterBurst, ChatterOptions, chatter_diagnostics, debounce, detect_chatter,
+};
+pub use clipping::{
+    ClippedInterval, MIN_CLIPPED_RUN, clipping_diagnostics, detect_clipping, is_clipped,
+};
+pub use config::{ComtradeConfig, SerializableSamplingRate};
+pub use ct_saturation::{
+    CtSaturation, SaturationInterval, ct_saturation_diagnostics, detect_ct_saturation,
+};
+pub use dc_offset::{DcOffset, DcOffsetOptions, fit_dc_offsets};
+pub use diagnostics::{Diagnostic, Severity};
+pub use differential::{
+    DifferentialAnalysis, DifferentialOptions, DifferentialSide, PhaseDifferential,
+    RestraintMethod, differential_currents,
+};
+pub use fault_location::{FaultLocation, FaultLocationOptions, locate_fault};
+pub use filter::{
+    FilterDefinition, FilterDesign, FilterKind, add_filtered_channel, add_filtered_channels,
+};
+pub use frequency::{FrequencyMethod, FrequencyOptions, FrequencyTrack, track_frequency};
+pub use harmonics::{
+    HarmonicComponent, HarmonicOptions, HarmonicSpectrum, harmonic_spectrum, harmonic_trend,
+};
+pub use impedance::{
+    ImpedanceAnalysis, ImpedanceOptions, LoopImpedance, ZoneCharacteristic, ZoneEntry, ZoneShape,
+    loop_impedances,
+};
+pub use inf::{InfEntry, InfFile, InfSection, parse_inf};
+pub use inrush::{
+    EventClassification, HarmonicRatioPoint, InrushAnalysis, InrushChannel, detect_inrush,
+    inrush_note,
+};
+pub use merge::{
+    Alignment, AlignmentMethod, MergeOptions, MergedRecording, align_recordings, merge_recordings,
+};
+pub use phases::{channel_phase, phase_number, sequence_components, three_phase_set};
+pub use power::{CircuitPower, PowerFlow, PowerOptions, calculate_power};
+pub use ratios::{RatioCheck, RatioOverride, check_ratios, override_ratios, scaling_failure};
+pub use resample::{Interpolation, ResampleOptions, resample_recording};
+pub use sequence_of_events::{
+    SoeEntry, SoeEventKind, SoeOptions, sequence_of_events, soe_to_csv, soe_to_json,
+};
+pub use series::DerivedSeries;
+pub use text_encoding::{DetectedEncoding, decode_text, detect_encoding};
+pub use validation::{validate_cfg, validate_dat, validate_recording, validate_trigger};
+pub use voltage_events::{
+    VoltageEvent, VoltageEventAnalysis, VoltageEventKind, VoltageEventOptions,
+    detect_voltage_events, voltage_event_name, voltage_event_note,
+};
+pub use wiring::{WiringOptions, check_wiring};
+pub use writer::{ComtradeFiles, write_comtrade};
+
 pub const GIT_HASH: &str = env!("GIT_HASH");
 
 #[derive(Debug, thiserror::Error)]
@@ -18,6 +111,12 @@ pub enum WasmComtradeError {
     ParseError(String),
     #[error("Invalid file combination: {0}")]
     InvalidFileCombination(String),
+    #[error("Archive error: {0}")]
+    ArchiveError(String),
+    #[error("Analysis error: {0}")]
+    AnalysisError(String),
+    #[error("Expression error: {0}")]
+    ExpressionError(String),
     #[error("Serialization error: {0}")]
     SerializationError(String),
     #[error("Internal panic: {0}")]
@@ -42,7 +141,7 @@ fn data_format_to_str(format: &DataFormat) -> &'static str {
 }
 
 /// Represents a single analog channel from a COMTRADE file, formatted for serialization.
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct SerializableAnalogChannel {
     /// The channel index number.
     pub index: u32,
@@ -78,10 +177,34 @@ pub struct SerializableAnalogChannel {
     pub skew: f64,
     /// The absolute skew-adjusted timestamps for this channel.
     pub skew_timestamps: Vec<f64>,
+    /// The expression a calculated channel was evaluated from, or `None` for
+    /// a recorded channel.
+    pub expression: Option<String>,
+}
+
+impl SerializableAnalogChannel {
+    /// Returns true if the channel measures current (units "A" or "kA").
+    pub fn is_current(&self) -> bool {
+        matches!(self.units.trim().to_lowercase().as_str(), "a" | "ka")
+    }
+
+    /// Returns true if the channel measures voltage (units "V" or "kV").
+    pub fn is_voltage(&self) -> bool {
+        matches!(self.units.trim().to_lowercase().as_str(), "v" | "kv")
+    }
+
+    /// Returns the factor converting the channel's values to volts or amperes:
+    /// 1000 for "kV" or "kA", otherwise 1.
+    pub fn si_scale(&self) -> f64 {
+        match self.units.trim().to_lowercase().as_str() {
+            "kv" | "ka" => 1000.0,
+            _ => 1.0,
+        }
+    }
 }
 
 /// Represents a single digital channel from a COMTRADE file, formatted for serialization.
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct SerializableDigitalChannel {
     /// The channel index number.
     pub index: u32,
@@ -89,6 +212,14 @@ pub struct SerializableDigitalChannel {
     pub name: String,
     /// The initial value of the channel.
     pub initial_value: u8,
+    /// The phase of the channel (e.g., "A"), typically set on per-pole
+    /// breaker contacts.
+    pub phase: String,
+    /// The state (0 or 1) at each sample.
+    pub values: Vec<u8>,
+    /// The expression a calculated channel was evaluated from, or `None` for
+    /// a recorded channel.
+    pub expression: Option<String>,
 }
 
 impl From<&StatusChannel> for SerializableDigitalChannel {
@@ -97,12 +228,19 @@ impl From<&StatusChannel> for SerializableDigitalChannel {
             index: channel.config.index.get() as u32,
             name: channel.config.name.clone(),
             initial_value: channel.config.normal_status_value,
+            phase: channel.config.phase.clone(),
+            values: channel.data.clone(),
+            expression: None,
         }
     }
 }
 
 /// Contains the parsed information from a COMTRADE file.
-#[derive(Serialize)]
+///
+/// The analysis entry points accept this structure back from JavaScript, so
+/// fields missing on input take their default values.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct ComtradeInfo {
     /// The name of the substation or station where the recording was made.
     pub station: String,
@@ -124,6 +262,21 @@ pub struct ComtradeInfo {
     /// The nominal line frequency in Hz (e.g., 50.0 or 60.0).
     pub frequency: f64,
 
+    /// The complete CFG header as declared in the configuration file,
+    /// including revision year, declared channel counts and time codes.
+    pub config: ComtradeConfig,
+
+    /// The verbatim text of the HDR file, if one was supplied.
+    pub header: Option<String>,
+
+    /// The INF file parsed into its public and private sections, if one was
+    /// supplied.
+    pub information: Option<InfFile>,
+
+    /// The text encoding used for each of the CFG, HDR and INF files, either
+    /// chosen by the user or detected automatically, with its confidence.
+    pub encodings: Vec<DetectedEncoding>,
+
     /// A list of the analog channels present in the file, with metadata and
     /// sample values for each channel.
     pub analog_channels: Vec<SerializableAnalogChannel>,
@@ -155,23 +308,103 @@ pub struct ComtradeInfo {
     /// noteworthy events in the recording.
     pub analysis_notes: Vec<String>,
 
+    /// Compliance findings from validating the CFG and DAT files against
+    /// IEEE C37.111. Each entry names the rule and where it was found so the
+    /// report can be returned to the vendor.
+    pub diagnostics: Vec<Diagnostic>,
+
+    /// Runs of samples pinned at the ADC limits (`min_value`/`max_value`) of
+    /// each analog channel. RMS, phasor and fault location values computed
+    /// over these intervals are unreliable.
+    pub clipped_intervals: Vec<ClippedInterval>,
+
+    /// Voltage sags, swells and interruptions across all voltage channels,
+    /// detected from the half-cycle-refreshed one-cycle RMS as in
+    /// IEC 61000-4-30, relative to each channel's first-cycle RMS.
+    pub voltage_events: Vec<VoltageEvent>,
+
+    /// Current channels showing signs of CT saturation during fault current,
+    /// with the saturation onset in each affected cycle.
+    pub ct_saturation: Vec<CtSaturation>,
+
+    /// The second and fifth harmonic ratios of the current channels over time,
+    /// with the current event classified as transformer inrush,
+    /// overexcitation or fault. `None` when no current event was found.
+    pub inrush: Option<InrushAnalysis>,
+
+    /// Breaker trip operations with per-pole opening, arcing and clearing
+    /// times and any auto-reclose that followed. `None` when no trip command
+    /// channel was found or it never asserts.
+    pub breaker: Option<BreakerAnalysis>,
+
+    /// Digital channels with bursts of rapid transitions from contact bounce
+    /// or chatter, found with the default `ChatterOptions`.
+    pub chatter: Vec<ChannelChatter>,
+
     /// Numeric trigger timestamp as Unix seconds (floating point). This is
     /// provided as a machine-friendly numeric value useful for programmatic
     /// timing calculations and alignment.
     pub trigger_timestamp: f64,
 }
 
+impl ComtradeInfo {
+    /// Returns the analog channel at position `index` in `analog_channels`.
+    pub fn analog_channel(
+        &self,
+        index: usize,
+    ) -> Result<&SerializableAnalogChannel, WasmComtradeError> {
+        self.analog_channels.get(index).ok_or_else(|| {
+            WasmComtradeError::AnalysisError(format!(
+                "Analog channel {} does not exist; the recording has {} analog channel(s).",
+                index,
+                self.analog_channels.len()
+            ))
+        })
+    }
+}
+
+/// The decoded text files and raw DAT bytes of a recording, collected while
+/// parsing so they can be validated afterwards.
+#[derive(Default)]
+struct DecodedText {
+    cfg: Option<String>,
+    dat: Option<Vec<u8>>,
+    header: Option<String>,
+    information: Option<String>,
+    encodings: Vec<DetectedEncoding>,
+    warnings: Vec<String>,
+}
+
+impl DecodedText {
+    /// Decodes `bytes` with the user's encoding or a detected one, recording
+    /// the encoding used and any decoding warnings.
+    fn decode(
+        &mut self,
+        bytes: &[u8],
+        encoding: Option<&'static encoding_rs::Encoding>,
+        file: &str,
+    ) -> String {
+        let (decoded, detected) = decode_text(bytes, encoding, file, &mut self.warnings);
+        self.encodings.push(detected);
+        decoded
+    }
+}
+
 /// Parses a COMTRADE file from its constituent parts.
 ///
-/// Accepts either a single CFF file, or a pair of CFG and DAT files.
+/// Accepts either a single CFF file, or a pair of CFG and DAT files with
+/// optional HDR and INF files.
 ///
 /// # Arguments
 ///
 /// * `cfg_file` - An optional byte array of the .cfg file content.
 /// * `dat_file` - An optional byte array of the .dat file content.
 /// * `cff_file` - An optional byte array of the .cff file content.
-/// * `encoding_label` - An optional string label for the text encoding of the CFG file (e.g., "utf-8", "latin1").
-///                      Defaults to UTF-8 if not provided. This is ignored for CFF files.
+/// * `encoding_label` - An optional string label for the text encoding of the CFG, HDR and INF files
+///                      (e.g., "utf-8", "latin1", "gbk"). Applies to CFF sections as well. When not provided,
+///                      or when the label is not recognised (e.g., "auto"), the encoding of each file is detected.
+/// * `hdr_file` - An optional byte array of the .hdr file content. Ignored when a CFF file is provided.
+/// * `inf_file` - An optional byte array of the .inf file content. Ignored when a CFF file is provided.
 ///
 /// # Returns
 ///
@@ -182,88 +415,749 @@ pub fn parse_comtrade(
     dat_file: Option<Box<[u8]>>,
     cff_file: Option<Box<[u8]>>,
     encoding_label: Option<String>,
+    hdr_file: Option<Box<[u8]>>,
+    inf_file: Option<Box<[u8]>>,
 ) -> Result<JsValue, WasmComtradeError> {
     let encoding = encoding_label
         .as_deref()
-        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
-        .unwrap_or(encoding_rs::UTF_8);
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()));
+
+    let files = RecordingFiles {
+        cfg: cfg_file.map(Vec::from),
+        dat: dat_file.map(Vec::from),
+        cff: cff_file.map(Vec::from),
+        hdr: hdr_file.map(Vec::from),
+        inf: inf_file.map(Vec::from),
+    };
+
+    let info = parse_recording(files, encoding)?;
+    serde_wasm_bindgen::to_value(&info)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Lists the COMTRADE recordings found in a ZIP archive.
+///
+/// # Arguments
+///
+/// * `zip_file` - A byte array of the .zip file content.
+///
+/// # Returns
+///
+/// A `JsValue` containing a list of `ZipRecording` entries, one per file stem, so the user can pick
+/// which recording to parse with `parse_comtrade_zip`.
+#[wasm_bindgen]
+pub fn list_zip_recordings(zip_file: Box<[u8]>) -> Result<JsValue, WasmComtradeError> {
+    let recordings = list_recordings(&zip_file)?;
+    serde_wasm_bindgen::to_value(&recordings)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
 
+/// Parses a COMTRADE recording contained in a ZIP archive.
+///
+/// # Arguments
+///
+/// * `zip_file` - A byte array of the .zip file content.
+/// * `recording` - The `name` of the recording to parse, as returned by `list_zip_recordings`.
+///                 May be omitted when the archive contains a single recording.
+/// * `encoding_label` - An optional string label for the text encoding, as for `parse_comtrade`.
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `ComtradeInfo` on success, or a `JsValue` with an error message on failure.
+#[wasm_bindgen]
+pub fn parse_comtrade_zip(
+    zip_file: Box<[u8]>,
+    recording: Option<String>,
+    encoding_label: Option<String>,
+) -> Result<JsValue, WasmComtradeError> {
+    let encoding = encoding_label
+        .as_deref()
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()));
+
+    let files = read_recording(&zip_file, recording.as_deref())?;
+    let info = parse_recording(files, encoding)?;
+    serde_wasm_bindgen::to_value(&info)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Deserializes a `ComtradeInfo` previously returned by `parse_comtrade`.
+fn recording_from_js(recording: JsValue) -> Result<ComtradeInfo, WasmComtradeError> {
+    serde_wasm_bindgen::from_value(recording)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Deserializes analysis options, using the defaults when `options` is undefined or null.
+fn options_from_js<T: DeserializeOwned + Default>(
+    options: JsValue,
+) -> Result<T, WasmComtradeError> {
+    if options.is_undefined() || options.is_null() {
+        return Ok(T::default());
+    }
+    serde_wasm_bindgen::from_value(options)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Computes the harmonic spectrum, THD and TDD of an analog channel over one window.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `channel_index` - The position of the channel in `analog_channels`.
+/// * `options` - An optional `HarmonicOptions` object (`max_order`, `window_cycles`, `start_time`,
+///               `demand_current`). Missing fields take their defaults.
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `HarmonicSpectrum`, or an error if the window does not fit
+/// in the recording.
+#[wasm_bindgen]
+pub fn analyze_harmonics(
+    recording: JsValue,
+    channel_index: usize,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: HarmonicOptions = options_from_js(options)?;
+    let spectrum = harmonic_spectrum(&info, channel_index, &options)?;
+    serde_wasm_bindgen::to_value(&spectrum)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Computes the harmonic spectrum of an analog channel over a sliding window.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `channel_index` - The position of the channel in `analog_channels`.
+/// * `options` - An optional `HarmonicOptions` object; `start_time`, `end_time` and `step_cycles`
+///               select the windows.
+///
+/// # Returns
+///
+/// A `JsValue` containing a list of `HarmonicSpectrum` entries, one per window.
+#[wasm_bindgen]
+pub fn analyze_harmonic_trend(
+    recording: JsValue,
+    channel_index: usize,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: HarmonicOptions = options_from_js(options)?;
+    let trend = harmonic_trend(&info, channel_index, &options)?;
+    serde_wasm_bindgen::to_value(&trend)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Measures the system frequency and ROCOF over time from the voltage channels.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `FrequencyOptions` object (`method`: "phasor_angle" or "zero_crossing",
+///               `channel_index`, `rocof_window_cycles`). Missing fields take their defaults.
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `FrequencyTrack` with the frequency and ROCOF series.
+#[wasm_bindgen]
+pub fn analyze_frequency(
+    recording: JsValue,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: FrequencyOptions = options_from_js(options)?;
+    let track = track_frequency(&info, &options)?;
+    serde_wasm_bindgen::to_value(&track)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Detects voltage sags, swells and interruptions following IEC 61000-4-30.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `VoltageEventOptions` object (`declared_voltage` in volts and the
+///               threshold percentages). Missing fields take their defaults.
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `VoltageEventAnalysis`, with the Urms(1/2) series of each
+/// voltage channel and the events.
+#[wasm_bindgen]
+pub fn analyze_voltage_events(
+    recording: JsValue,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: VoltageEventOptions = options_from_js(options)?;
+    let analysis = detect_voltage_events(
+        &info.analog_channels,
+        &info.timestamps,
+        info.frequency,
+        &info.clipped_intervals,
+        &options,
+    );
+    serde_wasm_bindgen::to_value(&analysis)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Calculates instantaneous and fundamental power per circuit, per phase and for three phases.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `PowerOptions` object (`circuit` to select one circuit component,
+///               `step_cycles` between fundamental points).
+///
+/// # Returns
+///
+/// A `JsValue` containing a `CircuitPower` for each circuit, with p, P, Q, S and power factor
+/// series in MW, Mvar and MVA from primary values.
+#[wasm_bindgen]
+pub fn analyze_power(recording: JsValue, options: JsValue) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: PowerOptions = options_from_js(options)?;
+    let circuits = calculate_power(&info, &options)?;
+    serde_wasm_bindgen::to_value(&circuits)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Checks the phase rotation, 120° spacing and polarity of the three-phase channels from their
+/// pre-fault phasors.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `WiringOptions` object (`time` ending the pre-fault cycle,
+///               `angle_tolerance_deg`, and `sum_tolerance` for the phase current sum).
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized array of `Diagnostic`, each naming the suspect channel.
+#[wasm_bindgen]
+pub fn analyze_wiring(recording: JsValue, options: JsValue) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: WiringOptions = options_from_js(options)?;
+    let diagnostics = check_wiring(&info, &options);
+    serde_wasm_bindgen::to_value(&diagnostics)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Calculates the apparent impedance trajectories of the six distance protection loops.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `ImpedanceOptions` object (`k0_magnitude`/`k0_angle_deg` for the
+///               zero-sequence compensation, `secondary` for secondary ohms, channel overrides,
+///               `step_cycles`, and `zones` as mho or quadrilateral characteristics).
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `ImpedanceAnalysis` with R and X series per loop and
+/// the zone entry times.
+#[wasm_bindgen]
+pub fn analyze_impedance(
+    recording: JsValue,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: ImpedanceOptions = options_from_js(options)?;
+    let analysis = loop_impedances(&info, &options)?;
+    serde_wasm_bindgen::to_value(&analysis)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Fits the decaying DC offset of the fault current on each current channel.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `DcOffsetOptions` object (`channels` by position, `inception_time`
+///               and `fit_cycles`).
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized array of `DcOffset`, with the time constant, X/R ratio
+/// and peak asymmetry of each channel.
+#[wasm_bindgen]
+pub fn analyze_dc_offset(
+    recording: JsValue,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: DcOffsetOptions = options_from_js(options)?;
+    let offsets = fit_dc_offsets(&info, &options)?;
+    serde_wasm_bindgen::to_value(&offsets)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Calculates differential operate and restraint currents and checks them against a percentage
+/// differential characteristic.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `DifferentialOptions` object (`local` and `remote` sides with their
+///               channels, ratio, vector group and polarity, `base_current`, `restraint` method,
+///               and the `pickup`, slopes and `breakpoint` of the characteristic).
+/// * `remote_recording` - An optional second `ComtradeInfo`, aligned in time with `recording`,
+///                        holding the remote side currents.
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `DifferentialAnalysis`.
+#[wasm_bindgen]
+pub fn analyze_differential(
+    recording: JsValue,
+    options: JsValue,
+    remote_recording: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: DifferentialOptions = options_from_js(options)?;
+    let remote = if remote_recording.is_undefined() || remote_recording.is_null() {
+        None
+    } else {
+        Some(recording_from_js(remote_recording)?)
+    };
+    let analysis = differential_currents(&info, remote.as_ref(), &options)?;
+    serde_wasm_bindgen::to_value(&analysis)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Locates a line fault from the negative sequence voltages and currents at both terminals.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` of the local terminal.
+/// * `options` - A `FaultLocationOptions` object with the line impedance `z1_magnitude`, and
+///               optionally `z1_angle_deg`, `line_length`, the terminal channels, the
+///               `remote_offset` and `clock_uncertainty` of the alignment and `target_accuracy`.
+/// * `remote_recording` - An optional second `ComtradeInfo` holding the remote terminal.
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `FaultLocation`.
+#[wasm_bindgen]
+pub fn analyze_fault_location(
+    recording: JsValue,
+    options: JsValue,
+    remote_recording: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: FaultLocationOptions = options_from_js(options)?;
+    let remote = if remote_recording.is_undefined() || remote_recording.is_null() {
+        None
+    } else {
+        Some(recording_from_js(remote_recording)?)
+    };
+    let location = locate_fault(&info, remote.as_ref(), &options)?;
+    serde_wasm_bindgen::to_value(&location)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Aligns two recordings, e.g. from the two ends of a line, and merges them onto a common time
+/// base.
+///
+/// # Arguments
+///
+/// * `local` - The `ComtradeInfo` of the recording whose time base is kept.
+/// * `remote` - The `ComtradeInfo` of the recording to align to it.
+/// * `options` - An optional `MergeOptions` object (`method` of `timestamps` or
+///               `cross_correlation`, the channels to correlate, `max_shift`, `sample_rate`, and
+///               labels prefixed to the channel names).
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `MergedRecording`: the `Alignment` found and the merged
+/// `ComtradeInfo`, which the analysis functions accept like any other recording.
+#[wasm_bindgen]
+pub fn merge_comtrade(
+    local: JsValue,
+    remote: JsValue,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let local = recording_from_js(local)?;
+    let remote = recording_from_js(remote)?;
+    let options: MergeOptions = options_from_js(options)?;
+    let merged = merge_recordings(&local, &remote, &options)?;
+    serde_wasm_bindgen::to_value(&merged)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Resamples every channel of a recording to one uniform sampling rate.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `ResampleOptions` object (`sample_rate`, `interpolation` of `linear`
+///               or `cubic`, and `no_anti_alias`).
+///
+/// # Returns
+///
+/// A `JsValue` containing the resampled `ComtradeInfo`, with a single sampling rate section.
+#[wasm_bindgen]
+pub fn resample_comtrade(
+    recording: JsValue,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: ResampleOptions = options_from_js(options)?;
+    let resampled = resample_recording(&info, &options)?;
+    serde_wasm_bindgen::to_value(&resampled)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Writes a recording, e.g. one returned by `resample_comtrade`, as COMTRADE files.
+///
+/// # Arguments
+///
+/// * `recording` - A `ComtradeInfo`.
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `ComtradeFiles`: the 1999 revision `cfg` text and the
+/// ASCII `dat` text, ready to be saved to files.
+#[wasm_bindgen]
+pub fn export_comtrade(recording: JsValue) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let files = write_comtrade(&info)?;
+    serde_wasm_bindgen::to_value(&files)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Analyses breaker operating times and auto-reclose sequences.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `BreakerOptions` object selecting the trip, close, 52a/52b and current
+///               channels by position, and the current interruption threshold. Channels not given
+///               are found by name.
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `BreakerAnalysis`, or `null` when there is no trip command.
+#[wasm_bindgen]
+pub fn analyze_breaker_operations(
+    recording: JsValue,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: BreakerOptions = options_from_js(options)?;
+    let analysis = analyze_breaker(
+        &info.analog_channels,
+        &info.digital_channels,
+        &info.timestamps,
+        info.frequency,
+        &options,
+    );
+    serde_wasm_bindgen::to_value(&analysis)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Adds user-defined calculated channels to a recording.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `channels` - An array of `CalculatedChannel` objects (`name`, `expression` such as
+///                `IA+IB+IC` or `TRIP & !52A`, optional `units` and `phase`), evaluated in order.
+///
+/// # Returns
+///
+/// A `JsValue` containing the `ComtradeInfo` with the new analog and digital channels appended,
+/// ready to pass to the analysis and export functions.
+#[wasm_bindgen]
+pub fn calculate_channels(
+    recording: JsValue,
+    channels: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let mut info = recording_from_js(recording)?;
+    let channels: Vec<CalculatedChannel> = options_from_js(channels)?;
+    add_calculated_channels(&mut info, &channels)?;
+    serde_wasm_bindgen::to_value(&info)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Checks the CT and VT ratios given by the primary and secondary factors of the analog channels.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+///
+/// # Returns
+///
+/// A `JsValue` containing the serialized `RatioCheck`, with the diagnostics of invalid,
+/// implausible and inconsistent ratios and the ratios inferred for the suspect channels.
+#[wasm_bindgen]
+pub fn analyze_ratios(recording: JsValue) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let check = check_ratios(&info.analog_channels);
+    serde_wasm_bindgen::to_value(&check)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Replaces the primary and secondary factors of analog channels and recomputes their primary
+/// and secondary values.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `overrides` - An array of `RatioOverride` objects (`channel` position, `primary_factor`,
+///                 `secondary_factor` and an optional `scaling_mode`), such as the `inferred`
+///                 ratios of `analyze_ratios`.
+///
+/// # Returns
+///
+/// A `JsValue` containing the `ComtradeInfo` with the new factors and values.
+#[wasm_bindgen]
+pub fn apply_ratio_overrides(
+    recording: JsValue,
+    overrides: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let mut info = recording_from_js(recording)?;
+    let overrides: Vec<RatioOverride> = options_from_js(overrides)?;
+    override_ratios(&mut info, &overrides)?;
+    serde_wasm_bindgen::to_value(&info)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Adds filtered copies of analog channels to a recording.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `filters` - An array of `FilterDefinition` objects (`channel` position, optional `name`,
+///               `kind` of `low_pass`, `high_pass`, `band_pass`, `notch` or `dc_removal`, `design`
+///               of `butterworth` or `fir`, the cutoffs or notch `center` and `bandwidth`, `order`
+///               and `zero_phase`), applied in order.
+///
+/// # Returns
+///
+/// A `JsValue` containing the `ComtradeInfo` with the filtered channels appended.
+#[wasm_bindgen]
+pub fn filter_channels(recording: JsValue, filters: JsValue) -> Result<JsValue, WasmComtradeError> {
+    let mut info = recording_from_js(recording)?;
+    let filters: Vec<FilterDefinition> = options_from_js(filters)?;
+    add_filtered_channels(&mut info, &filters)?;
+    serde_wasm_bindgen::to_value(&info)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Finds contact bounce and chatter on the digital channels.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `ChatterOptions` object (`interval` in seconds between transitions of
+///               one burst, `min_transitions`, `debounce` to also return debounced channels with
+///               `debounce_time`).
+///
+/// # Returns
+///
+/// A `JsValue` containing a `ChannelChatter` for each digital channel with at least one burst.
+#[wasm_bindgen]
+pub fn analyze_contact_chatter(
+    recording: JsValue,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: ChatterOptions = options_from_js(options)?;
+    let chatter = detect_chatter(&info.digital_channels, &info.timestamps, &options);
+    serde_wasm_bindgen::to_value(&chatter)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Builds the sequence-of-events log of the digital channels.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `SoeOptions` object (`include`/`exclude` wildcard patterns for channel
+///               names, `use_normal_state` to classify changes as alarm or return).
+///
+/// # Returns
+///
+/// A `JsValue` containing the list of `SoeEntry` rows in chronological order.
+#[wasm_bindgen]
+pub fn analyze_sequence_of_events(
+    recording: JsValue,
+    options: JsValue,
+) -> Result<JsValue, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: SoeOptions = options_from_js(options)?;
+    let entries = sequence_of_events(&info, &options);
+    serde_wasm_bindgen::to_value(&entries)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+/// Exports the sequence-of-events log of the digital channels as text.
+///
+/// # Arguments
+///
+/// * `recording` - The `ComtradeInfo` returned by `parse_comtrade`.
+/// * `options` - An optional `SoeOptions` object, as for `analyze_sequence_of_events`.
+/// * `format` - "csv" or "json".
+///
+/// # Returns
+///
+/// The log as CSV or JSON text, ready to be saved to a file.
+#[wasm_bindgen]
+pub fn export_sequence_of_events(
+    recording: JsValue,
+    options: JsValue,
+    format: String,
+) -> Result<String, WasmComtradeError> {
+    let info = recording_from_js(recording)?;
+    let options: SoeOptions = options_from_js(options)?;
+    let entries = sequence_of_events(&info, &options);
+    match format.to_lowercase().as_str() {
+        "csv" => Ok(soe_to_csv(&entries)),
+        "json" => soe_to_json(&entries),
+        other => Err(WasmComtradeError::AnalysisError(format!(
+            "Unsupported export format '{}'; use \"csv\" or \"json\".",
+            other
+        ))),
+    }
+}
+
+/// The raw bytes of the files that make up one recording.
+#[derive(Default)]
+pub struct RecordingFiles {
+    pub cfg: Option<Vec<u8>>,
+    pub dat: Option<Vec<u8>>,
+    pub cff: Option<Vec<u8>>,
+    pub hdr: Option<Vec<u8>>,
+    pub inf: Option<Vec<u8>>,
+}
+
+/// Splits a CFF file into its raw CFG, DAT, HDR and INF sections.
+///
+/// CFF files can contain binary data (DAT part), so the sections are split on
+/// the raw bytes before any text decoding. Each section starts after the line
+/// break that ends its header, and a binary DAT section is cut to the byte
+/// count its header declares (e.g. `--- file type: DAT BINARY: 1024 ---`), so
+/// the line break before the next header is not read as data.
+pub fn split_cff(cff_data: &[u8]) -> RecordingFiles {
+    let re = BytesRegex::new(
+        r"(?im-u)^---\s*file type:\s*(?P<file_type>[a-z]+)(?P<rest>.*?)---[ \t]*(\r\n|\n|\r)?",
+    )
+    .unwrap();
+    let size_re = BytesRegex::new(r":\s*(?P<size>[0-9]+)\s*$").unwrap();
+
+    let mut files = RecordingFiles::default();
+
+    let matches: Vec<_> = re.find_iter(cff_data).collect();
+    for i in 0..matches.len() {
+        let m = matches[i];
+        let header = &cff_data[m.start()..m.end()];
+        let caps = re.captures(header).unwrap();
+        let file_type = std::str::from_utf8(&caps["file_type"])
+            .unwrap()
+            .to_lowercase();
+
+        let next_start = if i + 1 < matches.len() {
+            matches[i + 1].start()
+        } else {
+            cff_data.len()
+        };
+
+        let mut content = &cff_data[m.end()..next_start];
+        let declared_size = size_re
+            .captures(&caps["rest"])
+            .and_then(|c| std::str::from_utf8(&c["size"]).ok()?.parse::<usize>().ok());
+        if let Some(size) = declared_size.filter(|&size| size <= content.len()) {
+            content = &content[..size];
+        }
+        let content = content.to_vec();
+
+        match file_type.as_str() {
+            "cfg" => files.cfg = Some(content),
+            "dat" => files.dat = Some(content),
+            "hdr" => files.hdr = Some(content),
+            "inf" => files.inf = Some(content),
+            _ => {}
+        }
+    }
+
+    files
+}
+
+/// Parses one recording into a `ComtradeInfo`, running the built-in checks
+/// and analyses.
+///
+/// A CFF file takes precedence over separate CFG/DAT/HDR/INF files.
+pub fn parse_recording(
+    files: RecordingFiles,
+    encoding: Option<&'static encoding_rs::Encoding>,
+) -> Result<ComtradeInfo, WasmComtradeError> {
     let result = panic::catch_unwind(move || {
-        if let Some(cff_data) = cff_file {
-            // CFF files can contain binary data (DAT part), so we split it into components first.
-            // Some parsers fail if they try to read the entire file as UTF-8.
-            let re =
-                BytesRegex::new(r"(?im-u)^---\s*file type:\s*(?P<file_type>[a-z]+).*?---").unwrap();
-
-            let mut cfg_raw = None;
-            let mut dat_raw = None;
-            let mut hdr_raw = None;
-            let mut inf_raw = None;
-
-            let matches: Vec<_> = re.find_iter(&cff_data).collect();
-            for i in 0..matches.len() {
-                let m = matches[i];
-                let header = &cff_data[m.start()..m.end()];
-                let caps = re.captures(header).unwrap();
-                let file_type = std::str::from_utf8(&caps["file_type"])
-                    .unwrap()
-                    .to_lowercase();
-
-                let next_start = if i + 1 < matches.len() {
-                    matches[i + 1].start()
-                } else {
-                    cff_data.len()
-                };
-
-                let content = &cff_data[m.end()..next_start];
-
-                match file_type.as_str() {
-                    "cfg" => cfg_raw = Some(content),
-                    "dat" => dat_raw = Some(content),
-                    "hdr" => hdr_raw = Some(content),
-                    "inf" => inf_raw = Some(content),
-                    _ => {}
-                }
-            }
+        let mut text = DecodedText::default();
+
+        if let Some(cff_data) = files.cff {
+            let RecordingFiles {
+                cfg: cfg_raw,
+                dat: dat_raw,
+                hdr: hdr_raw,
+                inf: inf_raw,
+                ..
+            } = split_cff(&cff_data);
 
             let decoded_cfg = cfg_raw.map(|b| {
-                let s = encoding.decode(b).0;
+                let s = text.decode(&b, encoding, "CFG");
                 let mut lines: Vec<_> = s.lines().filter(|l| !l.trim().is_empty()).collect();
                 lines.push(""); // Add trailing blank line
-                lines.join("\r\n")
+                let joined = lines.join("\r\n");
+                text.cfg = Some(s);
+                joined
             });
-            let decoded_hdr = hdr_raw.map(|b| encoding.decode(b).0);
-            let decoded_inf = inf_raw.map(|b| encoding.decode(b).0);
+            text.header = hdr_raw.map(|b| text.decode(&b, encoding, "HDR"));
+            text.information = inf_raw.map(|b| text.decode(&b, encoding, "INF"));
 
             if let Some(cfg) = decoded_cfg {
                 let mut builder = ComtradeParserBuilder::new();
                 builder = builder.cfg_file(std::io::Cursor::new(cfg.into_bytes()));
 
                 if let Some(dat_bytes) = dat_raw {
-                    builder = builder.dat_file(std::io::Cursor::new(dat_bytes.to_vec()));
+                    builder = builder.dat_file(std::io::Cursor::new(dat_bytes.clone()));
+                    text.dat = Some(dat_bytes);
                 }
 
-                if let Some(hdr) = decoded_hdr {
-                    builder = builder.hdr_file(std::io::Cursor::new(hdr.into_owned().into_bytes()));
+                if let Some(hdr) = &text.header {
+                    builder = builder.hdr_file(std::io::Cursor::new(hdr.clone().into_bytes()));
                 }
 
-                if let Some(inf) = decoded_inf {
-                    builder = builder.inf_file(std::io::Cursor::new(inf.into_owned().into_bytes()));
+                if let Some(inf) = &text.information {
+                    builder = builder.inf_file(std::io::Cursor::new(inf.clone().into_bytes()));
                 }
 
-                builder.build().parse()
+                (builder.build().parse(), text)
             } else {
                 panic!("No CFG section found in CFF file.");
             }
-        } else if let (Some(cfg_data), Some(dat_data)) = (cfg_file, dat_file) {
-            let (decoded_cfg, _, _) = encoding.decode(&cfg_data);
-            let cfg_reader = std::io::Cursor::new(decoded_cfg.into_owned().into_bytes());
-            let dat_reader = std::io::Cursor::new(dat_data.into_vec()); // DAT file is binary
-            ComtradeParserBuilder::new()
+        } else if let (Some(cfg_data), Some(dat_data)) = (files.cfg, files.dat) {
+            let decoded_cfg = text.decode(&cfg_data, encoding, "CFG");
+            let cfg_reader = std::io::Cursor::new(decoded_cfg.clone().into_bytes());
+            let dat_reader = std::io::Cursor::new(dat_data.clone()); // DAT file is binary
+            text.cfg = Some(decoded_cfg);
+            text.dat = Some(dat_data);
+            text.header = files.hdr.map(|b| text.decode(&b, encoding, "HDR"));
+            text.information = files.inf.map(|b| text.decode(&b, encoding, "INF"));
+
+            let mut builder = ComtradeParserBuilder::new()
                 .cfg_file(cfg_reader)
-                .dat_file(dat_reader)
-                .build()
-                .parse()
+                .dat_file(dat_reader);
+
+            if let Some(hdr) = &text.header {
+                builder = builder.hdr_file(std::io::Cursor::new(hdr.clone().into_bytes()));
+            }
+
+            if let Some(inf) = &text.information {
+                builder = builder.inf_file(std::io::Cursor::new(inf.clone().into_bytes()));
+            }
+
+            (builder.build().parse(), text)
         } else {
             panic!(
                 "Invalid file combination: either a CFF file, or both a CFG and a DAT file must be provided."
@@ -272,7 +1166,7 @@ pub fn parse_comtrade(
     });
 
     match result {
-        Ok(Ok(comtrade)) => {
+        Ok((Ok(comtrade), text)) => {
             let trigger_timestamp = comtrade.trigger_time.and_utc().timestamp_micros() as f64 / 1_000_000.0;
             let start_time_seconds = comtrade.start_time.and_utc().timestamp_micros() as f64 / 1_000_000.0;
 
@@ -296,15 +1190,30 @@ pub fn parse_comtrade(
                 .map(|&t_us| t_us / 1_000_000.0)
                 .collect();
 
+            let mut scaling_failures = Vec::new();
             let analog_channels: Vec<SerializableAnalogChannel> = comtrade
                 .analog_channels
                 .iter()
                 .map(|ch| {
-                    let primary_values: Vec<f64> = (0..ch.data.len())
-                        .map(|i| ch.primary_value(i).unwrap_or(ch.data[i]))
+                    let primary: Vec<Option<f64>> =
+                        (0..ch.data.len()).map(|i| ch.primary_value(i)).collect();
+                    let secondary: Vec<Option<f64>> =
+                        (0..ch.data.len()).map(|i| ch.secondary_value(i)).collect();
+                    scaling_failures.extend(scaling_failure(&ch.config.name, "primary", &primary));
+                    scaling_failures.extend(scaling_failure(
+                        &ch.config.name,
+                        "secondary",
+                        &secondary,
+                    ));
+                    let primary_values: Vec<f64> = primary
+                        .iter()
+                        .zip(&ch.data)
+                        .map(|(v, raw)| v.unwrap_or(*raw))
                         .collect();
-                    let secondary_values: Vec<f64> = (0..ch.data.len())
-                        .map(|i| ch.secondary_value(i).unwrap_or(ch.data[i]))
+                    let secondary_values: Vec<f64> = secondary
+                        .iter()
+                        .zip(&ch.data)
+                        .map(|(v, raw)| v.unwrap_or(*raw))
                         .collect();
                     let scaling_mode = match ch.config.scaling_mode {
                         comtrade::AnalogScalingMode::Primary => "Primary".to_string(),
@@ -338,6 +1247,7 @@ pub fn parse_comtrade(
                         secondary_factor: ch.config.secondary_factor,
                         skew: ch.config.skew,
                         skew_timestamps,
+                        expression: None,
                     }
                 })
                 .collect();
@@ -348,21 +1258,9 @@ pub fn parse_comtrade(
                 .map(SerializableDigitalChannel::from)
                 .collect();
 
-            let mut warnings = Vec::new();
-            let mut errors = Vec::new();
+            let mut warnings = text.warnings;
             let mut analysis_notes = Vec::new();
 
-            let actual_total_channels =
-                comtrade.analog_channels.len() + comtrade.status_channels.len();
-            if comtrade.declared_total_channels != actual_total_channels {
-                errors.push(format!(
-                    "The total number of channels ({}) does not match the sum of analog ({}) and digital ({}) channels.",
-                    comtrade.declared_total_channels,
-                    comtrade.analog_channels.len(),
-                    comtrade.status_channels.len()
-                ));
-            }
-
             // Check frequency
             if comtrade.line_frequency != 0.0
                 && (comtrade.line_frequency - 50.0).abs() > 1.0
@@ -374,61 +1272,37 @@ pub fn parse_comtrade(
                 ));
             }
 
-            // Detect voltage sag
-            let mut sag_detected = false;
-            let mut sag_start_time = 0.0;
-
-            for channel in &analog_channels {
-                let name = channel.name.to_lowercase();
-                let units = channel.units.to_lowercase();
-                if name.contains("v") || units == "v" || units == "kv" {
-                    let window_size = 50.min(channel.values.len());
-                    if window_size > 0 {
-                        let mut sum_sq_init = 0.0;
-                        for i in 0..window_size {
-                            sum_sq_init += channel.values[i] * channel.values[i];
-                        }
-                        let nominal_rms = (sum_sq_init / window_size as f64).sqrt();
-
-                        let sag_threshold = nominal_rms * 0.8;
-                        let mut sum_sq = 0.0;
-                        for i in 0..window_size {
-                            sum_sq += channel.values[i] * channel.values[i];
-                        }
-
-                        let mut rms = (sum_sq / window_size as f64).sqrt();
-                        if rms < sag_threshold {
-                            sag_detected = true;
-                            sag_start_time = timestamps[window_size - 1];
-                            analysis_notes.push(format!(
-                                "Possible voltage sag detected on channel '{}' at {:.4} seconds.",
-                                channel.name, sag_start_time - start_time_seconds
-                            ));
-                            break;
-                        }
-
-                        for i in window_size..channel.values.len() {
-                            sum_sq += channel.values[i] * channel.values[i];
-                            sum_sq -=
-                                channel.values[i - window_size] * channel.values[i - window_size];
-                            if sum_sq < 0.0 {
-                                sum_sq = 0.0;
-                            }
-                            rms = (sum_sq / window_size as f64).sqrt();
-                            if rms < sag_threshold {
-                                sag_detected = true;
-                                sag_start_time = timestamps[i];
-                                analysis_notes.push(format!("Possible voltage sag detected on channel '{}' at {:.4} seconds.", channel.name, sag_start_time - start_time_seconds));
-                                break;
-                            }
-                        }
-                        if sag_detected {
-                            break;
-                        }
-                    }
+            let clipped_intervals = detect_clipping(&analog_channels, &timestamps, MIN_CLIPPED_RUN);
+
+            // Detect voltage sags, swells and interruptions
+            let voltage_events = detect_voltage_events(
+                &analog_channels,
+                &timestamps,
+                comtrade.line_frequency,
+                &clipped_intervals,
+                &VoltageEventOptions::default(),
+            )
+            .events;
+            for event in &voltage_events {
+                analysis_notes.push(voltage_event_note(event, start_time_seconds));
+                if !event.reliable {
+                    warnings.push(format!(
+                        "The {} starting at {:.4} seconds overlaps clipped samples; its residual voltage is unreliable.",
+                        voltage_event_name(event.kind),
+                        event.start_time - start_time_seconds
+                    ));
                 }
             }
 
+            let first_dip = voltage_events.iter().find(|e| {
+                matches!(
+                    e.kind,
+                    VoltageEventKind::Sag | VoltageEventKind::Interruption
+                )
+            });
+            let sag_detected = first_dip.is_some();
+            let sag_start_time = first_dip.map_or(0.0, |e| e.start_time);
+
             if sag_detected {
                 let mut trip_found = false;
                 for (digital_index, digital_channel) in digital_channels.iter().enumerate() {
@@ -464,25 +1338,84 @@ pub fn parse_comtrade(
                 }
             }
 
-            let info = ComtradeInfo {
+            let mut info = ComtradeInfo {
                 station: comtrade.station_name.clone(),
                 recording_device_id: comtrade.recording_device_id.clone(),
                 start_time: comtrade.start_time.to_string(),
                 trigger_time: comtrade.trigger_time.to_string(),
                 data_format: data_format_to_str(&comtrade.data_format).to_string(),
                 frequency: comtrade.line_frequency,
+                config: ComtradeConfig::from(&comtrade),
+                information: text.information.as_deref().map(parse_inf),
+                header: text.header,
+                encodings: text.encodings,
                 analog_channels,
                 digital_channels,
                 timestamps,
                 warnings,
-                errors,
+                errors: Vec::new(),
                 analysis_notes,
+                diagnostics: Vec::new(),
+                clipped_intervals,
+                voltage_events,
+                ct_saturation: Vec::new(),
+                inrush: None,
+                breaker: None,
+                chatter: Vec::new(),
                 trigger_timestamp,
             };
-            serde_wasm_bindgen::to_value(&info)
-                .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+
+            if let Some(cfg) = &text.cfg {
+                info.diagnostics = validate_recording(cfg, text.dat.as_deref(), &info);
+            }
+            info.diagnostics.extend(scaling_failures);
+            info.diagnostics
+                .extend(check_ratios(&info.analog_channels).diagnostics);
+            info.diagnostics
+                .extend(clipping_diagnostics(&info.clipped_intervals));
+
+            info.ct_saturation =
+                detect_ct_saturation(&info.analog_channels, &info.timestamps, info.frequency);
+            info.diagnostics.extend(ct_saturation_diagnostics(
+                &info.ct_saturation,
+                start_time_seconds,
+            ));
+
+            info.inrush = detect_inrush(
+                &info.analog_channels,
+                &info.timestamps,
+                info.frequency,
+                &info.clipped_intervals,
+            );
+            if let Some(inrush) = &info.inrush {
+                let note = inrush_note(inrush, start_time_seconds);
+                info.analysis_notes.push(note);
+            }
+
+            info.breaker = analyze_breaker(
+                &info.analog_channels,
+                &info.digital_channels,
+                &info.timestamps,
+                info.frequency,
+                &BreakerOptions::default(),
+            );
+            if let Some(breaker) = &info.breaker {
+                let notes = breaker_notes(breaker, start_time_seconds);
+                info.analysis_notes.extend(notes);
+            }
+
+            info.chatter = detect_chatter(
+                &info.digital_channels,
+                &info.timestamps,
+                &ChatterOptions::default(),
+            );
+            info.diagnostics.extend(chatter_diagnostics(&info.chatter));
+            info.diagnostics
+                .extend(check_wiring(&info, &WiringOptions::default()));
+
+            Ok(info)
         }
-        Ok(Err(e)) => Err(WasmComtradeError::ParseError(format!("{:?}", e))),
+        Ok((Err(e), _)) => Err(WasmComtradeError::ParseError(format!("{:?}", e))),
         Err(e) => {
             let message = if let Some(s) = e.downcast_ref::<&'static str>() {
                 *s
@@ -514,55 +1447,53 @@ mod tests {
     #[test]
     fn test_split_cff() {
         let cff_data = b"--- file type: CFG ---\nCFG CONTENT\n--- file type: DAT ---\nDAT CONTENT\n--- file type: INF ---\nINF CONTENT\n--- file type: HDR ---\nHDR CONTENT";
-        let re =
-            BytesRegex::new(r"(?im-u)^---\s*file type:\s*(?P<file_type>[a-z]+).*?---").unwrap();
-
-        let mut cfg_raw = None;
-        let mut dat_raw = None;
-        let mut hdr_raw = None;
-        let mut inf_raw = None;
-
-        let matches: Vec<_> = re.find_iter(cff_data).collect();
-        for i in 0..matches.len() {
-            let m = matches[i];
-            let header = &cff_data[m.start()..m.end()];
-            let caps = re.captures(header).unwrap();
-            let file_type = std::str::from_utf8(&caps["file_type"])
-                .unwrap()
-                .to_lowercase();
-
-            let next_start = if i + 1 < matches.len() {
-                matches[i + 1].start()
-            } else {
-                cff_data.len()
-            };
-
-            let content = &cff_data[m.end()..next_start];
-
-            match file_type.as_str() {
-                "cfg" => cfg_raw = Some(content),
-                "dat" => dat_raw = Some(content),
-                "hdr" => hdr_raw = Some(content),
-                "inf" => inf_raw = Some(content),
-                _ => {}
-            }
-        }
+        let files = split_cff(cff_data);
 
         assert_eq!(
-            std::str::from_utf8(cfg_raw.unwrap()).unwrap().trim(),
+            std::str::from_utf8(files.cfg.as_deref().unwrap())
+                .unwrap()
+                .trim(),
             "CFG CONTENT"
         );
         assert_eq!(
-            std::str::from_utf8(dat_raw.unwrap()).unwrap().trim(),
+            std::str::from_utf8(files.dat.as_deref().unwrap())
+                .unwrap()
+                .trim(),
             "DAT CONTENT"
         );
         assert_eq!(
-            std::str::from_utf8(inf_raw.unwrap()).unwrap().trim(),
+            std::str::from_utf8(files.inf.as_deref().unwrap())
+                .unwrap()
+                .trim(),
             "INF CONTENT"
         );
         assert_eq!(
-            std::str::from_utf8(hdr_raw.unwrap()).unwrap().trim(),
+            std::str::from_utf8(files.hdr.as_deref().unwrap())
+                .unwrap()
+                .trim(),
             "HDR CONTENT"
         );
     }
+
+    #[test]
+    fn test_split_binary_cff() {
+        // Binary records may contain line break bytes of their own.
+        let records: &[u8] = &[1, 0, 0, 0, 0x0d, 0x0a, 0, 0, 0xff, 0x7f];
+        let mut cff_data =
+            b"--- file type: CFG ---\r\nstation,dev,2013\r\n--- file type: DAT BINARY: 10 ---\r\n"
+                .to_vec();
+        cff_data.extend_from_slice(records);
+        cff_data.extend_from_slice(b"\r\n--- file type: HDR ---\r\nnotes\r\n");
+        let files = split_cff(&cff_data);
+
+        assert_eq!(files.cfg.as_deref(), Some(&b"station,dev,2013\r\n"[..]));
+        assert_eq!(files.dat.as_deref(), Some(records));
+        assert_eq!(files.hdr.as_deref(), Some(&b"notes\r\n"[..]));
+
+        // One record of one 16-bit analog channel: no length or order errors.
+        let channel = test_support::analog_channel(1, "VA", "V", "A", vec![32767.0]);
+        let mut info = test_support::recording(1000.0, 50.0, vec![channel], vec![]);
+        info.data_format = "BINARY".to_string();
+        assert_eq!(validate_dat(files.dat.as_deref().unwrap(), &info), []);
+    }
 }
@@ -0,0 +1,547 @@
+// comtrade_rust/src/merge.rs
+// This file contains the time alignment of two recordings and their merge onto a common time base.
+// This file exists so recordings from the two ends of a line can be analysed together as one recording.
+// RELEVANT FILES: comtrade_rust/src/dsp.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::clipping::ClippedInterval;
+use crate::dsp::{interpolate, nominal_frequency, sample_rate_at, samples_per_cycle};
+use crate::phases::{channel_phase, three_phase_set};
+use crate::sequence_of_events::format_timestamp;
+use crate::{
+    ComtradeInfo, SerializableAnalogChannel, SerializableDigitalChannel, SerializableSamplingRate,
+    WasmComtradeError,
+};
+
+/// The largest clock offset searched by cross-correlation by default, in seconds.
+const DEFAULT_MAX_SHIFT: f64 = 0.5;
+/// A peak correlation below this is reported as an unreliable alignment.
+const WEAK_CORRELATION: f64 = 0.5;
+/// Uncertainties above this are reported, since they exceed a sample at typical rates.
+const COARSE_UNCERTAINTY: f64 = 1e-3;
+
+/// How the two recordings are aligned in time.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlignmentMethod {
+    /// By the absolute timestamps, corrected by the CFG time codes.
+    #[default]
+    Timestamps,
+    /// By cross-correlating the cycle-to-cycle change of one signal from each
+    /// recording, for recorders whose clocks are not synchronised.
+    CrossCorrelation,
+}
+
+/// Options for aligning and merging two recordings. Every field is optional.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct MergeOptions {
+    /// The alignment method. Defaults to `timestamps`.
+    pub method: AlignmentMethod,
+    /// The position in the local `analog_channels` of the signal to
+    /// correlate. Defaults to the phase A voltage, or the first voltage.
+    pub local_channel: Option<usize>,
+    /// The position in the remote `analog_channels` of the signal to
+    /// correlate. Defaults to the channel of the same phase and kind.
+    pub remote_channel: Option<usize>,
+    /// The largest clock offset searched by cross-correlation, in seconds,
+    /// around the timestamp alignment. Defaults to 0.5.
+    pub max_shift: Option<f64>,
+    /// The sampling rate of the merged recording in Hz. Defaults to the
+    /// higher rate of the two recordings.
+    pub sample_rate: Option<f64>,
+    /// Prefixed to the local channel names. Defaults to the station name.
+    pub local_label: Option<String>,
+    /// Prefixed to the remote channel names. Defaults to the station name.
+    pub remote_label: Option<String>,
+}
+
+/// How the remote recording was placed on the time base of the local one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Alignment {
+    /// The method used.
+    pub method: AlignmentMethod,
+    /// Seconds added to the remote timestamps to express them on the local
+    /// time base.
+    pub offset: f64,
+    /// The estimated uncertainty of `offset` in seconds, from the CFG time
+    /// quality codes or the sample period. `None` when unknown.
+    pub uncertainty: Option<f64>,
+    /// The peak normalised correlation, for cross-correlation.
+    pub correlation: Option<f64>,
+    /// Reasons to doubt the alignment.
+    pub warnings: Vec<String>,
+}
+
+/// Two recordings merged onto a common time base.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MergedRecording {
+    /// How the recordings were aligned.
+    pub alignment: Alignment,
+    /// The merged recording, covering the time both recordings overlap,
+    /// with the local channels followed by the remote ones.
+    pub recording: ComtradeInfo,
+}
+
+/// Parses a CFG time code such as "+05:30", "-5h30" or "0" into seconds
+/// ahead of UTC.
+fn utc_offset(code: &str) -> Option<f64> {
+    let code = code.trim();
+    let (sign, rest) = match code.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, code.strip_prefix('+').unwrap_or(code)),
+    };
+    let mut parts = rest.split([':', 'h', 'H']);
+    let hours: f64 = parts.next()?.trim().parse().ok()?;
+    let minutes: f64 = match parts.next().map(str::trim) {
+        Some(m) if !m.is_empty() => m.parse().ok()?,
+        _ => 0.0,
+    };
+    Some(sign * (hours * 3600.0 + minutes * 60.0))
+}
+
+/// Returns the clock uncertainty in seconds of a CFG time quality code: 0
+/// when locked, 10^(n-10) s for codes 1 to B, and infinity for F (clock
+/// failure). `None` if the code is not recognised.
+fn clock_uncertainty(quality: &str) -> Option<f64> {
+    let code = u8::from_str_radix(quality.trim(), 16).ok()?;
+    match code {
+        0 => Some(0.0),
+        1..=11 => Some(10f64.powi(i32::from(code) - 10)),
+        15 => Some(f64::INFINITY),
+        _ => None,
+    }
+}
+
+/// Returns the highest sampling rate of a recording.
+fn recording_rate(info: &ComtradeInfo) -> Option<f64> {
+    info.config
+        .sampling_rates
+        .iter()
+        .map(|r| r.rate_hz)
+        .filter(|r| *r > 0.0)
+        .reduce(f64::max)
+        .or_else(|| sample_rate_at(&info.timestamps, 0))
+}
+
+/// Returns the sampling rate of the common time base.
+fn common_rate(
+    local: &ComtradeInfo,
+    remote: &ComtradeInfo,
+    options: &MergeOptions,
+) -> Result<f64, WasmComtradeError> {
+    options
+        .sample_rate
+        .or_else(|| match (recording_rate(local), recording_rate(remote)) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        })
+        .filter(|r| *r > 0.0)
+        .ok_or_else(|| {
+            WasmComtradeError::AnalysisError("The sampling rate is unknown.".to_string())
+        })
+}
+
+/// Returns the default channel to correlate: the phase A voltage, the first
+/// voltage, or the first channel.
+fn correlation_channel(info: &ComtradeInfo) -> Option<usize> {
+    let channels = &info.analog_channels;
+    three_phase_set(channels, |c| c.is_voltage())
+        .map(|set| set[0])
+        .or_else(|| channels.iter().position(|c| c.is_voltage()))
+        .or((!channels.is_empty()).then_some(0))
+}
+
+/// Returns the remote channel of the same kind and phase as `local`.
+fn matching_channel(remote: &ComtradeInfo, local: &SerializableAnalogChannel) -> Option<usize> {
+    let channels = &remote.analog_channels;
+    let same_kind = |c: &SerializableAnalogChannel| {
+        c.is_voltage() == local.is_voltage() && c.is_current() == local.is_current()
+    };
+    channels
+        .iter()
+        .position(|c| same_kind(c) && channel_phase(c) == channel_phase(local))
+        .or_else(|| channels.iter().position(same_kind))
+        .or((!channels.is_empty()).then_some(0))
+}
+
+/// Resamples a channel at `rate` from its first sample and returns the
+/// absolute cycle-to-cycle change, which is zero in the steady state and so
+/// correlates without the one-cycle ambiguity of the sinusoid itself.
+fn superimposed(
+    channel: &SerializableAnalogChannel,
+    timestamps: &[f64],
+    rate: f64,
+    frequency: f64,
+) -> (f64, Vec<f64>) {
+    let values = &channel.primary_values;
+    let n = values.len().min(timestamps.len());
+    if n == 0 {
+        return (0.0, Vec::new());
+    }
+    let start = timestamps[0];
+    let count = ((timestamps[n - 1] - start) * rate).floor() as usize + 1;
+    let resampled: Vec<f64> = (0..count)
+        .map(|k| interpolate(&timestamps[..n], values, start + k as f64 / rate).unwrap_or(0.0))
+        .collect();
+    let cycle = samples_per_cycle(rate, frequency);
+    let change = (0..count)
+        .map(|k| {
+            if k < cycle {
+                0.0
+            } else {
+                (resampled[k] - resampled[k - cycle]).abs()
+            }
+        })
+        .collect();
+    (start, change)
+}
+
+/// Works out the offset that places the remote recording on the time base
+/// of the local one.
+pub fn align_recordings(
+    local: &ComtradeInfo,
+    remote: &ComtradeInfo,
+    options: &MergeOptions,
+) -> Result<Alignment, WasmComtradeError> {
+    let mut warnings = Vec::new();
+    let local_code = local.config.time_code.as_deref().and_then(utc_offset);
+    let remote_code = remote.config.time_code.as_deref().and_then(utc_offset);
+    if local_code.is_some() != remote_code.is_some() {
+        warnings.push(
+            "Only one recording states its time code; both are assumed to be on the same time zone."
+                .to_string(),
+        );
+    }
+    let offset = local_code.unwrap_or(0.0) - remote_code.unwrap_or(0.0);
+
+    if options.method == AlignmentMethod::Timestamps {
+        let qualities = [&local.config.time_quality, &remote.config.time_quality]
+            .map(|q| q.as_deref().and_then(clock_uncertainty));
+        let uncertainty = match qualities {
+            [Some(a), Some(b)] => Some(a + b),
+            _ => {
+                warnings.push(
+                    "The time quality of a recording is not stated; the clocks are assumed to be synchronised."
+                        .to_string(),
+                );
+                None
+            }
+        };
+        match uncertainty {
+            Some(u) if u.is_infinite() => warnings.push(
+                "A recorder reports a clock failure; align the recordings by cross-correlation instead."
+                    .to_string(),
+            ),
+            Some(u) if u > COARSE_UNCERTAINTY => warnings.push(format!(
+                "The recorder clocks may differ by up to {:.1} ms.",
+                u * 1000.0
+            )),
+            _ => {}
+        }
+        return Ok(Alignment {
+            method: options.method,
+            offset,
+            uncertainty,
+            correlation: None,
+            warnings,
+        });
+    }
+
+    let local_index = match options.local_channel {
+        Some(index) => index,
+        None => correlation_channel(local).ok_or_else(|| {
+            WasmComtradeError::AnalysisError(
+                "The local recording has no analog channel to correlate.".to_string(),
+            )
+        })?,
+    };
+    let local_channel = local.analog_channel(local_index)?;
+    let remote_index = match options.remote_channel {
+        Some(index) => index,
+        None => matching_channel(remote, local_channel).ok_or_else(|| {
+            WasmComtradeError::AnalysisError(
+                "The remote recording has no analog channel to correlate.".to_string(),
+            )
+        })?,
+    };
+    let remote_channel = remote.analog_channel(remote_index)?;
+
+    let rate = common_rate(local, remote, options)?;
+    let frequency = nominal_frequency(local.frequency);
+    let (local_start, a) = superimposed(local_channel, &local.timestamps, rate, frequency);
+    let (remote_start, b) = superimposed(remote_channel, &remote.timestamps, rate, frequency);
+
+    // Local sample k lines up with remote sample k + d0 - shift.
+    let d0 = ((local_start - offset - remote_start) * rate).round() as i64;
+    let max_shift = (options.max_shift.unwrap_or(DEFAULT_MAX_SHIFT).abs() * rate) as i64;
+    let min_overlap = 2 * samples_per_cycle(rate, frequency);
+    let mut best: Option<(i64, f64)> = None;
+    for shift in -max_shift..=max_shift {
+        let lag = d0 - shift;
+        let first = (-lag).max(0) as usize;
+        let last = (a.len() as i64).min(b.len() as i64 - lag);
+        if last <= first as i64 || ((last as usize) - first) < min_overlap {
+            continue;
+        }
+        let (mut ab, mut aa, mut bb) = (0.0, 0.0, 0.0);
+        for k in first..last as usize {
+            let x = a[k];
+            let y = b[(k as i64 + lag) as usize];
+            ab += x * y;
+            aa += x * x;
+            bb += y * y;
+        }
+        if aa > 0.0 && bb > 0.0 {
+            let correlation = ab / (aa * bb).sqrt();
+            if best.is_none_or(|(_, c)| correlation > c) {
+                best = Some((shift, correlation));
+            }
+        }
+    }
+
+    let (shift, correlation) = best.ok_or_else(|| {
+        WasmComtradeError::AnalysisError(format!(
+            "'{}' and '{}' show no change to correlate within the searched offsets.",
+            local_channel.name, remote_channel.name
+        ))
+    })?;
+    if correlation < WEAK_CORRELATION {
+        warnings.push(format!(
+            "The correlation between '{}' and '{}' is weak ({:.2}); the alignment may be wrong.",
+            local_channel.name, remote_channel.name, correlation
+        ));
+    }
+    Ok(Alignment {
+        method: options.method,
+        offset: local_start - remote_start - (d0 - shift) as f64 / rate,
+        uncertainty: Some(1.0 / rate),
+        correlation: Some(correlation),
+        warnings,
+    })
+}
+
+/// Returns the label for channel names, falling back when the station is unnamed.
+fn label(chosen: &Option<String>, station: &str, fallback: &str) -> String {
+    match chosen {
+        Some(label) => label.clone(),
+        None if !station.trim().is_empty() => station.trim().to_string(),
+        None => fallback.to_string(),
+    }
+}
+
+/// Resamples the channels of one recording onto `times`, shifting its own
+/// timestamps by `offset`, and appends them to `merged`.
+fn append_channels(
+    merged: &mut ComtradeInfo,
+    source: &ComtradeInfo,
+    offset: f64,
+    prefix: &str,
+    times: &[f64],
+) {
+    let shifted: Vec<f64> = source.timestamps.iter().map(|t| t + offset).collect();
+    let first_analog = merged.analog_channels.len();
+
+    for channel in &source.analog_channels {
+        let own_times: Vec<f64> = if channel.skew_timestamps.len() == channel.values.len() {
+            channel.skew_timestamps.iter().map(|t| t + offset).collect()
+        } else {
+            shifted.clone()
+        };
+        let resample = |values: &[f64]| -> Vec<f64> {
+            times
+                .iter()
+                .map(|&t| interpolate(&own_times, values, t).unwrap_or(f64::NAN))
+                .collect()
+        };
+        merged.analog_channels.push(SerializableAnalogChannel {
+            index: merged.analog_channels.len() as u32 + 1,
+            name: format!("{}: {}", prefix, channel.name),
+            values: resample(&channel.values),
+            primary_values: resample(&channel.primary_values),
+            secondary_values: resample(&channel.secondary_values),
+            skew: 0.0,
+            skew_timestamps: times.to_vec(),
+            ..channel.clone()
+        });
+    }
+
+    for channel in &source.digital_channels {
+        let values = times
+            .iter()
+            .map(|&t| {
+                let k = shifted.partition_point(|&s| s <= t).max(1) - 1;
+                channel
+                    .values
+                    .get(k)
+                    .copied()
+                    .unwrap_or(channel.initial_value)
+            })
+            .collect();
+        merged.digital_channels.push(SerializableDigitalChannel {
+            index: merged.digital_channels.len() as u32 + 1,
+            name: format!("{}: {}", prefix, channel.name),
+            values,
+            ..channel.clone()
+        });
+    }
+
+    for interval in &source.clipped_intervals {
+        let start = times.partition_point(|&t| t < interval.start_time + offset);
+        let end = times.partition_point(|&t| t <= interval.end_time + offset);
+        if start < end {
+            merged.clipped_intervals.push(ClippedInterval {
+                channel_index: first_analog + interval.channel_index,
+                channel: format!("{}: {}", prefix, interval.channel),
+                start_sample: start,
+                end_sample: end - 1,
+                start_time: times[start],
+                end_time: times[end - 1],
+                ..interval.clone()
+            });
+        }
+    }
+}
+
+/// Aligns `remote` to `local` and merges both channel sets onto a common,
+/// uniformly sampled time base covering the interval both recordings span.
+/// Analog channels are linearly interpolated and digital channels hold
+/// their last state. Channel names are prefixed with the station labels.
+pub fn merge_recordings(
+    local: &ComtradeInfo,
+    remote: &ComtradeInfo,
+    options: &MergeOptions,
+) -> Result<MergedRecording, WasmComtradeError> {
+    let alignment = align_recordings(local, remote, options)?;
+    let rate = common_rate(local, remote, options)?;
+
+    let span = |info: &ComtradeInfo, offset: f64| {
+        Some((
+            info.timestamps.first()? + offset,
+            info.timestamps.last()? + offset,
+        ))
+    };
+    let no_overlap =
+        || WasmComtradeError::AnalysisError("The recordings do not overlap in time.".to_string());
+    let (local_start, local_end) = span(local, 0.0).ok_or_else(no_overlap)?;
+    let (remote_start, remote_end) = span(remote, alignment.offset).ok_or_else(no_overlap)?;
+    let start = local_start.max(remote_start);
+    let end = local_end.min(remote_end);
+    if end <= start {
+        return Err(no_overlap());
+    }
+    let samples = ((end - start) * rate + 1e-6).floor() as usize + 1;
+    let times: Vec<f64> = (0..samples)
+        .map(|k| (start + k as f64 / rate).min(end))
+        .collect();
+
+    let mut local_label = label(&options.local_label, &local.station, "Local");
+    let mut remote_label = label(&options.remote_label, &remote.station, "Remote");
+    if local_label == remote_label {
+        local_label = format!("{} (local)", local_label);
+        remote_label = format!("{} (remote)", remote_label);
+    }
+
+    let mut merged = ComtradeInfo {
+        station: format!("{} / {}", local.station, remote.station),
+        recording_device_id: format!(
+            "{} / {}",
+            local.recording_device_id, remote.recording_device_id
+        ),
+        start_time: format_timestamp(start),
+        trigger_time: local.trigger_time.clone(),
+        data_format: local.data_format.clone(),
+        frequency: local.frequency,
+        config: local.config.clone(),
+        warnings: alignment.warnings.clone(),
+        trigger_timestamp: local.trigger_timestamp,
+        ..Default::default()
+    };
+    append_channels(&mut merged, local, 0.0, &local_label, &times);
+    append_channels(&mut merged, remote, alignment.offset, &remote_label, &times);
+    merged.timestamps = times;
+
+    let config = &mut merged.config;
+    config.station_name = merged.station.clone();
+    config.analog_channels = merged.analog_channels.len() as u32;
+    config.digital_channels = merged.digital_channels.len() as u32;
+    config.total_channels = config.analog_channels + config.digital_channels;
+    config.num_sampling_rates = 1;
+    config.sampling_rates = vec![SerializableSamplingRate {
+        rate_hz: rate,
+        end_sample_number: samples as u32,
+    }];
+    config.start_time = merged.start_time.clone();
+
+    Ok(MergedRecording {
+        alignment,
+        recording: merged,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording};
+
+    /// A 50 Hz voltage that steps from 1 to 3 at t = 0.15 s.
+    fn voltage(t: f64) -> f64 {
+        let amplitude = if t < 0.15 { 1.0 } else { 3.0 };
+        amplitude * (2.0 * std::f64::consts::PI * 50.0 * t).sin()
+    }
+
+    #[test]
+    fn test_merge_recordings() {
+        let local_values = (0..300).map(|k| voltage(k as f64 / 1000.0)).collect();
+        let mut local = recording(
+            1000.0,
+            50.0,
+            vec![analog_channel(1, "VA", "kV", "A", local_values)],
+            vec![],
+        );
+        // The remote recorder starts 20 ms later and its clock runs 23 ms ahead.
+        let remote_values = (0..300)
+            .map(|k| voltage(0.02 + k as f64 / 1000.0))
+            .collect();
+        let mut remote = recording(
+            1000.0,
+            50.0,
+            vec![analog_channel(1, "VA", "kV", "A", remote_values)],
+            vec![],
+        );
+        for t in remote.timestamps.iter_mut() {
+            *t += 0.043;
+        }
+        remote.analog_channels[0].skew_timestamps = remote.timestamps.clone();
+
+        let options = MergeOptions {
+            method: AlignmentMethod::CrossCorrelation,
+            ..Default::default()
+        };
+        let merged = merge_recordings(&local, &remote, &options).unwrap();
+        assert!((merged.alignment.offset + 0.023).abs() < 1e-6);
+        assert!(merged.alignment.correlation.unwrap() > 0.99);
+
+        let info = &merged.recording;
+        assert_eq!(info.analog_channels[1].name, "Remote: VA");
+        assert_eq!(info.timestamps.len(), 280);
+        assert!((info.timestamps[0] - 0.02).abs() < 1e-9);
+        for k in 0..info.timestamps.len() {
+            let (a, b) = (
+                info.analog_channels[0].values[k],
+                info.analog_channels[1].values[k],
+            );
+            assert!((a - b).abs() < 1e-6);
+        }
+
+        local.config.time_code = Some("+01:00".to_string());
+        local.config.time_quality = Some("7".to_string());
+        remote.config.time_code = Some("0".to_string());
+        remote.config.time_quality = Some("5".to_string());
+        let alignment = align_recordings(&local, &remote, &MergeOptions::default()).unwrap();
+        assert_eq!(alignment.offset, 3600.0);
+        assert!((alignment.uncertainty.unwrap() - 1.01e-3).abs() < 1e-12);
+        assert_eq!(alignment.warnings.len(), 1);
+    }
+}
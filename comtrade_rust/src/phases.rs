@@ -0,0 +1,107 @@
+// comtrade_rust/src/phases.rs
+// This file contains the mapping of analog channels to power system phases.
+// This file exists so three-phase analyses find the A, B and C channels the same way whichever naming convention the recorder uses.
+// RELEVANT FILES: comtrade_rust/src/frequency.rs, comtrade_rust/src/lib.rs
+
+use num_complex::Complex64;
+
+use crate::SerializableAnalogChannel;
+
+/// Returns 0, 1 or 2 for phase A, B or C of a phase identifier such as "A",
+/// "AN", "R", or "L1". Phase-to-phase, neutral and ground identifiers give `None`.
+pub fn phase_number(phase: &str) -> Option<usize> {
+    match phase.trim().to_uppercase().as_str() {
+        "A" | "AN" | "AG" | "R" | "L1" | "1" => Some(0),
+        "B" | "BN" | "BG" | "S" | "L2" | "2" => Some(1),
+        "C" | "CN" | "CG" | "T" | "L3" | "3" => Some(2),
+        _ => None,
+    }
+}
+
+/// Returns true if a phase identifier such as "AB" or "L12" denotes a
+/// phase-to-phase quantity.
+pub fn is_phase_to_phase(phase: &str) -> bool {
+    matches!(
+        phase.trim().to_uppercase().as_str(),
+        "AB" | "BC" | "CA" | "BA" | "CB" | "AC" | "RS" | "ST" | "TR" | "L12" | "L23" | "L31"
+    )
+}
+
+/// Returns the phase of a channel from its `phase` field, falling back to a
+/// name such as "IA", "VB", "U_L3" or "Ia" when the field is empty.
+pub fn channel_phase(channel: &SerializableAnalogChannel) -> Option<usize> {
+    if !channel.phase.trim().is_empty() {
+        return phase_number(&channel.phase);
+    }
+    let name = channel.name.trim();
+    let rest = name
+        .strip_prefix(['I', 'i', 'V', 'v', 'U', 'u'])?
+        .trim_start_matches(['_', ' ', '-']);
+    phase_number(rest)
+}
+
+/// Returns the positions in `channels` of the first A, B and C channels that
+/// satisfy `filter`, or `None` unless all three phases are present.
+pub fn three_phase_set(
+    channels: &[SerializableAnalogChannel],
+    filter: impl Fn(&SerializableAnalogChannel) -> bool,
+) -> Option<[usize; 3]> {
+    let mut set = [None; 3];
+    for (i, channel) in channels.iter().enumerate() {
+        if !filter(channel) {
+            continue;
+        }
+        if let Some(phase) = channel_phase(channel)
+            && set[phase].is_none()
+        {
+            set[phase] = Some(i);
+        }
+    }
+    Some([set[0]?, set[1]?, set[2]?])
+}
+
+/// The operator `a` = 1∠120° used by the symmetrical components.
+pub fn operator_a() -> Complex64 {
+    Complex64::from_polar(1.0, 120f64.to_radians())
+}
+
+/// Returns the zero, positive and negative sequence components of the phasors
+/// of phases A, B and C.
+pub fn sequence_components(phasors: [Complex64; 3]) -> [Complex64; 3] {
+    let a = operator_a();
+    let a2 = a * a;
+    let [pa, pb, pc] = phasors;
+    [
+        (pa + pb + pc) / 3.0,
+        (pa + a * pb + a2 * pc) / 3.0,
+        (pa + a2 * pb + a * pc) / 3.0,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::analog_channel;
+
+    #[test]
+    fn test_three_phase_set() {
+        let channels = vec![
+            analog_channel(1, "VAB", "kV", "AB", vec![]),
+            analog_channel(2, "U L1", "kV", "", vec![]),
+            analog_channel(3, "VB", "kV", "", vec![]),
+            analog_channel(4, "IC", "A", "C", vec![]),
+            analog_channel(5, "Line C", "kV", "CN", vec![]),
+        ];
+        assert_eq!(
+            three_phase_set(&channels, |c| c.is_voltage()),
+            Some([1, 2, 4])
+        );
+        assert_eq!(three_phase_set(&channels, |c| c.is_current()), None);
+
+        let balanced =
+            [0.0, -120.0, 120.0].map(|deg: f64| Complex64::from_polar(1.0, deg.to_radians()));
+        let [zero, positive, negative] = sequence_components(balanced);
+        assert!(zero.norm() < 1e-12 && negative.norm() < 1e-12);
+        assert!((positive.norm() - 1.0).abs() < 1e-12);
+    }
+}
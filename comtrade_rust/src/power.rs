@@ -0,0 +1,331 @@
+// comtrade_rust/src/power.rs
+// This file contains the instantaneous and fundamental power calculations per circuit, per phase and for three phases.
+// This file exists so generator protection and power swing studies get P, Q, S and power factor without exporting to a spreadsheet.
+// RELEVANT FILES: comtrade_rust/src/phases.rs, comtrade_rust/src/ratios.rs, comtrade_rust/src/series.rs, comtrade_rust/src/wiring.rs, comtrade_rust/src/lib.rs
+
+use num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+
+use crate::clipping::is_clipped;
+use crate::dsp::{cycle_windows, dft, nominal_frequency};
+use crate::phases::{channel_phase, is_phase_to_phase};
+use crate::series::DerivedSeries;
+use crate::{ComtradeInfo, SerializableAnalogChannel, WasmComtradeError};
+
+/// The spacing of the fundamental power points by default, in cycles.
+const DEFAULT_STEP_CYCLES: f64 = 0.25;
+/// Powers are reported in MW, Mvar and MVA.
+const MEGA: f64 = 1e6;
+const PHASE_NAMES: [&str; 3] = ["A", "B", "C"];
+
+/// Options for the power calculation. Every field is optional.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct PowerOptions {
+    /// Only the circuit whose `circuit_component_being_monitored` matches,
+    /// ignoring case. Defaults to every circuit.
+    pub circuit: Option<String>,
+    /// The spacing of the fundamental points in cycles. Defaults to 0.25.
+    pub step_cycles: Option<f64>,
+}
+
+/// The power flowing in one phase, or the sum of the three phases.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PowerFlow {
+    /// "A", "B", "C" or "ABC" for the three-phase sum.
+    pub phase: String,
+    /// The voltage channel(s) used.
+    pub voltage_channels: Vec<String>,
+    /// The current channel(s) used.
+    pub current_channels: Vec<String>,
+    /// v·i at every sample, in MW.
+    pub instantaneous: DerivedSeries,
+    /// The fundamental active power in MW, over one-cycle windows ending at each point.
+    pub active: DerivedSeries,
+    /// The fundamental reactive power in Mvar; positive when the current lags the voltage.
+    pub reactive: DerivedSeries,
+    /// The fundamental apparent power in MVA.
+    pub apparent: DerivedSeries,
+    /// P / S, signed like P. Omitted at points where S is zero.
+    pub power_factor: DerivedSeries,
+    /// False if a channel used is clipped somewhere in the recording.
+    pub reliable: bool,
+}
+
+/// The power of one circuit.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CircuitPower {
+    /// The `circuit_component_being_monitored` of the current channels;
+    /// empty when the CFG names none.
+    pub circuit: String,
+    /// One entry per phase with both a voltage and a current channel.
+    pub phases: Vec<PowerFlow>,
+    /// The sum of the three phases, when all three are present.
+    pub three_phase: Option<PowerFlow>,
+}
+
+/// The positions of a voltage and a current channel of the same phase.
+struct PhasePair {
+    phase: usize,
+    voltage: usize,
+    current: usize,
+}
+
+/// Returns the values of a channel in volts or amperes.
+fn si_values(channel: &SerializableAnalogChannel) -> Vec<f64> {
+    let scale = channel.si_scale();
+    channel.primary_values.iter().map(|v| v * scale).collect()
+}
+
+/// Returns the phase-to-neutral voltage channel of `phase`, preferring one
+/// that monitors `circuit`.
+pub(crate) fn phase_voltage(
+    channels: &[SerializableAnalogChannel],
+    circuit: &str,
+    phase: usize,
+) -> Option<usize> {
+    let candidates = || {
+        channels.iter().enumerate().filter(|(_, c)| {
+            c.is_voltage() && !is_phase_to_phase(&c.phase) && channel_phase(c) == Some(phase)
+        })
+    };
+    candidates()
+        .find(|(_, c)| circuit_key(c) == circuit)
+        .or_else(|| candidates().next())
+        .map(|(i, _)| i)
+}
+
+/// Returns the circuit a channel monitors, trimmed and lowercased for matching.
+pub(crate) fn circuit_key(channel: &SerializableAnalogChannel) -> String {
+    channel
+        .circuit_component_being_monitored
+        .trim()
+        .to_lowercase()
+}
+
+impl PowerFlow {
+    /// Creates a flow with empty series named after the current channels.
+    fn new(
+        phase: &str,
+        voltage_channels: Vec<String>,
+        current_channels: Vec<String>,
+        reliable: bool,
+    ) -> Self {
+        let label = format!("{} {}", current_channels.join("+"), phase);
+        PowerFlow {
+            phase: phase.to_string(),
+            voltage_channels,
+            current_channels,
+            instantaneous: DerivedSeries::new(format!("p ({})", label), "MW"),
+            active: DerivedSeries::new(format!("P ({})", label), "MW"),
+            reactive: DerivedSeries::new(format!("Q ({})", label), "Mvar"),
+            apparent: DerivedSeries::new(format!("S ({})", label), "MVA"),
+            power_factor: DerivedSeries::new(format!("PF ({})", label), ""),
+            reliable,
+        }
+    }
+
+    /// Fills the series from the instantaneous power at each sample and the
+    /// complex power of each window.
+    fn fill(
+        &mut self,
+        instantaneous: Vec<f64>,
+        powers: &[Complex64],
+        windows: &[(usize, usize)],
+        timestamps: &[f64],
+    ) {
+        self.instantaneous.times = timestamps[..instantaneous.len()].to_vec();
+        self.instantaneous.values = instantaneous;
+        for (&(_, end), power) in windows.iter().zip(powers) {
+            let time = timestamps[end - 1];
+            let apparent = power.norm();
+            self.active.push(time, power.re);
+            self.reactive.push(time, power.im);
+            self.apparent.push(time, apparent);
+            if apparent > 0.0 {
+                self.power_factor.push(time, power.re / apparent);
+            }
+        }
+    }
+}
+
+/// Computes the instantaneous and fundamental power of each circuit from
+/// its primary-scaled phase-to-neutral voltages and phase currents.
+///
+/// Current channels are grouped into circuits by
+/// `circuit_component_being_monitored`. Each phase current is paired with a
+/// voltage of the same phase on that circuit, or failing that with the first
+/// such voltage in the recording, e.g. a bus voltage shared by the feeders.
+pub fn calculate_power(
+    info: &ComtradeInfo,
+    options: &PowerOptions,
+) -> Result<Vec<CircuitPower>, WasmComtradeError> {
+    let frequency = nominal_frequency(info.frequency);
+    let step_cycles = options
+        .step_cycles
+        .filter(|s| *s > 0.0)
+        .unwrap_or(DEFAULT_STEP_CYCLES);
+    let wanted = options.circuit.as_deref().map(|c| c.trim().to_lowercase());
+    let channels = &info.analog_channels;
+
+    let mut circuits: Vec<String> = Vec::new();
+    for channel in channels.iter().filter(|c| c.is_current()) {
+        let key = circuit_key(channel);
+        if !circuits.contains(&key) && wanted.as_ref().is_none_or(|w| *w == key) {
+            circuits.push(key);
+        }
+    }
+
+    let mut results = Vec::new();
+    for circuit in circuits {
+        let pairs: Vec<PhasePair> = (0..3)
+            .filter_map(|phase| {
+                let current = channels.iter().position(|c| {
+                    c.is_current() && circuit_key(c) == circuit && channel_phase(c) == Some(phase)
+                })?;
+                let voltage = phase_voltage(channels, &circuit, phase)?;
+                Some(PhasePair {
+                    phase,
+                    voltage,
+                    current,
+                })
+            })
+            .collect();
+        if pairs.is_empty() {
+            continue;
+        }
+
+        let samples = pairs
+            .iter()
+            .flat_map(|p| [p.voltage, p.current])
+            .map(|i| channels[i].primary_values.len())
+            .min()
+            .unwrap_or(0)
+            .min(info.timestamps.len());
+        let windows = cycle_windows(&info.timestamps, samples, frequency, step_cycles);
+
+        let mut phases = Vec::new();
+        let mut total_instantaneous = vec![0.0; samples];
+        let mut total_powers = vec![Complex64::new(0.0, 0.0); windows.len()];
+        for pair in &pairs {
+            let v = si_values(&channels[pair.voltage]);
+            let i = si_values(&channels[pair.current]);
+            let instantaneous: Vec<f64> = (0..samples).map(|k| v[k] * i[k] / MEGA).collect();
+            let powers: Vec<Complex64> = windows
+                .iter()
+                .map(|&(start, end)| {
+                    dft(&v[start..end], 1) * dft(&i[start..end], 1).conj() / (2.0 * MEGA)
+                })
+                .collect();
+            for (total, p) in total_instantaneous.iter_mut().zip(&instantaneous) {
+                *total += p;
+            }
+            for (total, s) in total_powers.iter_mut().zip(&powers) {
+                *total += s;
+            }
+            let reliable = [pair.voltage, pair.current].iter().all(|&index| {
+                samples == 0 || !is_clipped(&info.clipped_intervals, index, 0, samples - 1)
+            });
+            let mut flow = PowerFlow::new(
+                PHASE_NAMES[pair.phase],
+                vec![channels[pair.voltage].name.clone()],
+                vec![channels[pair.current].name.clone()],
+                reliable,
+            );
+            flow.fill(instantaneous, &powers, &windows, &info.timestamps);
+            phases.push(flow);
+        }
+
+        let three_phase = (phases.len() == 3).then(|| {
+            let mut flow = PowerFlow::new(
+                "ABC",
+                phases
+                    .iter()
+                    .flat_map(|p| p.voltage_channels.clone())
+                    .collect(),
+                phases
+                    .iter()
+                    .flat_map(|p| p.current_channels.clone())
+                    .collect(),
+                phases.iter().all(|p| p.reliable),
+            );
+            flow.fill(
+                total_instantaneous,
+                &total_powers,
+                &windows,
+                &info.timestamps,
+            );
+            flow
+        });
+        let circuit = channels[pairs[0].current]
+            .circuit_component_being_monitored
+            .trim()
+            .to_string();
+        results.push(CircuitPower {
+            circuit,
+            phases,
+            three_phase,
+        });
+    }
+
+    if results.is_empty() {
+        return Err(WasmComtradeError::AnalysisError(
+            "No circuit has a phase-to-neutral voltage and a current channel of the same phase."
+                .to_string(),
+        ));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording, sine};
+
+    #[test]
+    fn test_calculate_power() {
+        let samples = 200;
+        let mut channels = Vec::new();
+        for (p, shift) in [("A", 0.0), ("B", -120.0), ("C", 120.0)] {
+            let v = sine(100.0, 50.0, shift, 1000.0, samples);
+            channels.push(analog_channel(1, &format!("V{}", p), "kV", p, v));
+        }
+        for (p, shift) in [("A", 0.0), ("B", -120.0), ("C", 120.0)] {
+            // Lagging the voltage by 30 degrees.
+            let i = sine(1.0, 50.0, shift - 30.0, 1000.0, samples);
+            let mut channel = analog_channel(1, &format!("I{}", p), "kA", p, i);
+            channel.circuit_component_being_monitored = "Line 1".to_string();
+            channels.push(channel);
+        }
+        let info = recording(1000.0, 50.0, channels, vec![]);
+
+        let circuits = calculate_power(&info, &PowerOptions::default()).unwrap();
+        assert_eq!(circuits.len(), 1);
+        assert_eq!(circuits[0].circuit, "Line 1");
+        assert_eq!(circuits[0].phases.len(), 3);
+
+        let phase_a = &circuits[0].phases[0];
+        assert_eq!(phase_a.voltage_channels, ["VA"]);
+        let expected_p = 50.0 * 30f64.to_radians().cos();
+        assert!((phase_a.active.values[0] - expected_p).abs() < 1e-6);
+        assert!((phase_a.reactive.values[0] - 25.0).abs() < 1e-6);
+        assert!((phase_a.power_factor.values[0] - 0.866).abs() < 1e-3);
+
+        let total = circuits[0].three_phase.as_ref().unwrap();
+        assert!((total.active.values[3] - 3.0 * expected_p).abs() < 1e-6);
+        // Balanced three-phase instantaneous power is constant.
+        assert!(
+            total
+                .instantaneous
+                .values
+                .iter()
+                .all(|p| (p - 3.0 * expected_p).abs() < 1e-6)
+        );
+
+        let options = PowerOptions {
+            circuit: Some("Line 2".to_string()),
+            ..Default::default()
+        };
+        assert!(calculate_power(&info, &options).is_err());
+    }
+}
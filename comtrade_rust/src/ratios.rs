@@ -0,0 +1,413 @@
+// comtrade_rust/src/ratios.rs
+// This file contains the validation, inference and override of the CT/VT ratios given by the primary and secondary factors.
+// This file exists because a wrong or missing ratio silently scales every primary or secondary value of a channel.
+// RELEVANT FILES: comtrade_rust/src/phases.rs, comtrade_rust/src/diagnostics.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::diagnostics::Diagnostic;
+use crate::phases::{is_phase_to_phase, three_phase_set};
+use crate::power::circuit_key;
+use crate::{ComtradeInfo, SerializableAnalogChannel, WasmComtradeError};
+
+/// The largest plausible CT ratio, e.g. 40000/1 on a generator.
+const MAX_CT_RATIO: f64 = 50_000.0;
+/// The largest plausible VT ratio, e.g. 1100 kV/110 V.
+const MAX_VT_RATIO: f64 = 20_000.0;
+/// The rated CT secondary currents in amperes.
+const CT_SECONDARIES: [f64; 2] = [1.0, 5.0];
+/// The range of rated VT secondary voltages in volts, from 100/√3 V
+/// phase-to-neutral up to 240 V.
+const VT_SECONDARY_RANGE: (f64, f64) = (50.0, 250.0);
+/// Two ratios closer than this, relatively, are the same ratio.
+const RATIO_TOLERANCE: f64 = 1e-6;
+
+/// Replacement primary and secondary factors for one analog channel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RatioOverride {
+    /// The position of the channel in `analog_channels`.
+    pub channel: usize,
+    /// The new primary factor, e.g. 1200 for a 1200/1 CT.
+    pub primary_factor: f64,
+    /// The new secondary factor, e.g. 1 for a 1200/1 CT.
+    pub secondary_factor: f64,
+    /// Which values the recorded data are, "Primary" or "Secondary". Keeps the
+    /// channel's own scaling mode when `None`.
+    #[serde(default)]
+    pub scaling_mode: Option<String>,
+}
+
+/// The findings of the ratio checks.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RatioCheck {
+    /// The invalid, implausible and inconsistent ratios.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The ratios inferred for the suspect channels, ready to pass to
+    /// `override_ratios` once confirmed.
+    pub inferred: Vec<RatioOverride>,
+}
+
+/// Returns the primary to secondary ratio of a channel, or `None` if either
+/// factor is not a positive number.
+fn ratio(channel: &SerializableAnalogChannel) -> Option<f64> {
+    let (primary, secondary) = (channel.primary_factor, channel.secondary_factor);
+    let valid = |f: f64| f.is_finite() && f > 0.0;
+    (valid(primary) && valid(secondary)).then(|| primary / secondary)
+}
+
+fn same_ratio(a: f64, b: f64) -> bool {
+    (a - b).abs() <= RATIO_TOLERANCE * a.abs().max(b.abs())
+}
+
+/// Formats the factors of a channel as "primary/secondary".
+fn factors(channel: &SerializableAnalogChannel) -> String {
+    format!("{}/{}", channel.primary_factor, channel.secondary_factor)
+}
+
+fn infer(inferred: &mut Vec<RatioOverride>, channel: usize, primary: f64, secondary: f64) {
+    if inferred.iter().all(|o| o.channel != channel) {
+        inferred.push(RatioOverride {
+            channel,
+            primary_factor: primary,
+            secondary_factor: secondary,
+            scaling_mode: None,
+        });
+    }
+}
+
+/// Checks the factors of one channel on their own: both positive, primary
+/// above secondary, and a ratio and secondary rating usual for a CT or VT.
+fn check_channel(index: usize, channel: &SerializableAnalogChannel, check: &mut RatioCheck) {
+    let Some(ratio) = ratio(channel) else {
+        check.diagnostics.push(
+            Diagnostic::error(
+                "ratio-invalid",
+                format!(
+                    "{} has primary and secondary factors {}; both must be positive, so its {} \
+                     values cannot be trusted.",
+                    channel.name,
+                    factors(channel),
+                    if channel.scaling_mode.eq_ignore_ascii_case("secondary") {
+                        "primary"
+                    } else {
+                        "secondary"
+                    }
+                ),
+            )
+            .on_channel(&channel.name),
+        );
+        return;
+    };
+    // A 1/1 ratio means the factors were not given, or the channel is not
+    // measured through a CT or VT.
+    if same_ratio(ratio, 1.0) || !(channel.is_current() || channel.is_voltage()) {
+        return;
+    }
+
+    if ratio < 1.0 {
+        check.diagnostics.push(
+            Diagnostic::warning(
+                "ratio-implausible",
+                format!(
+                    "{} has a primary factor below its secondary factor ({}); the factors appear \
+                     swapped.",
+                    channel.name,
+                    factors(channel)
+                ),
+            )
+            .on_channel(&channel.name),
+        );
+        infer(
+            &mut check.inferred,
+            index,
+            channel.secondary_factor,
+            channel.primary_factor,
+        );
+        return;
+    }
+
+    let (kind, largest) = if channel.is_current() {
+        ("CT", MAX_CT_RATIO)
+    } else {
+        ("VT", MAX_VT_RATIO)
+    };
+    if ratio > largest {
+        check.diagnostics.push(
+            Diagnostic::warning(
+                "ratio-implausible",
+                format!(
+                    "{} has a {} ratio of {:.0} ({}), above the {:.0} of any usual {}.",
+                    channel.name,
+                    kind,
+                    ratio,
+                    factors(channel),
+                    largest,
+                    kind
+                ),
+            )
+            .on_channel(&channel.name),
+        );
+    }
+
+    let secondary = channel.secondary_factor * channel.si_scale();
+    let rated = if channel.is_current() {
+        CT_SECONDARIES.iter().any(|&s| same_ratio(secondary, s))
+    } else {
+        (VT_SECONDARY_RANGE.0..=VT_SECONDARY_RANGE.1).contains(&secondary)
+    };
+    if !rated {
+        check.diagnostics.push(
+            Diagnostic::info(
+                "ratio-implausible",
+                format!(
+                    "{} has a secondary factor of {} {}, which is not a usual {} secondary rating.",
+                    channel.name, channel.secondary_factor, channel.units, kind
+                ),
+            )
+            .on_channel(&channel.name),
+        );
+    }
+}
+
+/// Checks that the three phases of a set share one ratio, and infers the
+/// ratio of a phase that differs from the other two.
+fn check_set(channels: &[SerializableAnalogChannel], set: [usize; 3], check: &mut RatioCheck) {
+    let ratios = set.map(|i| ratio(&channels[i]));
+    let agree = |a: usize, b: usize| match (ratios[a], ratios[b]) {
+        (Some(x), Some(y)) => same_ratio(x, y),
+        _ => false,
+    };
+    if agree(0, 1) && agree(1, 2) {
+        return;
+    }
+    let names = set.map(|i| channels[i].name.as_str()).join(", ");
+    let odd = [(2, 0, 1), (0, 1, 2), (1, 2, 0)]
+        .into_iter()
+        .find(|&(_, a, b)| agree(a, b));
+    let Some((odd, peer, _)) = odd else {
+        check.diagnostics.push(
+            Diagnostic::warning(
+                "ratio-inconsistent",
+                format!(
+                    "The phases of {} have different ratios ({}); check the factors of each.",
+                    names,
+                    set.map(|i| factors(&channels[i])).join(", ")
+                ),
+            )
+            .on_channel(&channels[set[0]].name),
+        );
+        return;
+    };
+
+    let (channel, peer) = (&channels[set[odd]], &channels[set[peer]]);
+    check.diagnostics.push(
+        Diagnostic::warning(
+            "ratio-inconsistent",
+            format!(
+                "{} has factors {} while the other phases of {} have {}, which it probably shares.",
+                channel.name,
+                factors(channel),
+                names,
+                factors(peer)
+            ),
+        )
+        .on_channel(&channel.name),
+    );
+    infer(
+        &mut check.inferred,
+        set[odd],
+        peer.primary_factor,
+        peer.secondary_factor,
+    );
+}
+
+/// Checks the CT and VT ratios of the analog channels.
+///
+/// Each channel's factors must both be positive, with the primary above the
+/// secondary, a ratio below what any CT or VT has, and a usual secondary
+/// rating (1 A or 5 A, or 50 to 250 V). The three phases of each voltage and
+/// current set, grouped by `circuit_component_being_monitored`, must share
+/// one ratio. Channels with a 1/1 ratio are taken as unscaled, unless the
+/// other phases of their set disagree.
+///
+/// Where the right ratio can be told, from the other phases of the set or by
+/// swapping the factors, it is returned in `inferred`.
+pub fn check_ratios(channels: &[SerializableAnalogChannel]) -> RatioCheck {
+    let mut check = RatioCheck {
+        diagnostics: Vec::new(),
+        inferred: Vec::new(),
+    };
+
+    let mut circuits: Vec<String> = Vec::new();
+    for channel in channels {
+        let key = circuit_key(channel);
+        if !circuits.contains(&key) {
+            circuits.push(key);
+        }
+    }
+    for circuit in &circuits {
+        let voltages = three_phase_set(channels, |c| {
+            c.is_voltage() && !is_phase_to_phase(&c.phase) && circuit_key(c) == *circuit
+        });
+        let currents = three_phase_set(channels, |c| c.is_current() && circuit_key(c) == *circuit);
+        for set in [voltages, currents].into_iter().flatten() {
+            check_set(channels, set, &mut check);
+        }
+    }
+
+    for (index, channel) in channels.iter().enumerate() {
+        check_channel(index, channel, &mut check);
+    }
+    check
+}
+
+/// Reports the samples of a channel whose primary or secondary scaling
+/// failed, where the raw values were kept instead. `scale` names which
+/// values, "primary" or "secondary".
+pub fn scaling_failure(channel: &str, scale: &str, values: &[Option<f64>]) -> Option<Diagnostic> {
+    let first = values.iter().position(Option::is_none)?;
+    let failed = values.iter().filter(|v| v.is_none()).count();
+    Some(
+        Diagnostic::error(
+            "scaling-failed",
+            format!(
+                "{} of {} {} values of {} could not be scaled and hold the raw data instead; check \
+                 the channel's multiplier and primary and secondary factors.",
+                failed,
+                values.len(),
+                scale,
+                channel
+            ),
+        )
+        .on_channel(channel)
+        .at_sample(first),
+    )
+}
+
+/// Replaces the primary and secondary factors (and optionally the scaling
+/// mode) of channels and recomputes their primary and secondary values from
+/// the recorded values.
+///
+/// The ratio diagnostics of the recording are replaced with those of the new
+/// factors.
+pub fn override_ratios(
+    info: &mut ComtradeInfo,
+    overrides: &[RatioOverride],
+) -> Result<(), WasmComtradeError> {
+    for o in overrides {
+        let count = info.analog_channels.len();
+        let channel = info.analog_channels.get_mut(o.channel).ok_or_else(|| {
+            WasmComtradeError::AnalysisError(format!(
+                "Analog channel {} does not exist; the recording has {}.",
+                o.channel, count
+            ))
+        })?;
+        let valid = |f: f64| f.is_finite() && f > 0.0;
+        if !valid(o.primary_factor) || !valid(o.secondary_factor) {
+            return Err(WasmComtradeError::AnalysisError(format!(
+                "The factors {}/{} for '{}' must both be positive.",
+                o.primary_factor, o.secondary_factor, channel.name
+            )));
+        }
+        if let Some(mode) = &o.scaling_mode {
+            channel.scaling_mode = match mode.trim().to_lowercase().as_str() {
+                "primary" | "p" => "Primary".to_string(),
+                "secondary" | "s" => "Secondary".to_string(),
+                _ => {
+                    return Err(WasmComtradeError::AnalysisError(format!(
+                        "Unknown scaling mode '{}' for '{}'; use \"Primary\" or \"Secondary\".",
+                        mode, channel.name
+                    )));
+                }
+            };
+        }
+
+        channel.primary_factor = o.primary_factor;
+        channel.secondary_factor = o.secondary_factor;
+        let ratio = o.primary_factor / o.secondary_factor;
+        let values = &channel.values;
+        if channel.scaling_mode.eq_ignore_ascii_case("secondary") {
+            channel.secondary_values = values.clone();
+            channel.primary_values = values.iter().map(|v| v * ratio).collect();
+        } else {
+            channel.primary_values = values.clone();
+            channel.secondary_values = values.iter().map(|v| v / ratio).collect();
+        }
+    }
+
+    info.diagnostics.retain(|d| !d.rule.starts_with("ratio-"));
+    let check = check_ratios(&info.analog_channels);
+    info.diagnostics.extend(check.diagnostics);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording};
+
+    #[test]
+    fn test_check_and_override_ratios() {
+        let channel = |name: &str, units: &str, phase: &str, primary: f64, secondary: f64| {
+            let mut channel = analog_channel(1, name, units, phase, vec![1.0, -2.0]);
+            channel.scaling_mode = "Secondary".to_string();
+            channel.primary_factor = primary;
+            channel.secondary_factor = secondary;
+            channel
+        };
+        let channels = vec![
+            channel("VA", "V", "A", 132_000.0, 110.0),
+            channel("VB", "V", "B", 132_000.0, 110.0),
+            channel("VC", "V", "C", 132_000.0, 110.0),
+            channel("IA", "A", "A", 1200.0, 1.0),
+            channel("IB", "A", "B", 1200.0, 1.0),
+            // Factors missing on one phase.
+            channel("IC", "A", "C", 1.0, 1.0),
+            channel("IN", "A", "N", 1.0, 1200.0),
+            channel("VX", "V", "", 132_000.0, 0.0),
+        ];
+        let check = check_ratios(&channels);
+        let rules: Vec<(&str, &str)> = check
+            .diagnostics
+            .iter()
+            .map(|d| (d.rule.as_str(), d.channel.as_deref().unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                ("ratio-inconsistent", "IC"),
+                ("ratio-implausible", "IN"),
+                ("ratio-invalid", "VX"),
+            ]
+        );
+        let inferred = |channel: usize, primary: f64, secondary: f64| RatioOverride {
+            channel,
+            primary_factor: primary,
+            secondary_factor: secondary,
+            scaling_mode: None,
+        };
+        assert_eq!(
+            check.inferred,
+            [inferred(5, 1200.0, 1.0), inferred(6, 1200.0, 1.0)]
+        );
+
+        let mut info = recording(1000.0, 50.0, channels, vec![]);
+        info.diagnostics = check.diagnostics;
+        override_ratios(&mut info, &check.inferred).unwrap();
+        assert_eq!(info.analog_channels[5].primary_values, [1200.0, -2400.0]);
+        assert_eq!(info.analog_channels[5].secondary_values, [1.0, -2.0]);
+        assert_eq!(info.diagnostics.len(), 1);
+        assert_eq!(info.diagnostics[0].rule, "ratio-invalid");
+
+        // Recorded as primary values, the secondary values are divided instead.
+        let mut primary = inferred(0, 1000.0, 100.0);
+        primary.scaling_mode = Some("primary".to_string());
+        override_ratios(&mut info, &[primary]).unwrap();
+        assert_eq!(info.analog_channels[0].secondary_values, [0.1, -0.2]);
+        assert!(override_ratios(&mut info, &[inferred(8, 1.0, 1.0)]).is_err());
+        assert!(
+            scaling_failure("VA", "primary", &[Some(1.0), None])
+                .is_some_and(|d| d.sample == Some(1))
+        );
+    }
+}
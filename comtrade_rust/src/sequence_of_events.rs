@@ -0,0 +1,217 @@
+// comtrade_rust/src/sequence_of_events.rs
+// This file contains the sequence-of-events log built from the state changes of the digital channels.
+// This file exists so every change of state can be reviewed, filtered and exported in chronological order.
+// RELEVANT FILES: comtrade_rust/src/lib.rs
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{ComtradeInfo, WasmComtradeError};
+
+/// Options for the sequence-of-events log. Every field is optional.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SoeOptions {
+    /// Only channels whose name matches one of these patterns are included.
+    /// Patterns are case-insensitive and may use `*` and `?` wildcards,
+    /// e.g. "52*" or "*trip*". Empty includes every channel.
+    pub include: Vec<String>,
+    /// Channels whose name matches one of these patterns are left out.
+    pub exclude: Vec<String>,
+    /// Classify each change as an alarm (leaving the channel's normal state)
+    /// or a return (back to it), using the CFG `normal_status_value`.
+    pub use_normal_state: bool,
+}
+
+/// Whether a change of state leaves or returns to the normal state.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SoeEventKind {
+    Alarm,
+    Return,
+}
+
+/// One change of state of a digital channel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SoeEntry {
+    /// The 1-based position of the entry in the log.
+    pub sequence: usize,
+    /// The sample at which the new state was first recorded.
+    pub sample: usize,
+    /// The absolute time as Unix seconds.
+    pub time: f64,
+    /// The absolute time formatted like `start_time`, with microseconds.
+    pub timestamp: String,
+    /// Seconds since the trigger; negative before it.
+    pub relative_time: f64,
+    /// The position of the channel in `digital_channels`.
+    pub channel_index: usize,
+    /// The channel name.
+    pub channel: String,
+    /// The new state.
+    pub state: u8,
+    /// The state before the change.
+    pub previous_state: u8,
+    /// Alarm or return, when `use_normal_state` was set.
+    pub kind: Option<SoeEventKind>,
+}
+
+/// Compiles a wildcard pattern into a case-insensitive regex matching the whole name.
+fn wildcard(pattern: &str) -> Regex {
+    let escaped = regex::escape(pattern.trim())
+        .replace(r"\*", ".*")
+        .replace(r"\?", ".");
+    RegexBuilder::new(&format!("^{}$", escaped))
+        .case_insensitive(true)
+        .build()
+        .expect("escaped wildcard patterns are valid regular expressions")
+}
+
+/// Formats Unix seconds like `NaiveDateTime`'s `Display`, with microseconds.
+pub(crate) fn format_timestamp(time: f64) -> String {
+    chrono::DateTime::from_timestamp_micros((time * 1_000_000.0).round() as i64)
+        .map(|t| t.naive_utc().format("%Y-%m-%d %H:%M:%S%.6f").to_string())
+        .unwrap_or_default()
+}
+
+/// Builds the chronologically ordered log of state changes across the
+/// digital channels. Changes at the same sample are ordered by channel.
+pub fn sequence_of_events(info: &ComtradeInfo, options: &SoeOptions) -> Vec<SoeEntry> {
+    let include: Vec<Regex> = options.include.iter().map(|p| wildcard(p)).collect();
+    let exclude: Vec<Regex> = options.exclude.iter().map(|p| wildcard(p)).collect();
+    let selected = |name: &str| {
+        (include.is_empty() || include.iter().any(|r| r.is_match(name)))
+            && !exclude.iter().any(|r| r.is_match(name))
+    };
+
+    let mut changes: Vec<(usize, usize, u8, u8)> = Vec::new();
+    for (channel_index, channel) in info.digital_channels.iter().enumerate() {
+        if !selected(&channel.name) {
+            continue;
+        }
+        for (sample, pair) in channel.values.windows(2).enumerate() {
+            if pair[0] != pair[1] {
+                changes.push((sample + 1, channel_index, pair[1], pair[0]));
+            }
+        }
+    }
+    changes.sort_by_key(|&(sample, channel_index, _, _)| (sample, channel_index));
+
+    changes
+        .into_iter()
+        .filter(|&(sample, ..)| sample < info.timestamps.len())
+        .enumerate()
+        .map(|(i, (sample, channel_index, state, previous_state))| {
+            let channel = &info.digital_channels[channel_index];
+            let time = info.timestamps[sample];
+            let kind = if state == channel.initial_value {
+                SoeEventKind::Return
+            } else {
+                SoeEventKind::Alarm
+            };
+            SoeEntry {
+                sequence: i + 1,
+                sample,
+                time,
+                timestamp: format_timestamp(time),
+                relative_time: time - info.trigger_timestamp,
+                channel_index,
+                channel: channel.name.clone(),
+                state,
+                previous_state,
+                kind: options.use_normal_state.then_some(kind),
+            }
+        })
+        .collect()
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes the log as CSV with a header row.
+pub fn soe_to_csv(entries: &[SoeEntry]) -> String {
+    let mut csv = String::from(
+        "sequence,timestamp,unix_time,relative_time_s,channel_index,channel,previous_state,state,kind\n",
+    );
+    for entry in entries {
+        let kind = match entry.kind {
+            Some(SoeEventKind::Alarm) => "alarm",
+            Some(SoeEventKind::Return) => "return",
+            None => "",
+        };
+        csv.push_str(&format!(
+            "{},{},{:.6},{:.6},{},{},{},{},{}\n",
+            entry.sequence,
+            entry.timestamp,
+            entry.time,
+            entry.relative_time,
+            entry.channel_index,
+            csv_field(&entry.channel),
+            entry.previous_state,
+            entry.state,
+            kind
+        ));
+    }
+    csv
+}
+
+/// Writes the log as a JSON array.
+pub fn soe_to_json(entries: &[SoeEntry]) -> Result<String, WasmComtradeError> {
+    serde_json::to_string_pretty(entries)
+        .map_err(|e| WasmComtradeError::SerializationError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, digital_channel, recording};
+
+    #[test]
+    fn test_sequence_of_events() {
+        let analog = analog_channel(1, "VA", "V", "A", vec![0.0; 6]);
+        let mut trip = digital_channel(1, "TRIP, main", 0);
+        trip.values = vec![0, 0, 1, 1, 0, 0];
+        let mut breaker = digital_channel(2, "52A", 1);
+        breaker.values = vec![1, 1, 1, 0, 0, 0];
+        let mut info = recording(1000.0, 50.0, vec![analog], vec![trip, breaker]);
+        info.trigger_timestamp = 0.002;
+
+        let options = SoeOptions {
+            use_normal_state: true,
+            ..Default::default()
+        };
+        let entries = sequence_of_events(&info, &options);
+        let order: Vec<(usize, &str)> = entries
+            .iter()
+            .map(|e| (e.sample, e.channel.as_str()))
+            .collect();
+        assert_eq!(order, [(2, "TRIP, main"), (3, "52A"), (4, "TRIP, main")]);
+        assert_eq!(entries[0].relative_time, 0.0);
+        assert_eq!(entries[1].kind, Some(SoeEventKind::Alarm));
+        assert_eq!(entries[2].kind, Some(SoeEventKind::Return));
+        assert_eq!(entries[0].timestamp, "1970-01-01 00:00:00.002000");
+
+        let csv = soe_to_csv(&entries);
+        assert!(
+            csv.lines()
+                .nth(1)
+                .unwrap()
+                .contains(",\"TRIP, main\",0,1,alarm")
+        );
+
+        let options = SoeOptions {
+            include: vec!["52*".to_string()],
+            ..Default::default()
+        };
+        let entries = sequence_of_events(&info, &options);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].sequence, 1);
+        assert_eq!(entries[0].kind, None);
+    }
+}
@@ -0,0 +1,36 @@
+// comtrade_rust/src/series.rs
+// This file contains the derived time series type returned by the tracking analyses.
+// This file exists so quantities computed over time, such as measured frequency, plot like channels in the frontend.
+// RELEVANT FILES: comtrade_rust/src/frequency.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+/// A quantity computed over time from one or more channels. Its points need
+/// not coincide with the samples of the recording.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DerivedSeries {
+    /// A descriptive name, e.g. "Frequency (VA)".
+    pub name: String,
+    /// The units of `values`, e.g. "Hz".
+    pub units: String,
+    /// The time of each point as Unix seconds.
+    pub times: Vec<f64>,
+    /// The value at each point.
+    pub values: Vec<f64>,
+}
+
+impl DerivedSeries {
+    pub fn new(name: impl Into<String>, units: &str) -> Self {
+        Self {
+            name: name.into(),
+            units: units.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Appends a point.
+    pub fn push(&mut self, time: f64, value: f64) {
+        self.times.push(time);
+        self.values.push(value);
+    }
+}
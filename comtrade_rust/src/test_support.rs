@@ -0,0 +1,93 @@
+// comtrade_rust/src/test_support.rs
+// This file contains helpers for building recordings in unit tests.
+// This file exists so analysis tests can construct a ComtradeInfo without parsing a COMTRADE file.
+// RELEVANT FILES: comtrade_rust/src/lib.rs
+
+use crate::{ComtradeInfo, SerializableAnalogChannel, SerializableDigitalChannel};
+
+/// Builds an analog channel with unity scaling, so values, primary values and
+/// secondary values are identical.
+pub fn analog_channel(
+    index: u32,
+    name: &str,
+    units: &str,
+    phase: &str,
+    values: Vec<f64>,
+) -> SerializableAnalogChannel {
+    SerializableAnalogChannel {
+        index,
+        name: name.to_string(),
+        units: units.to_string(),
+        min_value: -32767.0,
+        max_value: 32767.0,
+        multiplier: 1.0,
+        phase: phase.to_string(),
+        primary_values: values.clone(),
+        secondary_values: values.clone(),
+        values,
+        scaling_mode: "Primary".to_string(),
+        primary_factor: 1.0,
+        secondary_factor: 1.0,
+        ..Default::default()
+    }
+}
+
+/// Builds a digital channel with the given name and normal state and no samples.
+pub fn digital_channel(index: u32, name: &str, initial_value: u8) -> SerializableDigitalChannel {
+    SerializableDigitalChannel {
+        index,
+        name: name.to_string(),
+        initial_value,
+        ..Default::default()
+    }
+}
+
+/// Builds a recording sampled at `rate_hz` starting at t = 0 with the given
+/// channels. The trigger is placed at the first sample.
+pub fn recording(
+    rate_hz: f64,
+    frequency: f64,
+    analog_channels: Vec<SerializableAnalogChannel>,
+    digital_channels: Vec<SerializableDigitalChannel>,
+) -> ComtradeInfo {
+    let samples = analog_channels.first().map_or(0, |c| c.values.len());
+    let timestamps: Vec<f64> = (0..samples).map(|i| i as f64 / rate_hz).collect();
+    let analog_channels = analog_channels
+        .into_iter()
+        .map(|mut channel| {
+            channel.skew_timestamps = timestamps.clone();
+            channel
+        })
+        .collect();
+
+    let mut info = ComtradeInfo {
+        frequency,
+        data_format: "ASCII".to_string(),
+        analog_channels,
+        digital_channels,
+        timestamps,
+        ..Default::default()
+    };
+    info.config.line_frequency = frequency;
+    info.config.sampling_rates = vec![crate::SerializableSamplingRate {
+        rate_hz,
+        end_sample_number: samples as u32,
+    }];
+    info
+}
+
+/// Generates `samples` points of `amplitude * sin(2π f t + phase)` sampled at `rate_hz`.
+pub fn sine(
+    amplitude: f64,
+    frequency: f64,
+    phase_deg: f64,
+    rate_hz: f64,
+    samples: usize,
+) -> Vec<f64> {
+    (0..samples)
+        .map(|i| {
+            let t = i as f64 / rate_hz;
+            amplitude * (2.0 * std::f64::consts::PI * frequency * t + phase_deg.to_radians()).sin()
+        })
+        .collect()
+}
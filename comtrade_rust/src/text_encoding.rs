@@ -0,0 +1,195 @@
+// comtrade_rust/src/text_encoding.rs
+// This file contains automatic text encoding detection for the CFG, HDR and INF files.
+// This file exists because relays write station and channel names in Latin-1, Windows-1252, GBK or UTF-8 without declaring which.
+// RELEVANT FILES: comtrade_rust/src/lib.rs, app/src/lib/components/Upload.svelte
+
+use encoding_rs::{Encoding, GBK, UTF_8, WINDOWS_1252};
+use serde::{Deserialize, Serialize};
+
+/// The encoding used to decode one of the text files of a recording.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DetectedEncoding {
+    /// The file the encoding applies to ("CFG", "HDR" or "INF").
+    pub file: String,
+    /// The WHATWG name of the encoding (e.g., "UTF-8", "windows-1252", "GBK").
+    pub encoding: String,
+    /// How sure the detector is, from 0.0 to 1.0. Always 1.0 for user-supplied encodings.
+    pub confidence: f64,
+    /// How the encoding was chosen: "user", "bom", "utf-8", "ascii" or "heuristic".
+    pub method: String,
+}
+
+/// Single-byte and multi-byte candidates tried when the bytes are not valid UTF-8.
+/// `encoding_rs` maps the "latin1" and "iso-8859-1" labels to windows-1252.
+const CANDIDATES: [&Encoding; 2] = [WINDOWS_1252, GBK];
+
+/// Scores Windows-1252 text by how much its non-ASCII characters look like
+/// accented letters inside words, as in "Bäck" or "Umspannwerk Süd".
+fn score_latin(text: &str) -> f64 {
+    let chars: Vec<char> = text.chars().collect();
+    let mut score = 0.0;
+    let mut count = 0;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii() {
+            continue;
+        }
+        count += 1;
+
+        let neighbour_is_word = |j: Option<usize>| {
+            j.and_then(|j| chars.get(j))
+                .is_some_and(|n| n.is_ascii_alphabetic() || n.is_whitespace())
+        };
+        let in_word = neighbour_is_word(i.checked_sub(1)) || neighbour_is_word(Some(i + 1));
+
+        score += match (c.is_alphabetic(), in_word) {
+            (true, true) => 1.0,
+            (true, false) => 0.5,
+            _ => -1.0,
+        };
+    }
+
+    if count == 0 {
+        0.0
+    } else {
+        score / count as f64
+    }
+}
+
+/// Scores GBK text by how many of its non-ASCII characters are common
+/// (GB2312) hanzi or CJK punctuation.
+fn score_gbk(text: &str, bytes: &[u8]) -> f64 {
+    let mut score = 0.0;
+    let mut count = 0;
+
+    for c in text.chars().filter(|c| !c.is_ascii()) {
+        count += 1;
+        score += match c as u32 {
+            0x4E00..=0x9FFF => 1.0,
+            0x3000..=0x303F | 0xFF00..=0xFFEF => 0.5,
+            _ => -1.0,
+        };
+    }
+
+    // Two-byte sequences whose trail byte is printable ASCII are valid GBK but
+    // are far more often a Latin letter followed by plain text.
+    let ascii_trails = bytes
+        .windows(2)
+        .filter(|w| w[0] >= 0x81 && w[1].is_ascii_alphanumeric())
+        .count();
+    score -= 0.8 * ascii_trails as f64;
+
+    if count == 0 {
+        0.0
+    } else {
+        score / count as f64
+    }
+}
+
+/// Detects the text encoding of `bytes`.
+///
+/// Returns the encoding, a confidence between 0.0 and 1.0, and the method used.
+pub fn detect_encoding(bytes: &[u8]) -> (&'static Encoding, f64, &'static str) {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return (encoding, 1.0, "bom");
+    }
+
+    if bytes.is_ascii() {
+        return (UTF_8, 1.0, "ascii");
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return (UTF_8, 0.99, "utf-8");
+    }
+
+    let mut scored: Vec<(&'static Encoding, f64)> = CANDIDATES
+        .iter()
+        .filter_map(|&encoding| {
+            let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+            if had_errors {
+                return None;
+            }
+            let score = if encoding == GBK {
+                score_gbk(&text, bytes)
+            } else {
+                score_latin(&text)
+            };
+            Some((encoding, score))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    match scored.first() {
+        Some(&(encoding, score)) => {
+            let runner_up = scored.get(1).map_or(0.0, |s| s.1.max(0.0));
+            let confidence = (0.5 + 0.5 * (score - runner_up)).clamp(0.0, 0.95);
+            (encoding, confidence, "heuristic")
+        }
+        None => (WINDOWS_1252, 0.0, "heuristic"),
+    }
+}
+
+/// Decodes one text file of a recording.
+///
+/// Uses `encoding` when the user chose one, otherwise detects it. Pushes a
+/// warning when the decoder had to insert replacement characters.
+pub fn decode_text(
+    bytes: &[u8],
+    encoding: Option<&'static Encoding>,
+    file: &str,
+    warnings: &mut Vec<String>,
+) -> (String, DetectedEncoding) {
+    let (encoding, confidence, method) = match encoding {
+        Some(encoding) => (encoding, 1.0, "user"),
+        None => detect_encoding(bytes),
+    };
+
+    let (text, _, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        let replacements = text
+            .chars()
+            .filter(|&c| c == char::REPLACEMENT_CHARACTER)
+            .count();
+        warnings.push(format!(
+            "Decoding the {} file as {} produced {} replacement character(s); names may be garbled. Try selecting the encoding manually.",
+            file,
+            encoding.name(),
+            replacements
+        ));
+    }
+
+    let detected = DetectedEncoding {
+        file: file.to_string(),
+        encoding: encoding.name().to_string(),
+        confidence,
+        method: method.to_string(),
+    };
+
+    (text.into_owned(), detected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!(detect_encoding(b"Station,Relay,1999").2, "ascii");
+        assert_eq!(detect_encoding("Bäck,Relä,1999".as_bytes()).0, UTF_8);
+
+        let (latin, _, _) = WINDOWS_1252.encode("Umspannwerk Süd,Bäckström,1999");
+        assert_eq!(detect_encoding(&latin).0, WINDOWS_1252);
+
+        let (gbk, _, _) = GBK.encode("变电站,线路保护,1999");
+        assert_eq!(detect_encoding(&gbk).0, GBK);
+    }
+
+    #[test]
+    fn test_decode_text_reports_replacements() {
+        let mut warnings = Vec::new();
+        let (text, detected) = decode_text(b"A\xffB", Some(UTF_8), "CFG", &mut warnings);
+        assert_eq!(text, "A\u{FFFD}B");
+        assert_eq!(detected.method, "user");
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -0,0 +1,1002 @@
+// comtrade_rust/src/validation.rs
+// This file contains the structural validator that checks a recording against IEEE C37.111.
+// This file exists so users can return non-compliant files to vendors with a report naming each rule and location.
+// RELEVANT FILES: comtrade_rust/src/diagnostics.rs, comtrade_rust/src/lib.rs
+
+use crate::diagnostics::Diagnostic;
+use crate::{ComtradeInfo, SerializableAnalogChannel};
+
+/// Phase identifiers seen in practice. Others are reported as informational.
+const KNOWN_PHASES: [&str; 26] = [
+    "", "A", "B", "C", "N", "G", "E", "AB", "BC", "CA", "BA", "CB", "AC", "AN", "BN", "CN", "R",
+    "S", "T", "RS", "ST", "TR", "L1", "L2", "L3", "0",
+];
+
+/// Unit strings from the examples in the standard and common vendor usage.
+const KNOWN_UNITS: [&str; 30] = [
+    "V", "KV", "MV", "A", "KA", "MA", "W", "KW", "MW", "VAR", "KVAR", "MVAR", "VA", "KVA", "MVA",
+    "HZ", "OHM", "OHMS", "Ω", "S", "MS", "%", "PU", "DEG", "°", "RAD", "C", "°C", "BAR", "NONE",
+];
+
+/// A single CFG line split into comma-separated fields.
+struct CfgLine<'a> {
+    number: usize,
+    fields: Vec<&'a str>,
+}
+
+impl<'a> CfgLine<'a> {
+    fn field(&self, index: usize) -> Option<&'a str> {
+        self.fields.get(index).map(|f| f.trim())
+    }
+
+    fn value(&self, index: usize) -> &'a str {
+        self.field(index).unwrap_or_default()
+    }
+}
+
+/// Checks the length of a CFG field against the maximum allowed by the standard.
+fn check_length(
+    diagnostics: &mut Vec<Diagnostic>,
+    line: &CfgLine,
+    field: &str,
+    value: &str,
+    max_len: usize,
+) {
+    let len = value.chars().count();
+    if len > max_len {
+        diagnostics.push(
+            Diagnostic::error(
+                "cfg-field-length",
+                format!(
+                    "Field '{}' is {} characters long; the maximum is {}.",
+                    field, len, max_len
+                ),
+            )
+            .at_line("CFG", line.number)
+            .in_field(field),
+        );
+    }
+}
+
+/// Checks that a free-text field contains only characters the revision allows.
+fn check_characters(
+    diagnostics: &mut Vec<Diagnostic>,
+    line: &CfgLine,
+    field: &str,
+    value: &str,
+    revision: u16,
+) {
+    if value.chars().any(|c| c.is_control()) {
+        diagnostics.push(
+            Diagnostic::error(
+                "cfg-allowed-characters",
+                format!("Field '{}' contains control characters.", field),
+            )
+            .at_line("CFG", line.number)
+            .in_field(field),
+        );
+    } else if revision < 2013 && !value.is_ascii() {
+        diagnostics.push(
+            Diagnostic::warning(
+                "cfg-allowed-characters",
+                format!(
+                    "Field '{}' contains non-ASCII characters, which are only allowed from the 2013 revision.",
+                    field
+                ),
+            )
+            .at_line("CFG", line.number)
+            .in_field(field),
+        );
+    }
+}
+
+/// Checks that a field is a number no longer than `max_len` characters.
+fn check_number(
+    diagnostics: &mut Vec<Diagnostic>,
+    line: &CfgLine,
+    field: &str,
+    value: &str,
+    max_len: usize,
+) -> Option<f64> {
+    check_length(diagnostics, line, field, value, max_len);
+    let parsed = value.parse::<f64>().ok();
+    if parsed.is_none() {
+        diagnostics.push(
+            Diagnostic::error(
+                "cfg-numeric-field",
+                format!("Field '{}' is not a number: '{}'.", field, value),
+            )
+            .at_line("CFG", line.number)
+            .in_field(field),
+        );
+    }
+    parsed
+}
+
+fn missing_fields(line: &CfgLine, expected: usize, description: &str) -> Diagnostic {
+    Diagnostic::error(
+        "cfg-field-count",
+        format!(
+            "The {} has {} fields; at least {} are required.",
+            description,
+            line.fields.len(),
+            expected
+        ),
+    )
+    .at_line("CFG", line.number)
+}
+
+/// Parses a channel count such as "12A" or "4D", requiring the type suffix.
+/// `None` if the count is not a number.
+fn parse_count(
+    diagnostics: &mut Vec<Diagnostic>,
+    line: &CfgLine,
+    field: &str,
+    value: &str,
+    suffix: char,
+) -> Option<usize> {
+    let digits = value
+        .strip_suffix(suffix)
+        .or_else(|| value.strip_suffix(suffix.to_ascii_lowercase()));
+    let count = digits.unwrap_or(value).parse().ok();
+    let message = match (digits, count) {
+        (None, _) => format!(
+            "Field '{}' must end with '{}' but is '{}'.",
+            field, suffix, value
+        ),
+        (Some(_), None) => format!(
+            "Field '{}' must be a number of channels but is '{}'.",
+            field, value
+        ),
+        (Some(_), Some(_)) => return count,
+    };
+    diagnostics.push(
+        Diagnostic::error("cfg-channel-count", message)
+            .at_line("CFG", line.number)
+            .in_field(field),
+    );
+    count
+}
+
+fn check_analog_line(
+    diagnostics: &mut Vec<Diagnostic>,
+    line: &CfgLine,
+    expected_index: usize,
+    revision: u16,
+) {
+    let required = if revision >= 1999 { 13 } else { 10 };
+    if line.fields.len() < required {
+        diagnostics.push(missing_fields(line, required, "analog channel line"));
+        return;
+    }
+
+    let index = line.value(0);
+    if let Some(n) = check_number(diagnostics, line, "An", index, 6)
+        && n as usize != expected_index
+    {
+        diagnostics.push(
+            Diagnostic::warning(
+                "cfg-channel-index",
+                format!(
+                    "Analog channel index is {} but {} was expected.",
+                    index, expected_index
+                ),
+            )
+            .at_line("CFG", line.number)
+            .in_field("An"),
+        );
+    }
+
+    let name = line.value(1);
+    check_length(diagnostics, line, "ch_id", name, 64);
+    check_characters(diagnostics, line, "ch_id", name, revision);
+
+    let phase = line.value(2);
+    check_phase(diagnostics, line, name, phase, revision);
+
+    let ccbm = line.value(3);
+    check_length(diagnostics, line, "ccbm", ccbm, 64);
+    check_characters(diagnostics, line, "ccbm", ccbm, revision);
+
+    let units = line.value(4);
+    check_length(diagnostics, line, "uu", units, 32);
+    if units.is_empty() {
+        diagnostics.push(
+            Diagnostic::error("cfg-units", "The unit field 'uu' is empty.")
+                .at_line("CFG", line.number)
+                .in_field("uu")
+                .on_channel(name),
+        );
+    } else if !KNOWN_UNITS.contains(&units.to_uppercase().as_str()) {
+        diagnostics.push(
+            Diagnostic::warning("cfg-units", format!("Unrecognised unit '{}'.", units))
+                .at_line("CFG", line.number)
+                .in_field("uu")
+                .on_channel(name),
+        );
+    }
+
+    check_number(diagnostics, line, "a", line.value(5), 32);
+    check_number(diagnostics, line, "b", line.value(6), 32);
+    check_number(diagnostics, line, "skew", line.value(7), 32);
+    let min = check_number(diagnostics, line, "min", line.value(8), 13);
+    let max = check_number(diagnostics, line, "max", line.value(9), 13);
+    if let (Some(min), Some(max)) = (min, max)
+        && min > max
+    {
+        diagnostics.push(
+            Diagnostic::error(
+                "cfg-value-range",
+                format!(
+                    "The minimum value {} is greater than the maximum value {}.",
+                    min, max
+                ),
+            )
+            .at_line("CFG", line.number)
+            .in_field("min")
+            .on_channel(name),
+        );
+    }
+
+    if revision >= 1999 {
+        check_number(diagnostics, line, "primary", line.value(10), 32);
+        check_number(diagnostics, line, "secondary", line.value(11), 32);
+        let ps = line.value(12);
+        if !matches!(ps, "P" | "p" | "S" | "s") {
+            diagnostics.push(
+                Diagnostic::error(
+                    "cfg-ps-flag",
+                    format!("The P/S flag must be 'P' or 'S' but is '{}'.", ps),
+                )
+                .at_line("CFG", line.number)
+                .in_field("PS")
+                .on_channel(name),
+            );
+        }
+    }
+}
+
+fn check_phase(
+    diagnostics: &mut Vec<Diagnostic>,
+    line: &CfgLine,
+    channel: &str,
+    phase: &str,
+    revision: u16,
+) {
+    check_length(diagnostics, line, "ph", phase, 2);
+    check_characters(diagnostics, line, "ph", phase, revision);
+    if phase.chars().count() <= 2 && !KNOWN_PHASES.contains(&phase.to_uppercase().as_str()) {
+        diagnostics.push(
+            Diagnostic::info(
+                "cfg-phase",
+                format!("Unusual phase identifier '{}'.", phase),
+            )
+            .at_line("CFG", line.number)
+            .in_field("ph")
+            .on_channel(channel),
+        );
+    }
+}
+
+fn check_digital_line(
+    diagnostics: &mut Vec<Diagnostic>,
+    line: &CfgLine,
+    expected_index: usize,
+    revision: u16,
+) {
+    let (required, status_field) = if revision >= 1999 { (5, 4) } else { (3, 2) };
+    if line.fields.len() < required {
+        diagnostics.push(missing_fields(line, required, "digital channel line"));
+        return;
+    }
+
+    let index = line.value(0);
+    if let Some(n) = check_number(diagnostics, line, "Dn", index, 6)
+        && n as usize != expected_index
+    {
+        diagnostics.push(
+            Diagnostic::warning(
+                "cfg-channel-index",
+                format!(
+                    "Digital channel index is {} but {} was expected.",
+                    index, expected_index
+                ),
+            )
+            .at_line("CFG", line.number)
+            .in_field("Dn"),
+        );
+    }
+
+    let name = line.value(1);
+    check_length(diagnostics, line, "ch_id", name, 64);
+    check_characters(diagnostics, line, "ch_id", name, revision);
+
+    if revision >= 1999 {
+        check_phase(diagnostics, line, name, line.value(2), revision);
+        let ccbm = line.value(3);
+        check_length(diagnostics, line, "ccbm", ccbm, 64);
+        check_characters(diagnostics, line, "ccbm", ccbm, revision);
+    }
+
+    let normal = line.value(status_field);
+    if !matches!(normal, "0" | "1") {
+        diagnostics.push(
+            Diagnostic::error(
+                "cfg-normal-state",
+                format!("The normal state 'y' must be 0 or 1 but is '{}'.", normal),
+            )
+            .at_line("CFG", line.number)
+            .in_field("y")
+            .on_channel(name),
+        );
+    }
+}
+
+/// Validates the CFG text against the field rules of the standard.
+pub fn validate_cfg(cfg_text: &str, info: &ComtradeInfo) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut lines = cfg_text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| CfgLine {
+            number: i + 1,
+            fields: l.split(',').collect(),
+        });
+
+    let Some(line) = lines.next() else {
+        diagnostics.push(Diagnostic::error(
+            "cfg-field-count",
+            "The CFG file is empty.",
+        ));
+        return diagnostics;
+    };
+    let station = line.value(0);
+    check_length(&mut diagnostics, &line, "station_name", station, 64);
+    let device = line.value(1);
+    check_length(&mut diagnostics, &line, "rec_dev_id", device, 64);
+    let revision = match line.field(2) {
+        None | Some("") => 1991,
+        Some(year @ ("1991" | "1999" | "2013")) => year.parse().unwrap_or(1991),
+        Some(year) => {
+            diagnostics.push(
+                Diagnostic::error(
+                    "cfg-revision-year",
+                    format!("Unknown revision year '{}'.", year),
+                )
+                .at_line("CFG", line.number)
+                .in_field("rev_year"),
+            );
+            2013
+        }
+    };
+    check_characters(&mut diagnostics, &line, "station_name", station, revision);
+    check_characters(&mut diagnostics, &line, "rec_dev_id", device, revision);
+
+    let Some(line) = lines.next() else {
+        diagnostics.push(Diagnostic::error(
+            "cfg-field-count",
+            "The CFG file ends before the channel count line.",
+        ));
+        return diagnostics;
+    };
+    if line.fields.len() < 3 {
+        diagnostics.push(missing_fields(&line, 3, "channel count line"));
+        return diagnostics;
+    }
+    let total = check_number(&mut diagnostics, &line, "TT", line.value(0), 7);
+    let analog_count = parse_count(&mut diagnostics, &line, "##A", line.value(1), 'A');
+    let digital_count = parse_count(&mut diagnostics, &line, "##D", line.value(2), 'D');
+    // Without both counts the channel lines cannot be told from the lines after them.
+    let (Some(analog_count), Some(digital_count)) = (analog_count, digital_count) else {
+        return diagnostics;
+    };
+    if let Some(total) = total
+        && total as usize != analog_count + digital_count
+    {
+        diagnostics.push(
+            Diagnostic::error(
+                "cfg-channel-count",
+                format!(
+                    "The total number of channels ({}) does not match the sum of analog ({}) and digital ({}) channels.",
+                    total, analog_count, digital_count
+                ),
+            )
+            .at_line("CFG", line.number)
+            .in_field("TT"),
+        );
+    }
+
+    for i in 0..analog_count {
+        let Some(line) = lines.next() else {
+            diagnostics.push(Diagnostic::error(
+                "cfg-field-count",
+                "The CFG file ends before all analog channels are declared.",
+            ));
+            return diagnostics;
+        };
+        check_analog_line(&mut diagnostics, &line, i + 1, revision);
+    }
+
+    for i in 0..digital_count {
+        let Some(line) = lines.next() else {
+            diagnostics.push(Diagnostic::error(
+                "cfg-field-count",
+                "The CFG file ends before all digital channels are declared.",
+            ));
+            return diagnostics;
+        };
+        check_digital_line(&mut diagnostics, &line, i + 1, revision);
+    }
+
+    let Some(line) = lines.next() else {
+        diagnostics.push(Diagnostic::error(
+            "cfg-field-count",
+            "The CFG file ends before the line frequency.",
+        ));
+        return diagnostics;
+    };
+    check_number(&mut diagnostics, &line, "lf", line.value(0), 32);
+
+    let Some(line) = lines.next() else {
+        diagnostics.push(Diagnostic::error(
+            "cfg-field-count",
+            "The CFG file ends before the number of sampling rates.",
+        ));
+        return diagnostics;
+    };
+    let nrates =
+        check_number(&mut diagnostics, &line, "nrates", line.value(0), 3).unwrap_or(0.0) as usize;
+    // A file with nrates = 0 still has one rate line holding the total sample count.
+    let mut last_end = 0.0;
+    for _ in 0..nrates.max(1) {
+        let Some(line) = lines.next() else {
+            diagnostics.push(Diagnostic::error(
+                "cfg-field-count",
+                "The CFG file ends before all sampling rates are declared.",
+            ));
+            return diagnostics;
+        };
+        if line.fields.len() < 2 {
+            diagnostics.push(missing_fields(&line, 2, "sampling rate line"));
+            continue;
+        }
+        check_number(&mut diagnostics, &line, "samp", line.value(0), 32);
+        if let Some(end) = check_number(&mut diagnostics, &line, "endsamp", line.value(1), 10) {
+            if end <= last_end {
+                diagnostics.push(
+                    Diagnostic::error(
+                        "cfg-sample-order",
+                        format!("The end sample number {} does not increase.", end),
+                    )
+                    .at_line("CFG", line.number)
+                    .in_field("endsamp"),
+                );
+            }
+            last_end = end;
+        }
+    }
+
+    // Start and trigger time stamps.
+    for field in ["start", "trigger"] {
+        let Some(line) = lines.next() else {
+            diagnostics.push(Diagnostic::error(
+                "cfg-field-count",
+                format!("The CFG file ends before the {} time stamp.", field),
+            ));
+            return diagnostics;
+        };
+        if line.fields.len() < 2 {
+            diagnostics.push(missing_fields(
+                &line,
+                2,
+                &format!("{} time stamp line", field),
+            ));
+        }
+    }
+
+    let Some(line) = lines.next() else {
+        diagnostics.push(Diagnostic::error(
+            "cfg-field-count",
+            "The CFG file ends before the data file type.",
+        ));
+        return diagnostics;
+    };
+    let format = line.value(0).to_uppercase();
+    match format.as_str() {
+        "ASCII" | "BINARY" => {}
+        "BINARY32" | "FLOAT32" if revision >= 2013 => {}
+        "BINARY32" | "FLOAT32" => diagnostics.push(
+            Diagnostic::warning(
+                "cfg-data-format",
+                format!(
+                    "The {} data file type was introduced in the 2013 revision.",
+                    format
+                ),
+            )
+            .at_line("CFG", line.number)
+            .in_field("ft"),
+        ),
+        _ => diagnostics.push(
+            Diagnostic::error(
+                "cfg-data-format",
+                format!("Unknown data file type '{}'.", format),
+            )
+            .at_line("CFG", line.number)
+            .in_field("ft"),
+        ),
+    }
+
+    if revision >= 1999 {
+        let Some(line) = lines.next() else {
+            diagnostics.push(Diagnostic::error(
+                "cfg-field-count",
+                "The CFG file ends before the time stamp multiplication factor.",
+            ));
+            return diagnostics;
+        };
+        if let Some(timemult) = check_number(&mut diagnostics, &line, "timemult", line.value(0), 32)
+            && timemult <= 0.0
+        {
+            diagnostics.push(
+                Diagnostic::error(
+                    "cfg-timemult",
+                    "The time stamp multiplication factor must be positive.",
+                )
+                .at_line("CFG", line.number)
+                .in_field("timemult"),
+            );
+        }
+    }
+
+    if revision >= 2013 {
+        match lines.next() {
+            Some(line) => {
+                for (i, field) in ["time_code", "local_code"].into_iter().enumerate() {
+                    check_length(&mut diagnostics, &line, field, line.value(i), 6);
+                }
+            }
+            None => diagnostics.push(Diagnostic::error(
+                "cfg-field-count",
+                "The CFG file ends before the time codes.",
+            )),
+        }
+        match lines.next() {
+            Some(line) => {
+                let tmq = line.value(0);
+                if tmq.len() != 1 || !tmq.chars().all(|c| c.is_ascii_hexdigit()) {
+                    diagnostics.push(
+                        Diagnostic::error(
+                            "cfg-time-quality",
+                            format!(
+                                "The time quality code must be one hexadecimal digit but is '{}'.",
+                                tmq
+                            ),
+                        )
+                        .at_line("CFG", line.number)
+                        .in_field("tmq_code"),
+                    );
+                }
+                let leap = line.value(1);
+                if !matches!(leap, "0" | "1" | "2" | "3") {
+                    diagnostics.push(
+                        Diagnostic::error(
+                            "cfg-leap-second",
+                            format!("The leap second indicator must be 0-3 but is '{}'.", leap),
+                        )
+                        .at_line("CFG", line.number)
+                        .in_field("leapsec"),
+                    );
+                }
+            }
+            None => diagnostics.push(Diagnostic::error(
+                "cfg-field-count",
+                "The CFG file ends before the time quality line.",
+            )),
+        }
+    }
+
+    if analog_count != info.analog_channels.len() || digital_count != info.digital_channels.len() {
+        diagnostics.push(Diagnostic::error(
+            "cfg-channel-count",
+            format!(
+                "The CFG declares {} analog and {} digital channels but {} and {} were parsed.",
+                analog_count,
+                digital_count,
+                info.analog_channels.len(),
+                info.digital_channels.len()
+            ),
+        ));
+    }
+
+    diagnostics
+}
+
+/// Per-channel counts of sample problems found in the DAT file.
+#[derive(Default, Clone)]
+struct ChannelTally {
+    missing: usize,
+    first_missing: Option<usize>,
+    out_of_range: usize,
+    first_out_of_range: Option<usize>,
+}
+
+impl ChannelTally {
+    fn check(&mut self, sample: usize, raw: Option<f64>, channel: &SerializableAnalogChannel) {
+        match raw {
+            None => {
+                self.missing += 1;
+                self.first_missing.get_or_insert(sample);
+            }
+            Some(raw) => {
+                if channel.min_value < channel.max_value
+                    && (raw < channel.min_value || raw > channel.max_value)
+                {
+                    self.out_of_range += 1;
+                    self.first_out_of_range.get_or_insert(sample);
+                }
+            }
+        }
+    }
+}
+
+/// Tracks sample number order and record shape across the DAT file.
+#[derive(Default)]
+struct RecordTally {
+    records: usize,
+    last_sample_number: Option<u64>,
+    out_of_order: usize,
+    first_out_of_order: Option<usize>,
+    bad_length: usize,
+    first_bad_length: Option<usize>,
+    /// The 1-based DAT line of the first bad record, for ASCII files.
+    first_bad_line: Option<usize>,
+}
+
+impl RecordTally {
+    fn sample_number(&mut self, sample: usize, number: u64) {
+        if self.last_sample_number.is_some_and(|last| number <= last) {
+            self.out_of_order += 1;
+            self.first_out_of_order.get_or_insert(sample);
+        }
+        self.last_sample_number = Some(number);
+    }
+}
+
+fn read_ascii_dat(
+    dat: &[u8],
+    analog: &[SerializableAnalogChannel],
+    digital_count: usize,
+    records: &mut RecordTally,
+    channels: &mut [ChannelTally],
+) {
+    let text = String::from_utf8_lossy(dat);
+    let expected = 2 + analog.len() + digital_count;
+
+    // Blank lines hold no record but still count for the line numbers.
+    for (line_index, line) in text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+    {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let sample = records.records;
+        records.records += 1;
+
+        if fields.len() != expected {
+            records.bad_length += 1;
+            if records.first_bad_length.is_none() {
+                records.first_bad_length = Some(sample);
+                records.first_bad_line = Some(line_index + 1);
+            }
+        }
+
+        if let Some(number) = fields.first().and_then(|f| f.parse::<u64>().ok()) {
+            records.sample_number(sample, number);
+        }
+
+        for (i, channel) in analog.iter().enumerate() {
+            let raw = fields
+                .get(2 + i)
+                .filter(|f| !f.is_empty() && **f != "99999")
+                .and_then(|f| f.parse::<f64>().ok());
+            channels[i].check(sample, raw, channel);
+        }
+    }
+}
+
+fn read_binary_dat(
+    dat: &[u8],
+    format: &str,
+    analog: &[SerializableAnalogChannel],
+    digital_count: usize,
+    records: &mut RecordTally,
+    channels: &mut [ChannelTally],
+) {
+    let value_size = if format == "BINARY" { 2 } else { 4 };
+    let record_length = 8 + value_size * analog.len() + 2 * digital_count.div_ceil(16);
+
+    if !dat.len().is_multiple_of(record_length) {
+        records.bad_length += 1;
+        records
+            .first_bad_length
+            .get_or_insert(dat.len() / record_length);
+    }
+
+    for (sample, record) in dat.chunks_exact(record_length).enumerate() {
+        records.records += 1;
+        let number = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+        records.sample_number(sample, number as u64);
+
+        for (i, channel) in analog.iter().enumerate() {
+            let at = 8 + i * value_size;
+            let raw = match format {
+                "BINARY" => {
+                    let v = i16::from_le_bytes([record[at], record[at + 1]]);
+                    (v != i16::MIN).then_some(v as f64)
+                }
+                "BINARY32" => {
+                    let v = i32::from_le_bytes([
+                        record[at],
+                        record[at + 1],
+                        record[at + 2],
+                        record[at + 3],
+                    ]);
+                    (v != i32::MIN).then_some(v as f64)
+                }
+                _ => {
+                    let v = f32::from_le_bytes([
+                        record[at],
+                        record[at + 1],
+                        record[at + 2],
+                        record[at + 3],
+                    ]);
+                    (!v.is_nan()).then_some(v as f64)
+                }
+            };
+            channels[i].check(sample, raw, channel);
+        }
+    }
+}
+
+/// Validates the DAT file contents against the CFG: record length, sample
+/// number order, value range and missing-data markers.
+pub fn validate_dat(dat: &[u8], info: &ComtradeInfo) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let analog = &info.analog_channels;
+    let digital_count = info.digital_channels.len();
+    let format = info.data_format.as_str();
+
+    let mut records = RecordTally::default();
+    let mut channels = vec![ChannelTally::default(); analog.len()];
+
+    if format == "ASCII" {
+        read_ascii_dat(dat, analog, digital_count, &mut records, &mut channels);
+    } else {
+        read_binary_dat(
+            dat,
+            format,
+            analog,
+            digital_count,
+            &mut records,
+            &mut channels,
+        );
+    }
+
+    if let Some(first) = records.first_bad_length {
+        let message = if format == "ASCII" {
+            format!(
+                "{} record(s) do not have {} fields (sample number, time stamp, {} analog and {} digital values).",
+                records.bad_length,
+                2 + analog.len() + digital_count,
+                analog.len(),
+                digital_count
+            )
+        } else {
+            "The DAT file length is not a multiple of the record length implied by the channel count.".to_string()
+        };
+        let diagnostic = Diagnostic::error("dat-record-length", message).at_sample(first);
+        // Binary records have no lines; only the record index applies.
+        diagnostics.push(match records.first_bad_line {
+            Some(line) => diagnostic.at_line("DAT", line),
+            None => diagnostic,
+        });
+    }
+
+    if let Some(first) = records.first_out_of_order {
+        diagnostics.push(
+            Diagnostic::error(
+                "dat-sample-order",
+                format!(
+                    "{} sample number(s) do not increase monotonically.",
+                    records.out_of_order
+                ),
+            )
+            .at_sample(first),
+        );
+    }
+
+    if let Some(declared) = info
+        .config
+        .sampling_rates
+        .last()
+        .map(|r| r.end_sample_number as usize)
+        && declared != 0
+        && declared != records.records
+    {
+        diagnostics.push(Diagnostic::warning(
+            "dat-record-count",
+            format!(
+                "The CFG declares {} samples but the DAT file contains {} records.",
+                declared, records.records
+            ),
+        ));
+    }
+
+    for (channel, tally) in analog.iter().zip(&channels) {
+        if let Some(first) = tally.first_out_of_range {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "dat-value-range",
+                    format!(
+                        "{} sample(s) lie outside the declared range [{}, {}].",
+                        tally.out_of_range, channel.min_value, channel.max_value
+                    ),
+                )
+                .on_channel(&channel.name)
+                .at_sample(first),
+            );
+        }
+        if let Some(first) = tally.first_missing {
+            diagnostics.push(
+                Diagnostic::info(
+                    "dat-missing-data",
+                    format!("{} sample(s) are marked as missing data.", tally.missing),
+                )
+                .on_channel(&channel.name)
+                .at_sample(first),
+            );
+        }
+    }
+
+    diagnostics
+}
+
+/// Checks that the trigger time lies within the recorded time span.
+pub fn validate_trigger(info: &ComtradeInfo) -> Option<Diagnostic> {
+    let (first, last) = (info.timestamps.first()?, info.timestamps.last()?);
+    if info.trigger_timestamp < *first || info.trigger_timestamp > *last {
+        Some(
+            Diagnostic::warning(
+                "cfg-trigger-time",
+                format!(
+                    "The trigger time lies {:.6} s outside the recording.",
+                    if info.trigger_timestamp < *first {
+                        first - info.trigger_timestamp
+                    } else {
+                        info.trigger_timestamp - last
+                    }
+                ),
+            )
+            .between(*first, *last),
+        )
+    } else {
+        None
+    }
+}
+
+/// Runs every structural check on a recording.
+pub fn validate_recording(
+    cfg_text: &str,
+    dat: Option<&[u8]>,
+    info: &ComtradeInfo,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = validate_cfg(cfg_text, info);
+    if let Some(dat) = dat {
+        diagnostics.extend(validate_dat(dat, info));
+    }
+    diagnostics.extend(validate_trigger(info));
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, digital_channel, recording};
+
+    const CFG: &str = "Station,Relay,1999\r\n3,2A,1D\r\n1,IA,A,Line,A,1,0,0,-100,100,600,1,P\r\n2,VA,XY,Line,Volts,1,0,0,-100,100,1000,1,Q\r\n1,TRIP,,Line,2\r\n50\r\n1\r\n1000,4\r\n01/01/2024,00:00:00.000000\r\n01/01/2024,00:00:01.000000\r\nASCII\r\n1\r\n";
+
+    fn info() -> ComtradeInfo {
+        let mut info = recording(
+            1000.0,
+            50.0,
+            vec![
+                analog_channel(1, "IA", "A", "A", vec![0.0; 4]),
+                analog_channel(2, "VA", "Volts", "XY", vec![0.0; 4]),
+            ],
+            vec![digital_channel(1, "TRIP", 0)],
+        );
+        for channel in &mut info.analog_channels {
+            channel.min_value = -100.0;
+            channel.max_value = 100.0;
+        }
+        info.trigger_timestamp = 1.0;
+        info
+    }
+
+    fn rules(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|d| d.rule.as_str()).collect()
+    }
+
+    #[test]
+    fn test_validate_cfg() {
+        let diagnostics = validate_cfg(CFG, &info());
+        let rules = rules(&diagnostics);
+
+        assert!(rules.contains(&"cfg-units"));
+        assert!(rules.contains(&"cfg-phase"));
+        assert!(rules.contains(&"cfg-ps-flag"));
+        assert!(rules.contains(&"cfg-normal-state"));
+
+        let ps = diagnostics
+            .iter()
+            .find(|d| d.rule == "cfg-ps-flag")
+            .unwrap();
+        assert_eq!(ps.line, Some(4));
+        assert_eq!(ps.channel.as_deref(), Some("VA"));
+
+        // Non-ASCII text before the 2013 revision is reported once.
+        let cfg = CFG.replacen("Station", "Stätion", 1);
+        let characters = validate_cfg(&cfg, &info())
+            .iter()
+            .filter(|d| d.rule == "cfg-allowed-characters")
+            .count();
+        assert_eq!(characters, 1);
+
+        // A count that is not a number is reported on its own.
+        let cfg = CFG.replacen("3,2A,1D", "3,xA,1D", 1);
+        let diagnostics = validate_cfg(&cfg, &info());
+        let count = diagnostics
+            .iter()
+            .find(|d| d.rule == "cfg-channel-count")
+            .unwrap();
+        assert_eq!(count.field.as_deref(), Some("##A"));
+        assert!(!diagnostics.iter().any(|d| d.rule == "cfg-units"));
+    }
+
+    #[test]
+    fn test_validate_dat() {
+        let dat = b"1,0,10,20,0\r\n2,1000,150,20,0\r\n2,2000,99999,20\r\n4,3000,10,20,1\r\n";
+        let diagnostics = validate_dat(dat, &info());
+        let rules = rules(&diagnostics);
+
+        assert_eq!(
+            rules,
+            [
+                "dat-record-length",
+                "dat-sample-order",
+                "dat-value-range",
+                "dat-missing-data"
+            ]
+        );
+        assert_eq!(diagnostics[0].line, Some(3));
+        assert_eq!(diagnostics[2].sample, Some(1));
+
+        // A blank line moves the line number but not the sample.
+        let dat = b"1,0,10,20,0\r\n\r\n2,1000,150,20\r\n";
+        let diagnostics = validate_dat(dat, &info());
+        assert_eq!(diagnostics[0].rule, "dat-record-length");
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].sample),
+            (Some(3), Some(1))
+        );
+    }
+
+    #[test]
+    fn test_validate_trigger() {
+        let mut info = info();
+        assert!(validate_trigger(&info).is_some());
+        info.trigger_timestamp = 0.002;
+        assert!(validate_trigger(&info).is_none());
+    }
+}
@@ -0,0 +1,442 @@
+// comtrade_rust/src/voltage_events.rs
+// This file contains voltage sag, swell and interruption detection following IEC 61000-4-30.
+// This file exists so power quality events are reported with their duration, residual voltage and affected phases across all voltage channels.
+// RELEVANT FILES: comtrade_rust/src/dsp.rs, comtrade_rust/src/phases.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::SerializableAnalogChannel;
+use crate::clipping::{ClippedInterval, is_clipped};
+use crate::dsp::{cycle_windows, nominal_frequency, rms};
+use crate::phases::is_phase_to_phase;
+use crate::series::DerivedSeries;
+
+const DEFAULT_SAG_THRESHOLD: f64 = 90.0;
+const DEFAULT_SWELL_THRESHOLD: f64 = 110.0;
+const DEFAULT_INTERRUPTION_THRESHOLD: f64 = 10.0;
+const DEFAULT_HYSTERESIS: f64 = 2.0;
+
+/// Options for the voltage event detection. Every field is optional;
+/// thresholds are percentages of the declared voltage.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct VoltageEventOptions {
+    /// The declared phase-to-neutral supply voltage in volts (primary).
+    /// Phase-to-phase channels are compared with √3 times this value.
+    /// Defaults to the RMS of each channel's first cycle.
+    pub declared_voltage: Option<f64>,
+    /// A sag starts when any channel falls below this. Defaults to 90 %.
+    pub sag_threshold_percent: Option<f64>,
+    /// A swell starts when any channel rises above this. Defaults to 110 %.
+    pub swell_threshold_percent: Option<f64>,
+    /// An interruption starts when all channels fall below this. Defaults to 10 %.
+    pub interruption_threshold_percent: Option<f64>,
+    /// How far past the threshold the voltage must recover for an event to
+    /// end. Defaults to 2 %.
+    pub hysteresis_percent: Option<f64>,
+}
+
+/// The kind of a voltage event.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VoltageEventKind {
+    Sag,
+    Swell,
+    Interruption,
+}
+
+/// A sag, swell or interruption aggregated over all voltage channels.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VoltageEvent {
+    /// Whether this is a sag, swell or interruption.
+    pub kind: VoltageEventKind,
+    /// The time of the Urms(1/2) value that crossed the threshold, as Unix seconds.
+    pub start_time: f64,
+    /// The time of the Urms(1/2) value at which the event ended, as Unix
+    /// seconds, or of the last value when the recording ends first.
+    pub end_time: f64,
+    /// `end_time - start_time` in seconds.
+    pub duration: f64,
+    /// The Urms(1/2) furthest from the declared voltage on any channel
+    /// during the event (lowest for sags and interruptions, highest for
+    /// swells), in volts.
+    pub residual_voltage: f64,
+    /// `residual_voltage` as a percentage of that channel's declared voltage.
+    pub residual_percent: f64,
+    /// The names of the channels that crossed the threshold.
+    pub channels: Vec<String>,
+    /// The phases of those channels (e.g. "A", "BC"), empty where the CFG
+    /// does not declare one.
+    pub phases: Vec<String>,
+    /// False when the recording ends before the event does.
+    pub complete: bool,
+    /// False when any channel is clipped during the event.
+    pub reliable: bool,
+}
+
+/// The voltage event analysis of a recording.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct VoltageEventAnalysis {
+    /// The half-cycle-refreshed one-cycle RMS, Urms(1/2), of each voltage
+    /// channel in primary units, timestamped at the end of each window.
+    pub rms: Vec<DerivedSeries>,
+    /// The declared voltage each channel was compared with, in volts.
+    pub reference_voltages: Vec<f64>,
+    /// The events in order of their start time.
+    pub events: Vec<VoltageEvent>,
+}
+
+/// A voltage channel prepared for event detection.
+struct VoltageChannel<'a> {
+    index: usize,
+    channel: &'a SerializableAnalogChannel,
+    /// The factor converting the channel's primary values to volts.
+    to_volts: f64,
+    /// The declared voltage of the channel in volts.
+    reference: f64,
+}
+
+/// An event in progress.
+struct ActiveEvent {
+    start_time: f64,
+    start_sample: usize,
+    /// The most extreme Urms(1/2) so far as (volts, percent of declared).
+    extreme: Option<(f64, f64)>,
+    affected: Vec<bool>,
+}
+
+/// How one kind of event starts, ends and which channels it affects.
+struct EventRule {
+    kind: VoltageEventKind,
+    threshold: f64,
+    hysteresis: f64,
+}
+
+impl EventRule {
+    fn crosses(&self, percent: f64) -> bool {
+        match self.kind {
+            VoltageEventKind::Swell => percent > self.threshold,
+            _ => percent < self.threshold,
+        }
+    }
+
+    fn recovered(&self, percent: f64) -> bool {
+        match self.kind {
+            VoltageEventKind::Swell => percent <= self.threshold - self.hysteresis,
+            _ => percent >= self.threshold + self.hysteresis,
+        }
+    }
+
+    /// Sags and swells start on any channel and end when all channels have
+    /// recovered; interruptions start when all channels are below the
+    /// threshold and end when any channel has recovered.
+    fn starts(&self, percents: &[f64]) -> bool {
+        match self.kind {
+            VoltageEventKind::Interruption => percents.iter().all(|&p| self.crosses(p)),
+            _ => percents.iter().any(|&p| self.crosses(p)),
+        }
+    }
+
+    fn ends(&self, percents: &[f64]) -> bool {
+        match self.kind {
+            VoltageEventKind::Interruption => percents.iter().any(|&p| self.recovered(p)),
+            _ => percents.iter().all(|&p| self.recovered(p)),
+        }
+    }
+
+    fn more_extreme(&self, a: f64, b: f64) -> bool {
+        match self.kind {
+            VoltageEventKind::Swell => a > b,
+            _ => a < b,
+        }
+    }
+}
+
+/// Detects sags, swells and interruptions across all voltage channels ("V" or
+/// "kV") from their Urms(1/2) values, as described in IEC 61000-4-30.
+pub fn detect_voltage_events(
+    channels: &[SerializableAnalogChannel],
+    timestamps: &[f64],
+    line_frequency: f64,
+    clipped_intervals: &[ClippedInterval],
+    options: &VoltageEventOptions,
+) -> VoltageEventAnalysis {
+    let frequency = nominal_frequency(line_frequency);
+    let samples = channels
+        .iter()
+        .filter(|c| c.is_voltage())
+        .map(|c| c.primary_values.len())
+        .min()
+        .unwrap_or(0)
+        .min(timestamps.len());
+    // One-cycle windows refreshed every half cycle.
+    let windows = cycle_windows(timestamps, samples, frequency, 0.5);
+    let Some(&(first_start, first_end)) = windows.first() else {
+        return VoltageEventAnalysis::default();
+    };
+
+    let voltages: Vec<VoltageChannel> = channels
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.is_voltage())
+        .filter_map(|(index, channel)| {
+            let to_volts = channel.si_scale();
+            let reference = match options.declared_voltage {
+                Some(declared) if is_phase_to_phase(&channel.phase) => declared * 3f64.sqrt(),
+                Some(declared) => declared,
+                None => to_volts * rms(&channel.primary_values[first_start..first_end]),
+            };
+            (reference > 0.0).then_some(VoltageChannel {
+                index,
+                channel,
+                to_volts,
+                reference,
+            })
+        })
+        .collect();
+    if voltages.is_empty() {
+        return VoltageEventAnalysis::default();
+    }
+
+    let mut analysis = VoltageEventAnalysis {
+        rms: voltages
+            .iter()
+            .map(|v| {
+                DerivedSeries::new(format!("Urms(1/2) ({})", v.channel.name), &v.channel.units)
+            })
+            .collect(),
+        reference_voltages: voltages.iter().map(|v| v.reference).collect(),
+        events: Vec::new(),
+    };
+
+    let hysteresis = options.hysteresis_percent.unwrap_or(DEFAULT_HYSTERESIS);
+    let rules = [
+        (
+            VoltageEventKind::Sag,
+            options.sag_threshold_percent,
+            DEFAULT_SAG_THRESHOLD,
+        ),
+        (
+            VoltageEventKind::Swell,
+            options.swell_threshold_percent,
+            DEFAULT_SWELL_THRESHOLD,
+        ),
+        (
+            VoltageEventKind::Interruption,
+            options.interruption_threshold_percent,
+            DEFAULT_INTERRUPTION_THRESHOLD,
+        ),
+    ]
+    .map(|(kind, threshold, default)| EventRule {
+        kind,
+        threshold: threshold.unwrap_or(default),
+        hysteresis,
+    });
+    let mut active: [Option<ActiveEvent>; 3] = [None, None, None];
+
+    let finish =
+        |rule: &EventRule, event: ActiveEvent, end_time: f64, end_sample: usize, complete: bool| {
+            let affected: Vec<&VoltageChannel> = voltages
+                .iter()
+                .zip(&event.affected)
+                .filter_map(|(v, &a)| a.then_some(v))
+                .collect();
+            let reliable = !voltages
+                .iter()
+                .any(|v| is_clipped(clipped_intervals, v.index, event.start_sample, end_sample));
+            VoltageEvent {
+                kind: rule.kind,
+                start_time: event.start_time,
+                end_time,
+                duration: end_time - event.start_time,
+                residual_voltage: event.extreme.map_or(0.0, |e| e.0),
+                residual_percent: event.extreme.map_or(0.0, |e| e.1),
+                channels: affected.iter().map(|v| v.channel.name.clone()).collect(),
+                phases: affected
+                    .iter()
+                    .map(|v| v.channel.phase.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect(),
+                complete,
+                reliable,
+            }
+        };
+
+    for &(start, end) in &windows {
+        let last = end - 1;
+        let time = timestamps[last];
+        let levels: Vec<(f64, f64)> = voltages
+            .iter()
+            .zip(analysis.rms.iter_mut())
+            .map(|(v, series)| {
+                let value = rms(&v.channel.primary_values[start..end]);
+                series.push(time, value);
+                let volts = value * v.to_volts;
+                (volts, 100.0 * volts / v.reference)
+            })
+            .collect();
+        let percents: Vec<f64> = levels.iter().map(|l| l.1).collect();
+
+        for (rule, slot) in rules.iter().zip(active.iter_mut()) {
+            match slot {
+                None => {
+                    if rule.starts(&percents) {
+                        *slot = Some(ActiveEvent {
+                            start_time: time,
+                            start_sample: start,
+                            extreme: None,
+                            affected: vec![false; voltages.len()],
+                        });
+                    }
+                }
+                Some(_) if rule.ends(&percents) => {
+                    if let Some(event) = slot.take() {
+                        analysis.events.push(finish(rule, event, time, last, true));
+                    }
+                    continue;
+                }
+                Some(_) => {}
+            }
+            if let Some(event) = slot {
+                for (i, &level) in levels.iter().enumerate() {
+                    if rule.crosses(level.1) {
+                        event.affected[i] = true;
+                    }
+                    if event
+                        .extreme
+                        .is_none_or(|e| rule.more_extreme(level.1, e.1))
+                    {
+                        event.extreme = Some(level);
+                    }
+                }
+            }
+        }
+    }
+
+    let last_end = windows[windows.len() - 1].1 - 1;
+    for (rule, slot) in rules.iter().zip(active.iter_mut()) {
+        if let Some(event) = slot.take() {
+            analysis
+                .events
+                .push(finish(rule, event, timestamps[last_end], last_end, false));
+        }
+    }
+
+    analysis
+        .events
+        .sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+    analysis
+}
+
+/// Returns "voltage sag", "voltage swell" or "voltage interruption".
+pub fn voltage_event_name(kind: VoltageEventKind) -> &'static str {
+    match kind {
+        VoltageEventKind::Sag => "voltage sag",
+        VoltageEventKind::Swell => "voltage swell",
+        VoltageEventKind::Interruption => "voltage interruption",
+    }
+}
+
+/// Describes an event for `analysis_notes`. `recording_start` is the time of
+/// the first sample, so the note gives seconds into the recording.
+pub fn voltage_event_note(event: &VoltageEvent, recording_start: f64) -> String {
+    let name = voltage_event_name(event.kind);
+    let duration = if event.complete {
+        format!("lasting {:.1} ms", event.duration * 1000.0)
+    } else {
+        format!(
+            "lasting at least {:.1} ms (until the end of the recording)",
+            event.duration * 1000.0
+        )
+    };
+    let mut note = format!(
+        "{}{} detected on {} at {:.4} seconds, {}, with residual voltage {:.1}% of declared.",
+        name[..1].to_uppercase(),
+        &name[1..],
+        event.channels.join(", "),
+        event.start_time - recording_start,
+        duration,
+        event.residual_percent
+    );
+    if !event.phases.is_empty() {
+        note.push_str(&format!(" Affected phases: {}.", event.phases.join(", ")));
+    }
+    note
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording};
+    use std::f64::consts::PI;
+
+    /// Phase voltage with 100 V RMS sampled at 2 kHz for 0.5 s, scaled by
+    /// `level` at each time.
+    fn phase_voltage(shift_deg: f64, level: impl Fn(f64) -> f64) -> Vec<f64> {
+        (0..1000)
+            .map(|i| {
+                let t = i as f64 / 2000.0;
+                let value =
+                    100.0 * 2f64.sqrt() * (2.0 * PI * 50.0 * t + shift_deg.to_radians()).sin();
+                value * level(t)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_voltage_events() {
+        let interruption = |t: f64| if (0.3..0.36).contains(&t) { 0.0 } else { 1.0 };
+        let va = phase_voltage(0.0, |t| {
+            if (0.1..0.2).contains(&t) {
+                0.5
+            } else {
+                interruption(t)
+            }
+        });
+        let vb = phase_voltage(-120.0, |t| {
+            if (0.4..0.45).contains(&t) {
+                1.2
+            } else {
+                interruption(t)
+            }
+        });
+        let vc = phase_voltage(120.0, interruption);
+        let channels = vec![
+            analog_channel(1, "VA", "V", "A", va),
+            analog_channel(2, "VB", "V", "B", vb),
+            analog_channel(3, "VC", "V", "C", vc),
+        ];
+        let info = recording(2000.0, 50.0, channels, vec![]);
+        let options = VoltageEventOptions {
+            declared_voltage: Some(100.0),
+            ..Default::default()
+        };
+
+        let analysis =
+            detect_voltage_events(&info.analog_channels, &info.timestamps, 50.0, &[], &options);
+        let kinds: Vec<VoltageEventKind> = analysis.events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                VoltageEventKind::Sag,
+                VoltageEventKind::Sag,
+                VoltageEventKind::Interruption,
+                VoltageEventKind::Swell
+            ]
+        );
+
+        let sag = &analysis.events[0];
+        assert_eq!(sag.phases, ["A"]);
+        assert!((sag.residual_percent - 50.0).abs() < 0.5);
+        assert!((sag.duration - 0.1).abs() <= 0.02);
+        assert!(sag.complete && sag.reliable);
+        assert!(voltage_event_note(sag, 0.0).starts_with("Voltage sag detected on VA"));
+
+        let interruption = &analysis.events[2];
+        assert_eq!(interruption.channels.len(), 3);
+        assert!(interruption.residual_percent < 1.0);
+        assert!((analysis.events[3].residual_voltage - 120.0).abs() < 0.5);
+        assert_eq!(analysis.rms.len(), 3);
+        assert_eq!(analysis.rms[0].values.len(), 49);
+    }
+}
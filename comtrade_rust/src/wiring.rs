@@ -0,0 +1,460 @@
+// comtrade_rust/src/wiring.rs
+// This file contains the phase rotation, polarity and current sum checks on the pre-fault phasors.
+// This file exists because swapped phases and reversed CTs are common commissioning findings that otherwise corrupt every three-phase analysis.
+// RELEVANT FILES: comtrade_rust/src/phases.rs, comtrade_rust/src/power.rs, comtrade_rust/src/diagnostics.rs, comtrade_rust/src/lib.rs
+
+use num_complex::Complex64;
+use serde::Deserialize;
+
+use crate::diagnostics::Diagnostic;
+use crate::dsp::{cycle_windows, nominal_frequency, phasors_at};
+use crate::phases::{is_phase_to_phase, three_phase_set};
+use crate::power::{circuit_key, phase_voltage};
+use crate::{ComtradeInfo, SerializableAnalogChannel};
+
+const DEFAULT_ANGLE_TOLERANCE_DEG: f64 = 20.0;
+const DEFAULT_SUM_TOLERANCE: f64 = 0.1;
+/// A set is skipped when a phase is below this fraction of the largest,
+/// e.g. an open phase or a dead line.
+const MIN_PHASE_FRACTION: f64 = 0.1;
+/// Currents below this fraction of the channel's largest sample are taken as
+/// noise rather than load.
+const MIN_LOAD_FRACTION: f64 = 0.01;
+
+/// Options for the wiring checks. Every field is optional.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct WiringOptions {
+    /// The end of the one-cycle pre-fault window as Unix seconds. Defaults
+    /// to the first cycle of the recording.
+    pub time: Option<f64>,
+    /// How far, in degrees, angles may stray from the expected 120° spacing
+    /// or from the other phases. Defaults to 20.
+    pub angle_tolerance_deg: Option<f64>,
+    /// The largest |IA + IB + IC| (or its difference from the neutral
+    /// current) as a fraction of the mean phase current. Defaults to 0.1.
+    pub sum_tolerance: Option<f64>,
+}
+
+/// The phasors of a three-phase set of channels in the pre-fault window.
+struct PhaseSet<'a> {
+    channels: [&'a SerializableAnalogChannel; 3],
+    phasors: [Complex64; 3],
+}
+
+impl PhaseSet<'_> {
+    fn names(&self) -> String {
+        self.channels.map(|c| c.name.as_str()).join(", ")
+    }
+}
+
+/// Returns `degrees` wrapped into (−180, 180].
+fn wrap(degrees: f64) -> f64 {
+    let wrapped = degrees.rem_euclid(360.0);
+    if wrapped > 180.0 {
+        wrapped - 360.0
+    } else {
+        wrapped
+    }
+}
+
+/// Returns the angle of `b` relative to `a` in degrees.
+fn angle_between(a: Complex64, b: Complex64) -> f64 {
+    (b / a).arg().to_degrees()
+}
+
+/// Returns the largest deviation in degrees of a set from 120° spacing in
+/// ABC (or ACB) rotation, after reversing the phase `flip`.
+fn spacing_error(phasors: [Complex64; 3], flip: Option<usize>, abc: bool) -> f64 {
+    let mut phasors = phasors;
+    if let Some(flip) = flip {
+        phasors[flip] = -phasors[flip];
+    }
+    let step = if abc { -120.0 } else { 120.0 };
+    (0..3)
+        .map(|k| wrap(angle_between(phasors[k], phasors[(k + 1) % 3]) - step).abs())
+        .fold(0.0, f64::max)
+}
+
+/// Returns true if a current channel monitors the neutral or ground.
+fn is_neutral(channel: &SerializableAnalogChannel) -> bool {
+    let phase = channel.phase.trim().to_uppercase();
+    let name = channel.name.trim().to_uppercase();
+    matches!(phase.as_str(), "N" | "G" | "E" | "NG" | "GN")
+        || ["IN", "I0", "3I0", "IG", "IE"].iter().any(|p| name == *p)
+}
+
+/// Checks the rotation and spacing of one set, reporting the channel whose
+/// reversal or swap explains the angles. Returns the suspect channels.
+fn check_rotation(set: &PhaseSet, tolerance: f64, diagnostics: &mut Vec<Diagnostic>) -> Vec<usize> {
+    let candidates = [None, Some(0), Some(1), Some(2)]
+        .into_iter()
+        .flat_map(|flip| [(flip, true), (flip, false)]);
+    let Some((flip, abc, error)) = candidates
+        .map(|(flip, abc)| (flip, abc, spacing_error(set.phasors, flip, abc)))
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+    else {
+        return Vec::new();
+    };
+
+    let [a, b, c] = set.phasors;
+    if error > tolerance {
+        diagnostics.push(
+            Diagnostic::warning(
+                "phase-spacing",
+                format!(
+                    "The pre-fault phasors of {} are not 120° apart (A to B {:.0}°, B to C {:.0}°, \
+                     C to A {:.0}°); check the phase assignment of these channels.",
+                    set.names(),
+                    angle_between(a, b),
+                    angle_between(b, c),
+                    angle_between(c, a)
+                ),
+            )
+            .on_channel(&set.channels[0].name),
+        );
+        return Vec::new();
+    }
+
+    let mut suspects = Vec::new();
+    if let Some(flip) = flip {
+        diagnostics.push(
+            Diagnostic::warning(
+                "phase-polarity",
+                format!(
+                    "{} is 180° from where the rest of {} place it; its polarity appears reversed.",
+                    set.channels[flip].name,
+                    set.names()
+                ),
+            )
+            .on_channel(&set.channels[flip].name),
+        );
+        suspects.push(flip);
+    }
+    if !abc {
+        diagnostics.push(
+            Diagnostic::warning(
+                "phase-rotation",
+                format!(
+                    "{} rotate A-C-B; {} and {} appear swapped, unless the system rotation is ACB.",
+                    set.names(),
+                    set.channels[1].name,
+                    set.channels[2].name
+                ),
+            )
+            .on_channel(&set.channels[1].name),
+        );
+        suspects.extend([1, 2]);
+    }
+    suspects
+}
+
+/// Checks that the phase currents of a circuit sum to the neutral current,
+/// or to about zero when there is none.
+fn check_current_sum(
+    set: &PhaseSet,
+    neutral: Option<(&SerializableAnalogChannel, Complex64)>,
+    tolerance: f64,
+    suspects: &[usize],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mean = set.phasors.iter().map(|p| p.norm()).sum::<f64>() / 3.0;
+    let sum: Complex64 = set.phasors.iter().sum();
+    let relative = |x: Complex64| x.norm() / mean;
+
+    if let Some((channel, neutral)) = neutral {
+        if relative(sum - neutral) <= tolerance {
+            return;
+        }
+        let message = if relative(sum + neutral) <= tolerance {
+            format!(
+                "The sum of {} matches {} with the opposite sign; the polarity of {} appears \
+                 reversed.",
+                set.names(),
+                channel.name,
+                channel.name
+            )
+        } else {
+            format!(
+                "The sum of {} differs from {} by {:.0}% of the phase current.",
+                set.names(),
+                channel.name,
+                relative(sum - neutral) * 100.0
+            )
+        };
+        diagnostics.push(Diagnostic::warning("current-sum", message).on_channel(&channel.name));
+        return;
+    }
+
+    if relative(sum) <= tolerance {
+        return;
+    }
+    // The phase whose reversal would bring the sum back to zero.
+    let suspect = (0..3)
+        .filter(|&p| relative(sum - 2.0 * set.phasors[p]) <= tolerance)
+        .min_by(|&p, &q| {
+            relative(sum - 2.0 * set.phasors[p]).total_cmp(&relative(sum - 2.0 * set.phasors[q]))
+        });
+    if suspect.is_some_and(|p| suspects.contains(&p)) {
+        return;
+    }
+    let message = match suspect {
+        Some(p) => format!(
+            "The sum of {} is {:.0}% of the phase current; reversing {} would balance it.",
+            set.names(),
+            relative(sum) * 100.0,
+            set.channels[p].name
+        ),
+        None => format!(
+            "The sum of {} is {:.0}% of the phase current; a CT may be reversed or missing, \
+             unless the circuit carries earth current.",
+            set.names(),
+            relative(sum) * 100.0
+        ),
+    };
+    let channel = set.channels[suspect.unwrap_or(0)];
+    diagnostics.push(Diagnostic::warning("current-sum", message).on_channel(&channel.name));
+}
+
+/// Checks that the angle from voltage to current is about the same on every
+/// phase of a circuit, naming a current that stands out.
+fn check_voltage_current_angles(
+    pairs: &[(
+        usize,
+        &SerializableAnalogChannel,
+        f64,
+        &SerializableAnalogChannel,
+    )],
+    tolerance: f64,
+    suspects: &[usize],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if pairs.len() < 3 {
+        return;
+    }
+    for (k, &(phase, voltage, angle, current)) in pairs.iter().enumerate() {
+        let others: Vec<f64> = pairs
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != k)
+            .map(|(_, p)| p.2)
+            .collect();
+        if wrap(others[0] - others[1]).abs() > tolerance || suspects.contains(&phase) {
+            continue;
+        }
+        let reference = others[0] + wrap(others[1] - others[0]) / 2.0;
+        let deviation = wrap(angle - reference);
+        if deviation.abs() <= tolerance {
+            continue;
+        }
+        let cause = if deviation.abs() >= 180.0 - tolerance {
+            "its polarity appears reversed".to_string()
+        } else if (deviation.abs() - 120.0).abs() <= tolerance {
+            "it appears to be wired to another phase".to_string()
+        } else {
+            "check its phase and polarity".to_string()
+        };
+        diagnostics.push(
+            Diagnostic::warning(
+                "vi-angle",
+                format!(
+                    "The angle from {} to {} ({:.0}°) differs by {:.0}° from the other phases \
+                     ({:.0}°); {}.",
+                    voltage.name, current.name, angle, deviation, reference, cause
+                ),
+            )
+            .on_channel(&current.name),
+        );
+    }
+}
+
+/// Checks the wiring of the three-phase voltage and current sets from their
+/// phasors in a pre-fault cycle: ABC rotation and 120° spacing of each set,
+/// the polarity of each channel, consistent voltage-to-current angles on the
+/// phases of a circuit, and phase currents that sum to the neutral current,
+/// or to about zero.
+///
+/// Phases come from the `phase` field, or the channel name when it is empty.
+/// Sets are grouped by `circuit_component_being_monitored`. Each diagnostic
+/// names the suspect channel.
+pub fn check_wiring(info: &ComtradeInfo, options: &WiringOptions) -> Vec<Diagnostic> {
+    let frequency = nominal_frequency(info.frequency);
+    let tolerance = options
+        .angle_tolerance_deg
+        .filter(|t| *t > 0.0)
+        .unwrap_or(DEFAULT_ANGLE_TOLERANCE_DEG);
+    let sum_tolerance = options
+        .sum_tolerance
+        .filter(|t| *t > 0.0)
+        .unwrap_or(DEFAULT_SUM_TOLERANCE);
+    let time = match options.time {
+        Some(time) => time,
+        None => {
+            let samples = info.timestamps.len();
+            match cycle_windows(&info.timestamps, samples, frequency, 1.0).first() {
+                Some(&(_, end)) => info.timestamps[end - 1],
+                None => return Vec::new(),
+            }
+        }
+    };
+    let channels = &info.analog_channels;
+    let phasor = |index: usize| phasors_at(info, [index], frequency, time).map(|[p]| p);
+    let phase_set = |set: [usize; 3]| {
+        let phasors = [phasor(set[0])?, phasor(set[1])?, phasor(set[2])?];
+        let largest = phasors.iter().map(|p| p.norm()).fold(0.0, f64::max);
+        if largest == 0.0
+            || phasors
+                .iter()
+                .any(|p| p.norm() < MIN_PHASE_FRACTION * largest)
+        {
+            return None;
+        }
+        Some(PhaseSet {
+            channels: set.map(|i| &channels[i]),
+            phasors,
+        })
+    };
+    let carries_load = |index: usize| {
+        let channel = &channels[index];
+        let largest = channel
+            .primary_values
+            .iter()
+            .fold(0.0f64, |m, v| m.max(v.abs()));
+        let largest = largest * channel.si_scale();
+        phasor(index).is_some_and(|p| largest > 0.0 && p.norm() >= MIN_LOAD_FRACTION * largest)
+    };
+
+    let mut circuits: Vec<String> = Vec::new();
+    for channel in channels {
+        let key = circuit_key(channel);
+        if !circuits.contains(&key) {
+            circuits.push(key);
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    for circuit in &circuits {
+        let voltages = three_phase_set(channels, |c| {
+            c.is_voltage() && !is_phase_to_phase(&c.phase) && circuit_key(c) == *circuit
+        });
+        if let Some(set) = voltages.and_then(phase_set) {
+            check_rotation(&set, tolerance, &mut diagnostics);
+        }
+
+        let currents = three_phase_set(channels, |c| {
+            c.is_current() && !is_neutral(c) && circuit_key(c) == *circuit
+        });
+        let Some(currents) = currents.filter(|set| set.iter().all(|&i| carries_load(i))) else {
+            continue;
+        };
+        let Some(set) = phase_set(currents) else {
+            continue;
+        };
+        let suspects = check_rotation(&set, tolerance, &mut diagnostics);
+
+        let neutral = channels
+            .iter()
+            .enumerate()
+            .find(|(_, c)| c.is_current() && is_neutral(c) && circuit_key(c) == *circuit)
+            .and_then(|(i, c)| Some((c, phasor(i)?)));
+        check_current_sum(&set, neutral, sum_tolerance, &suspects, &mut diagnostics);
+
+        let pairs: Vec<_> = (0..3)
+            .filter_map(|phase| {
+                let voltage = phase_voltage(channels, circuit, phase)?;
+                let angle = angle_between(set.phasors[phase], phasor(voltage)?);
+                Some((phase, &channels[voltage], angle, set.channels[phase]))
+            })
+            .collect();
+        check_voltage_current_angles(&pairs, tolerance, &suspects, &mut diagnostics);
+    }
+
+    let start = info.timestamps.first().copied().unwrap_or(time);
+    let window_start = time - 1.0 / frequency;
+    diagnostics
+        .into_iter()
+        .map(|d| d.between(window_start.max(start), time))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, recording, sine};
+
+    fn three_phase(
+        prefix: &str,
+        units: &str,
+        amplitude: f64,
+        shifts: [f64; 3],
+    ) -> Vec<SerializableAnalogChannel> {
+        ["A", "B", "C"]
+            .iter()
+            .zip(shifts)
+            .map(|(p, shift)| {
+                let values = sine(amplitude, 50.0, shift, 1000.0, 100);
+                let mut channel = analog_channel(1, &format!("{}{}", prefix, p), units, p, values);
+                channel.circuit_component_being_monitored = "Line 1".to_string();
+                channel
+            })
+            .collect()
+    }
+
+    fn reversed(channel: &SerializableAnalogChannel) -> SerializableAnalogChannel {
+        let values = channel.primary_values.iter().map(|v| -v).collect();
+        let mut reversed = analog_channel(1, &channel.name, &channel.units, &channel.phase, values);
+        reversed.circuit_component_being_monitored =
+            channel.circuit_component_being_monitored.clone();
+        reversed
+    }
+
+    fn rules(channels: Vec<SerializableAnalogChannel>) -> Vec<(String, String)> {
+        let info = recording(1000.0, 50.0, channels, vec![]);
+        check_wiring(&info, &WiringOptions::default())
+            .into_iter()
+            .map(|d| (d.rule, d.channel.unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn test_check_wiring() {
+        let healthy = || {
+            let mut channels = three_phase("V", "kV", 100.0, [0.0, -120.0, 120.0]);
+            channels.extend(three_phase("I", "A", 500.0, [-30.0, -150.0, 90.0]));
+            channels
+        };
+        assert!(rules(healthy()).is_empty());
+
+        // IB reversed.
+        let mut channels = healthy();
+        channels[4] = reversed(&channels[4]);
+        assert_eq!(
+            rules(channels),
+            [("phase-polarity".to_string(), "IB".to_string())]
+        );
+
+        // VB and VC swapped.
+        let mut channels = healthy();
+        channels[1].phase = "C".to_string();
+        channels[2].phase = "B".to_string();
+        channels.swap(1, 2);
+        let found = rules(channels);
+        assert_eq!(found[0], ("phase-rotation".to_string(), "VC".to_string()));
+        // The currents now disagree with the voltages of phases B and C.
+        assert!(
+            found
+                .iter()
+                .all(|(rule, _)| rule == "phase-rotation" || rule == "vi-angle")
+        );
+
+        // A neutral current channel wired the wrong way round.
+        let mut channels = healthy();
+        // An unbalanced load, so the neutral carries current.
+        channels[5] = three_phase("I", "A", 1000.0, [0.0, 0.0, 90.0]).remove(2);
+        let mut neutral = analog_channel(1, "IN", "A", "N", sine(500.0, 50.0, -90.0, 1000.0, 100));
+        neutral.circuit_component_being_monitored = "Line 1".to_string();
+        channels.push(neutral);
+        let found = rules(channels);
+        assert!(found.contains(&("current-sum".to_string(), "IN".to_string())));
+    }
+}
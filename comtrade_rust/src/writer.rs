@@ -0,0 +1,255 @@
+// comtrade_rust/src/writer.rs
+// This file contains the writer that turns a ComtradeInfo back into IEEE C37.111-1999 CFG and ASCII DAT files.
+// This file exists so resampled, merged or calculated recordings can be saved and opened in other COMTRADE tools.
+// RELEVANT FILES: comtrade_rust/src/resample.rs, comtrade_rust/src/config.rs, comtrade_rust/src/dsp.rs, comtrade_rust/src/lib.rs
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+use crate::dsp::{RAW_LIMIT, RawScaling, finite_range, rate_sections, uniform_rate};
+use crate::{ComtradeInfo, SerializableAnalogChannel, WasmComtradeError};
+
+/// The largest rounding error, relative to the range of a channel's values,
+/// for which its own conversion is kept.
+const QUANTISATION_TOLERANCE: f64 = 1e-4;
+
+/// The text of a recording written as COMTRADE files.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ComtradeFiles {
+    /// The configuration file.
+    pub cfg: String,
+    /// The ASCII data file.
+    pub dat: String,
+}
+
+/// Chooses the CFG conversion of one analog channel. Its own conversion is
+/// kept while the values are still whole raw counts within the declared
+/// min/max, e.g. for an unchanged channel; interpolated or derived values
+/// that would lose resolution or leave that range are spread over the
+/// 16-bit range instead.
+fn scaling_of(channel: &SerializableAnalogChannel) -> RawScaling {
+    let own = RawScaling {
+        multiplier: channel.multiplier,
+        offset: channel.offset_adder,
+        min: channel.min_value,
+        max: channel.max_value,
+    };
+    if channel.multiplier == 0.0 || !channel.multiplier.is_finite() {
+        return RawScaling::spanning(&channel.values);
+    }
+    let Some((low, high)) = finite_range(&channel.values) else {
+        return own;
+    };
+    let declared = |raw: f64| raw >= own.min && raw <= own.max && raw.abs() <= RAW_LIMIT;
+    let error = channel
+        .values
+        .iter()
+        .filter(|v| v.is_finite())
+        .map(|&v| (own.multiplier * own.raw(v) + own.offset - v).abs())
+        .fold(0.0, f64::max);
+    if declared(own.raw(low))
+        && declared(own.raw(high))
+        && error <= QUANTISATION_TOLERANCE * (high - low)
+    {
+        own
+    } else {
+        RawScaling::spanning(&channel.values)
+    }
+}
+
+/// Makes a CFG field safe by replacing the commas that separate fields.
+fn field(text: &str) -> String {
+    text.trim().replace(',', ";")
+}
+
+/// Formats Unix seconds as a CFG date and time, "dd/mm/yyyy,hh:mm:ss.ssssss".
+fn cfg_timestamp(time: f64) -> String {
+    chrono::DateTime::from_timestamp_micros((time * 1_000_000.0).round() as i64)
+        .map(|t| t.naive_utc().format("%d/%m/%Y,%H:%M:%S%.6f").to_string())
+        .unwrap_or_else(|| "01/01/1970,00:00:00.000000".to_string())
+}
+
+/// Writes a recording as a 1999 revision CFG file and an ASCII DAT file.
+///
+/// The analog channels keep their conversion factors when their values are
+/// still whole raw counts within the declared range, and are rescaled
+/// otherwise. The sampling rate sections are taken from the timestamps, and
+/// every sample also carries its time in microseconds. Missing (NaN) values
+/// are written as empty fields.
+pub fn write_comtrade(info: &ComtradeInfo) -> Result<ComtradeFiles, WasmComtradeError> {
+    let timestamps = &info.timestamps;
+    let Some(&start) = timestamps.first() else {
+        return Err(WasmComtradeError::AnalysisError(
+            "The recording has no samples to write.".to_string(),
+        ));
+    };
+    let samples = timestamps.len();
+    if let Some(channel) = info
+        .analog_channels
+        .iter()
+        .find(|c| c.values.len() != samples)
+    {
+        return Err(WasmComtradeError::AnalysisError(format!(
+            "Analog channel '{}' has {} samples but the recording has {}.",
+            channel.name,
+            channel.values.len(),
+            samples
+        )));
+    }
+    if let Some(channel) = info
+        .digital_channels
+        .iter()
+        .find(|c| c.values.len() != samples)
+    {
+        return Err(WasmComtradeError::AnalysisError(format!(
+            "Digital channel '{}' has {} samples but the recording has {}.",
+            channel.name,
+            channel.values.len(),
+            samples
+        )));
+    }
+
+    let analog = info.analog_channels.len();
+    let digital = info.digital_channels.len();
+    let scalings: Vec<RawScaling> = info.analog_channels.iter().map(scaling_of).collect();
+
+    let mut cfg = String::new();
+    let mut line = |text: String| {
+        cfg.push_str(&text);
+        cfg.push_str("\r\n");
+    };
+    line(format!(
+        "{},{},1999",
+        field(&info.station),
+        field(&info.recording_device_id)
+    ));
+    line(format!("{},{}A,{}D", analog + digital, analog, digital));
+    for (k, (channel, scaling)) in info.analog_channels.iter().zip(&scalings).enumerate() {
+        let scaling_mode = if channel.scaling_mode.eq_ignore_ascii_case("secondary") {
+            "S"
+        } else {
+            "P"
+        };
+        line(format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            k + 1,
+            field(&channel.name),
+            field(&channel.phase),
+            field(&channel.circuit_component_being_monitored),
+            field(&channel.units),
+            scaling.multiplier,
+            scaling.offset,
+            channel.skew,
+            scaling.min,
+            scaling.max,
+            channel.primary_factor,
+            channel.secondary_factor,
+            scaling_mode
+        ));
+    }
+    for (k, channel) in info.digital_channels.iter().enumerate() {
+        line(format!(
+            "{},{},{},,{}",
+            k + 1,
+            field(&channel.name),
+            field(&channel.phase),
+            channel.initial_value
+        ));
+    }
+    line(format!("{}", info.frequency));
+    let sections = rate_sections(timestamps);
+    let rates: Vec<Option<f64>> = sections
+        .iter()
+        .map(|&(s, e)| uniform_rate(timestamps, s, (e + 1).min(samples)))
+        .collect();
+    if rates.iter().all(Option::is_some) {
+        line(format!("{}", sections.len()));
+        for (&(_, end), rate) in sections.iter().zip(&rates) {
+            let rate = (rate.unwrap_or_default() * 1e6).round() / 1e6;
+            line(format!("{},{}", rate, end));
+        }
+    } else {
+        // A single sample has no rate; the timestamps alone place it.
+        line("0".to_string());
+        line(format!("0,{}", samples));
+    }
+    let trigger = if info.trigger_timestamp > 0.0 {
+        info.trigger_timestamp
+    } else {
+        start
+    };
+    line(cfg_timestamp(start));
+    line(cfg_timestamp(trigger));
+    line("ASCII".to_string());
+    line("1".to_string());
+
+    let mut dat = String::new();
+    for (i, &time) in timestamps.iter().enumerate() {
+        let _ = write!(dat, "{},{}", i + 1, ((time - start) * 1e6).round());
+        for (channel, scaling) in info.analog_channels.iter().zip(&scalings) {
+            let value = channel.values[i];
+            if value.is_finite() {
+                let _ = write!(dat, ",{}", scaling.raw(value));
+            } else {
+                dat.push(',');
+            }
+        }
+        for channel in &info.digital_channels {
+            let _ = write!(dat, ",{}", channel.values[i]);
+        }
+        dat.push_str("\r\n");
+    }
+
+    Ok(ComtradeFiles { cfg, dat })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analog_channel, digital_channel, recording};
+
+    #[test]
+    fn test_write_comtrade() {
+        let mut voltage = analog_channel(1, "VA, bus", "kV", "A", vec![0.0, 1.5, -2.0]);
+        voltage.multiplier = 0.5;
+        let current = analog_channel(2, "IA", "A", "A", vec![0.0, 1e6, f64::NAN]);
+        let mut trip = digital_channel(1, "Trip", 0);
+        trip.values = vec![0, 0, 1];
+        let mut info = recording(1000.0, 50.0, vec![voltage, current], vec![trip]);
+        info.station = "North".to_string();
+        info.timestamps = vec![1.0, 1.001, 1.002];
+
+        let files = write_comtrade(&info).unwrap();
+        let cfg: Vec<&str> = files.cfg.lines().collect();
+        assert_eq!(cfg[0], "North,,1999");
+        assert_eq!(cfg[1], "3,2A,1D");
+        assert_eq!(cfg[2], "1,VA; bus,A,,kV,0.5,0,0,-32767,32767,1,1,P");
+        // Values beyond the 16-bit range are rescaled.
+        assert_eq!(cfg[3], "2,IA,A,,A,15.625,500000,0,-32767,32767,1,1,P");
+        assert_eq!(cfg[4], "1,Trip,,,0");
+        assert_eq!(&cfg[5..8], ["50", "1", "1000,3"]);
+        assert_eq!(cfg[8], "01/01/1970,00:00:01.000000");
+        assert_eq!(&cfg[10..], ["ASCII", "1"]);
+
+        let dat: Vec<&str> = files.dat.lines().collect();
+        assert_eq!(dat, ["1,0,0,-32000,0", "2,1000,3,32000,0", "3,2000,-4,,1"]);
+
+        // Values between raw counts, or raw counts beyond the declared range,
+        // are rescaled as well.
+        let rescaled = |info: &ComtradeInfo| {
+            let files = write_comtrade(info).unwrap();
+            let cfg = files.cfg.lines().nth(2).unwrap().to_string();
+            !cfg.contains(",0.5,0,0,-32767,32767,")
+        };
+        assert!(!rescaled(&info));
+        let mut interpolated = info.clone();
+        interpolated.analog_channels[0].values[1] = 1.3;
+        assert!(rescaled(&interpolated));
+        let mut narrow = info.clone();
+        narrow.analog_channels[0].max_value = 2.0;
+        assert!(rescaled(&narrow));
+
+        info.digital_channels[0].values.pop();
+        assert!(write_comtrade(&info).is_err());
+    }
+}